@@ -0,0 +1,222 @@
+//! Parsing of the command line.
+
+use std::{
+    ffi::OsString,
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+use bendy::pretty::PrettyPrinter;
+
+/// What the tool was asked to do
+pub enum Command {
+    Print {
+        input: Input,
+        printer: PrettyPrinter,
+    },
+    Validate {
+        input: Input,
+    },
+    ToJson {
+        input: Input,
+        pretty: bool,
+    },
+    FromJson {
+        input: Input,
+    },
+    Get {
+        input: Input,
+        pointer: String,
+        raw: bool,
+    },
+    Stats {
+        input: Input,
+    },
+    Help,
+    Version,
+}
+
+/// Where to read the input from
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+/// A command line that doesn't match the usage
+#[derive(Debug)]
+pub struct UsageError(String);
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The name and whether it takes a value of every option of a command
+type OptionSpec = &'static [(&'static str, bool)];
+
+/// Parse the arguments following the name of the binary
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Command, UsageError> {
+    let mut args = args.into_iter();
+    let name = match args.next() {
+        Some(name) => name,
+        None => return Err(UsageError("missing command".to_owned())),
+    };
+
+    let command = match name.to_str() {
+        Some("print") => {
+            let parsed = Parsed::new(args, &[("max-string-width", true), ("max-depth", true)])?;
+            let mut printer = PrettyPrinter::new();
+            if let Some(width) = parsed.number("max-string-width")? {
+                printer = printer.with_max_string_width(width);
+            }
+            if let Some(depth) = parsed.number("max-depth")? {
+                printer = printer.with_max_depth(depth);
+            }
+            Command::Print {
+                input: parsed.input()?,
+                printer,
+            }
+        },
+        Some("validate") => Command::Validate {
+            input: Parsed::new(args, &[])?.input()?,
+        },
+        Some("to-json") => {
+            let parsed = Parsed::new(args, &[("pretty", false)])?;
+            Command::ToJson {
+                pretty: parsed.flag("pretty"),
+                input: parsed.input()?,
+            }
+        },
+        Some("from-json") => Command::FromJson {
+            input: Parsed::new(args, &[])?.input()?,
+        },
+        Some("get") => {
+            let mut parsed = Parsed::new(args, &[("raw", false)])?;
+            let pointer = parsed
+                .positional()
+                .ok_or_else(|| UsageError("missing path".to_owned()))?
+                .into_string()
+                .map_err(|_| UsageError("path is not valid UTF-8".to_owned()))?;
+            Command::Get {
+                raw: parsed.flag("raw"),
+                input: parsed.input()?,
+                pointer,
+            }
+        },
+        Some("stats") => Command::Stats {
+            input: Parsed::new(args, &[])?.input()?,
+        },
+        Some("help") | Some("--help") | Some("-h") => Command::Help,
+        Some("--version") | Some("-V") => Command::Version,
+        _ => {
+            return Err(UsageError(format!(
+                "unknown command `{}`",
+                name.to_string_lossy()
+            )))
+        },
+    };
+
+    Ok(command)
+}
+
+/// The options and positional arguments of a command
+struct Parsed {
+    options: Vec<(&'static str, Option<String>)>,
+    positionals: std::vec::IntoIter<OsString>,
+}
+
+impl Parsed {
+    fn new(args: impl Iterator<Item = OsString>, spec: OptionSpec) -> Result<Self, UsageError> {
+        let mut args = args.peekable();
+        let mut options = Vec::new();
+        let mut positionals = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let option = match arg.to_str() {
+                Some("--") => {
+                    positionals.extend(args.by_ref());
+                    break;
+                },
+                Some(option) if option.starts_with("--") => &option[2..],
+                _ => {
+                    positionals.push(arg);
+                    continue;
+                },
+            };
+
+            let (name, inline_value) = match option.find('=') {
+                Some(equals) => (&option[..equals], Some(option[equals + 1..].to_owned())),
+                None => (option, None),
+            };
+            let (name, takes_value) = spec
+                .iter()
+                .find(|(known, _)| *known == name)
+                .copied()
+                .ok_or_else(|| UsageError(format!("unknown option `--{}`", name)))?;
+
+            let value = match (takes_value, inline_value) {
+                (true, Some(value)) => Some(value),
+                (true, None) => {
+                    let value = args
+                        .next()
+                        .and_then(|value| value.into_string().ok())
+                        .ok_or_else(|| UsageError(format!("`--{}` requires a value", name)))?;
+                    Some(value)
+                },
+                (false, None) => None,
+                (false, Some(_)) => {
+                    return Err(UsageError(format!("`--{}` doesn't take a value", name)))
+                },
+            };
+            options.push((name, value));
+        }
+
+        Ok(Parsed {
+            options,
+            positionals: positionals.into_iter(),
+        })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| *option == name)
+    }
+
+    /// The value of the last occurrence of `name`, parsed as a number
+    fn number(&self, name: &str) -> Result<Option<usize>, UsageError> {
+        let value = self
+            .options
+            .iter()
+            .rev()
+            .find(|(option, _)| *option == name)
+            .and_then(|(_, value)| value.as_ref());
+
+        match value {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                UsageError(format!("`--{}` requires a number, not `{}`", name, value))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn positional(&mut self) -> Option<OsString> {
+        self.positionals.next()
+    }
+
+    /// The input file given as last positional argument, or stdin if it is missing or `-`
+    fn input(mut self) -> Result<Input, UsageError> {
+        let input = match self.positional() {
+            Some(path) if path == "-" => Input::Stdin,
+            Some(path) => Input::File(PathBuf::from(path)),
+            None => Input::Stdin,
+        };
+
+        match self.positional() {
+            Some(extra) => Err(UsageError(format!(
+                "unexpected argument `{}`",
+                extra.to_string_lossy()
+            ))),
+            None => Ok(input),
+        }
+    }
+}
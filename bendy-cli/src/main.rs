@@ -0,0 +1,257 @@
+//! Inspect, validate and convert bencode files.
+//!
+//! Every command reads a single bencode value from a file, or from stdin if the file is
+//! missing or `-`. Run `bendy help` for the list of commands.
+
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    fs,
+    io::{self, Read, Write},
+    process,
+};
+
+use bendy::{
+    decoding::{self, resolve_pointer, validate, ErrorKind, FromBencode, PathSegment, Statistics},
+    encoding::{self, ToBencode},
+    json,
+    pretty::PrettyPrinter,
+    state_tracker::StructureError,
+    value::Value,
+};
+
+use crate::args::{Command, Input};
+
+mod args;
+
+const USAGE: &str = "\
+Usage: bendy <command> [options] [file]
+
+Reads a single bencode value from `file`, or from stdin if it is missing or `-`.
+
+Commands:
+    print [--max-string-width N] [--max-depth N]
+                        Print the value in a human-readable form
+    validate            Check that the value is canonical bencode
+    to-json [--pretty]  Convert the value into JSON
+    from-json           Convert JSON into bencode, see `bendy::json` for the format
+    get [--raw] <path>  Print the value at a path like `/info/files/0/length`, or
+                        write it as bencode with `--raw`
+    stats               Print statistics about the structure of the value
+    help, --help, -h    Print this message
+    --version, -V       Print the version
+";
+
+fn main() {
+    let command = match args::parse(env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("bendy: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        },
+    };
+
+    match run(command) {
+        Ok(()) => {},
+        Err(error @ Failure::MalformedPointer(_)) => {
+            eprintln!("bendy: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        },
+        Err(error) => {
+            eprintln!("bendy: {}", error);
+            process::exit(1);
+        },
+    }
+}
+
+fn run(command: Command) -> Result<(), Failure> {
+    match command {
+        Command::Print { input, printer } => {
+            let input = read(&input)?;
+            println!("{}", printer.print(&input)?);
+        },
+        Command::Validate { input } => {
+            let input = read(&input)?;
+            let stats = check(&input)?;
+            println!("valid canonical bencode, {} bytes", stats.length);
+        },
+        Command::ToJson { input, pretty } => {
+            let input = read(&input)?;
+            check(&input)?;
+            let value = Value::from_bencode(&input)?;
+            let json = if pretty {
+                json::to_json_pretty(&value)
+            } else {
+                json::to_json(&value)
+            };
+            // Nesting too deeply is the only way converting into JSON can fail
+            println!("{}", json.map_err(|_| Failure::TooDeepForJson)?);
+        },
+        Command::FromJson { input } => {
+            let input = read(&input)?;
+            let text = String::from_utf8(input).map_err(|_| Failure::NotUtf8)?;
+            let value = json::from_json(&text)?;
+            write_raw(&value.to_bencode()?)?;
+        },
+        Command::Get {
+            input,
+            pointer,
+            raw,
+        } => {
+            let input = read(&input)?;
+            check(&input)?;
+            let value = match resolve_pointer(&input, &pointer) {
+                Ok(value) => value.ok_or(Failure::NotFound(pointer))?,
+                Err(error) => match error.kind() {
+                    ErrorKind::MalformedPointer(_) => {
+                        return Err(Failure::MalformedPointer(pointer))
+                    },
+                    _ => return Err(error.into()),
+                },
+            };
+            if raw {
+                write_raw(value)?;
+            } else {
+                println!("{}", PrettyPrinter::new().print(value)?);
+            }
+        },
+        Command::Stats { input } => {
+            let input = read(&input)?;
+            print_stats(&check(&input)?);
+        },
+        Command::Help => print!("{}", USAGE),
+        Command::Version => println!("bendy {}", env!("CARGO_PKG_VERSION")),
+    }
+    Ok(())
+}
+
+fn read(input: &Input) -> Result<Vec<u8>, Failure> {
+    match input {
+        Input::Stdin => {
+            let mut buffer = Vec::new();
+            io::stdin()
+                .read_to_end(&mut buffer)
+                .map_err(|error| Failure::Io("stdin".to_owned(), error))?;
+            Ok(buffer)
+        },
+        Input::File(path) => {
+            fs::read(path).map_err(|error| Failure::Io(path.display().to_string(), error))
+        },
+    }
+}
+
+fn write_raw(bytes: &[u8]) -> Result<(), Failure> {
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(bytes)
+        .and_then(|_| stdout.flush())
+        .map_err(|error| Failure::Io("stdout".to_owned(), error))
+}
+
+/// Check that `input` holds exactly one canonical value
+fn check(input: &[u8]) -> Result<Statistics, Failure> {
+    let stats = validate(input)?;
+    if stats.length != input.len() {
+        return Err(Failure::TrailingData(stats.length));
+    }
+    Ok(stats)
+}
+
+fn print_stats(stats: &Statistics) {
+    println!("length:         {} bytes", stats.length);
+    println!("max depth:      {}", stats.max_depth);
+    println!("dicts:          {}", stats.dicts);
+    println!("lists:          {}", stats.lists);
+    println!("integers:       {}", stats.integers);
+    println!("strings:        {}", stats.strings);
+    println!("largest string: {} bytes", stats.largest_string);
+}
+
+/// The reasons a command can fail
+enum Failure {
+    Io(String, io::Error),
+    Decoding(decoding::Error),
+    Encoding(encoding::Error),
+    Json(json::Error),
+    TooDeepForJson,
+    TrailingData(usize),
+    NotUtf8,
+    NotFound(String),
+    MalformedPointer(String),
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Failure::Io(name, error) => write!(f, "{}: {}", name, error),
+            Failure::Decoding(error) => {
+                write!(f, "invalid bencode: {}", error.kind())?;
+                // Syntax errors already mention their offset
+                let syntax = matches!(
+                    error.kind(),
+                    ErrorKind::StructureError(StructureError::SyntaxError(_))
+                );
+                if let (Some(offset), false) = (error.offset(), syntax) {
+                    write!(f, " at offset {}", offset)?;
+                }
+                if !error.path().is_empty() {
+                    write!(f, " in {}", pointer(error.path()))?;
+                }
+                Ok(())
+            },
+            Failure::Encoding(error) => write!(f, "{}", error),
+            Failure::Json(error) => write!(f, "invalid JSON input: {}", error),
+            Failure::TooDeepForJson => f.write_str("value is nested too deeply for JSON"),
+            Failure::TrailingData(offset) => {
+                write!(f, "invalid bencode: trailing data at offset {}", offset)
+            },
+            Failure::NotUtf8 => f.write_str("JSON input is not valid UTF-8"),
+            Failure::NotFound(pointer) => write!(f, "no value at `{}`", pointer),
+            Failure::MalformedPointer(pointer) => write!(
+                f,
+                "malformed path `{}`, expected a path like `/info/files/0/length`",
+                pointer
+            ),
+        }
+    }
+}
+
+impl From<decoding::Error> for Failure {
+    fn from(error: decoding::Error) -> Self {
+        Failure::Decoding(error)
+    }
+}
+
+impl From<encoding::Error> for Failure {
+    fn from(error: encoding::Error) -> Self {
+        Failure::Encoding(error)
+    }
+}
+
+impl From<json::Error> for Failure {
+    fn from(error: json::Error) -> Self {
+        Failure::Json(error)
+    }
+}
+
+/// Format `path` in the syntax accepted by `get`
+fn pointer(path: &[PathSegment]) -> String {
+    let mut pointer = String::new();
+    for segment in path {
+        pointer.push('/');
+        match segment {
+            PathSegment::Index(index) => pointer.push_str(&index.to_string()),
+            PathSegment::Key(key) => {
+                for &byte in key {
+                    match byte {
+                        b'~' => pointer.push_str("~0"),
+                        b'/' => pointer.push_str("~1"),
+                        b' '..=b'~' => pointer.push(char::from(byte)),
+                        _ => pointer.push_str(&format!("~x{:02x}", byte)),
+                    }
+                }
+            },
+        }
+    }
+    pointer
+}
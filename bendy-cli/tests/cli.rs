@@ -0,0 +1,161 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+const TORRENT: &[u8] = b"d8:announce3:url4:infod5:filesld6:lengthi5e4:pathl1:a1:beee\
+                         4:name1:x6:pieces2:\xab\xcdee";
+
+fn bendy(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bendy"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    assert!(output.status.success(), "{:?}", output);
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn print_should_render_the_value() {
+    let output = bendy(
+        &["print", "--max-depth=2", "--max-string-width", "2"],
+        TORRENT,
+    );
+    assert_eq!(
+        stdout(&output),
+        "{\n  \"an\"... (8 bytes): \"ur\"... (3 bytes),\n  \"in\"... (4 bytes): {\n    \
+         \"fi\"... (5 bytes): [... 1 item],\n    \"na\"... (4 bytes): \"x\",\n    \
+         \"pi\"... (6 bytes): <2 bytes: ab...>\n  }\n}\n"
+    );
+}
+
+#[test]
+fn validate_should_report_the_location_of_errors() {
+    assert_eq!(
+        stdout(&bendy(&["validate", "-"], TORRENT)),
+        "valid canonical bencode, 82 bytes\n"
+    );
+
+    let output = bendy(&["validate"], b"d1:ad1:bi1e1:ai2eee");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "bendy: invalid bencode: bencode encoding corrupted (Keys were not sorted) at offset \
+         11 in /a\n"
+    );
+
+    let output = bendy(&["validate"], b"i1x");
+    assert_eq!(
+        stderr(&output),
+        "bendy: invalid bencode: bencode encoding corrupted (Malformed number of unexpected \
+         character: Expected 'e' or '0'..'9', got 'x' at offset 2)\n"
+    );
+
+    let output = bendy(&["validate"], b"i1ei2e");
+    assert_eq!(
+        stderr(&output),
+        "bendy: invalid bencode: trailing data at offset 3\n"
+    );
+}
+
+#[test]
+fn json_should_round_trip() {
+    let json = bendy(&["to-json"], TORRENT);
+    assert_eq!(
+        stdout(&json),
+        "{\"announce\":\"url\",\"info\":{\"files\":[{\"length\":5,\"path\":[\"a\",\"b\"]}],\
+         \"name\":\"x\",\"pieces\":{\"$bytes\":\"abcd\"}}}\n"
+    );
+
+    let pretty = bendy(&["to-json", "--pretty"], TORRENT);
+    for json in [json, pretty].iter() {
+        let bencode = bendy(&["from-json"], &json.stdout);
+        assert!(bencode.status.success());
+        assert_eq!(bencode.stdout, TORRENT);
+    }
+
+    let output = bendy(&["from-json"], b"{\"a\": null}");
+    assert_eq!(
+        stderr(&output),
+        "bendy: invalid JSON input: JSON value without bencode counterpart at offset 6\n"
+    );
+
+    let deep = [&[b'l'; 513][..], &[b'e'; 513][..]].concat();
+    let output = bendy(&["to-json"], &deep);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "bendy: value is nested too deeply for JSON\n"
+    );
+}
+
+#[test]
+fn get_should_extract_values() {
+    let output = bendy(&["get", "/info/files/0/path"], TORRENT);
+    assert_eq!(stdout(&output), "[\n  \"a\",\n  \"b\"\n]\n");
+
+    let output = bendy(&["get", "--raw", "/info/pieces"], TORRENT);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"2:\xab\xcd");
+
+    let output = bendy(&["get", "/info/files/1"], TORRENT);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "bendy: no value at `/info/files/1`\n");
+
+    let output = bendy(&["get", "info"], TORRENT);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with(
+        "bendy: malformed path `info`, expected a path like `/info/files/0/length`\n\nUsage: bendy"
+    ));
+}
+
+#[test]
+fn stats_should_describe_the_structure() {
+    assert_eq!(
+        stdout(&bendy(&["stats"], TORRENT)),
+        "length:         82 bytes\nmax depth:      5\ndicts:          3\nlists:          2\n\
+         integers:       1\nstrings:        12\nlargest string: 8 bytes\n"
+    );
+}
+
+#[test]
+fn version_should_be_printed() {
+    for flag in ["--version", "-V"].iter() {
+        let output = bendy(&[flag], b"");
+        assert_eq!(
+            stdout(&output),
+            format!("bendy {}\n", env!("CARGO_PKG_VERSION"))
+        );
+    }
+}
+
+#[test]
+fn invalid_arguments_should_print_the_usage() {
+    for args in [
+        &["frobnicate"][..],
+        &[],
+        &["get"],
+        &["print", "--max-depth"],
+        &["print", "--max-depth", "x"],
+        &["to-json", "--raw"],
+        &["stats", "a", "b"],
+    ]
+    .iter()
+    {
+        let output = bendy(args, b"");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains("Usage: bendy"), "{:?}", args);
+    }
+}
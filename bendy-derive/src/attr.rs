@@ -0,0 +1,166 @@
+use syn::{meta::ParseNestedMeta, Attribute, ExprPath, Lit, LitStr};
+
+/// How a missing field is filled in while decoding
+pub enum DefaultValue {
+    /// Use `Default::default()`
+    Trait,
+    /// Call the given function
+    Path(ExprPath),
+}
+
+/// How the value of a field is represented in bencode
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// Use the `ToBencode` and `FromBencode` implementations of the field type
+    Plain,
+    /// Encode a byte container like `Vec<u8>` as a single byte string
+    AsString,
+    /// Encode a collection of byte containers like `Vec<Vec<u8>>` as a list of byte strings
+    Bytes,
+}
+
+/// The `#[bencode(...)]` attributes of a struct or enum
+#[derive(Default)]
+pub struct ContainerAttrs {
+    /// The key of the variant tag of an internally or adjacently tagged enum
+    pub tag: Option<Vec<u8>>,
+    /// The key of the variant content of an adjacently tagged enum
+    pub content: Option<Vec<u8>>,
+}
+
+/// The `#[bencode(...)]` attributes of an enum variant
+#[derive(Default)]
+pub struct VariantAttrs {
+    pub rename: Option<Vec<u8>>,
+}
+
+/// The `#[bencode(...)]` attributes of a single field
+pub struct FieldAttrs {
+    pub rename: Option<Vec<u8>>,
+    pub default: Option<DefaultValue>,
+    pub skip: bool,
+    pub skip_serializing_if: Option<ExprPath>,
+    pub format: Format,
+    /// Collect the pairs that don't belong to any other field
+    pub flatten: bool,
+}
+
+impl ContainerAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = ContainerAttrs::default();
+
+        parse_bencode_attrs(attrs, |meta| {
+            if meta.path.is_ident("tag") {
+                result.tag = Some(parse_key(&meta)?);
+            } else if meta.path.is_ident("content") {
+                result.content = Some(parse_key(&meta)?);
+            } else {
+                return Err(meta.error("unknown bencode container attribute"));
+            }
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+}
+
+impl VariantAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = VariantAttrs::default();
+
+        parse_bencode_attrs(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                result.rename = Some(parse_key(&meta)?);
+            } else {
+                return Err(meta.error("unknown bencode variant attribute"));
+            }
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+}
+
+impl FieldAttrs {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = FieldAttrs {
+            rename: None,
+            default: None,
+            skip: false,
+            skip_serializing_if: None,
+            format: Format::Plain,
+            flatten: false,
+        };
+
+        parse_bencode_attrs(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                result.rename = Some(parse_key(&meta)?);
+            } else if meta.path.is_ident("default") {
+                result.default = Some(if meta.input.peek(syn::Token![=]) {
+                    DefaultValue::Path(parse_path(meta.value()?.parse()?)?)
+                } else {
+                    DefaultValue::Trait
+                });
+            } else if meta.path.is_ident("skip") {
+                result.skip = true;
+            } else if meta.path.is_ident("skip_serializing_if") {
+                result.skip_serializing_if = Some(parse_path(meta.value()?.parse()?)?);
+            } else if meta.path.is_ident("as_string") {
+                result.set_format(Format::AsString, &meta)?;
+            } else if meta.path.is_ident("bytes") {
+                result.set_format(Format::Bytes, &meta)?;
+            } else if meta.path.is_ident("flatten") {
+                result.flatten = true;
+            } else {
+                return Err(meta.error("unknown bencode field attribute"));
+            }
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// Whether any attribute besides `flatten` is set
+    pub fn has_options(&self) -> bool {
+        self.rename.is_some()
+            || self.default.is_some()
+            || self.skip
+            || self.skip_serializing_if.is_some()
+            || self.format != Format::Plain
+    }
+
+    fn set_format(&mut self, format: Format, meta: &ParseNestedMeta) -> syn::Result<()> {
+        if self.format != Format::Plain {
+            return Err(meta.error("`as_string` and `bytes` can't be combined"));
+        }
+        self.format = format;
+        Ok(())
+    }
+}
+
+fn parse_bencode_attrs(
+    attrs: &[Attribute],
+    mut parse: impl FnMut(ParseNestedMeta) -> syn::Result<()>,
+) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bencode")) {
+        attr.parse_nested_meta(&mut parse)?;
+    }
+    Ok(())
+}
+
+/// Parse a dictionary key or tag given as string or byte string literal
+fn parse_key(meta: &ParseNestedMeta) -> syn::Result<Vec<u8>> {
+    match meta.value()?.parse()? {
+        Lit::Str(literal) => Ok(literal.value().into_bytes()),
+        Lit::ByteStr(literal) => Ok(literal.value()),
+        literal => Err(syn::Error::new_spanned(
+            literal,
+            "expected a string or byte string literal",
+        )),
+    }
+}
+
+/// Parse the path to a function given as string literal, e.g. `"Vec::is_empty"`
+fn parse_path(literal: LitStr) -> syn::Result<ExprPath> {
+    literal.parse()
+}
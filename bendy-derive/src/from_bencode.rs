@@ -0,0 +1,395 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::{
+    attr::{DefaultValue, Format},
+    model::{self, byte_str, Field, Shape, Tagging, Variant, VariantKind},
+};
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let shape = model::parse(input)?;
+
+    let name = &input.ident;
+    let generics = model::add_bounds(&input.generics, quote!(::bendy::decoding::FromBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (depth, body) = match &shape {
+        Shape::Dict(fields) => {
+            let value = decode_dict(fields, quote!(object), quote!(Self), None);
+            (
+                model::dict_depth(fields, value_depth),
+                quote!(::core::result::Result::Ok(#value)),
+            )
+        },
+        Shape::Newtype(field) => {
+            let value = decode_value(field, quote!(object));
+            (
+                value_depth(field),
+                quote!(::core::result::Result::Ok(Self(#value))),
+            )
+        },
+        Shape::Enum(variants, tagging) => (
+            model::enum_depth(variants, tagging, value_depth),
+            decode_enum(variants, tagging),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::bendy::decoding::FromBencode for #name #ty_generics #where_clause {
+            const EXPECTED_RECURSION_DEPTH: usize = #depth;
+
+            fn decode_bencode_object(
+                object: ::bendy::decoding::Object,
+            ) -> ::core::result::Result<Self, ::bendy::decoding::Error> {
+                #body
+            }
+        }
+    })
+}
+
+/// The maximum depth of the encoded value of `field`
+fn value_depth(field: &Field) -> TokenStream {
+    let ty = field.value_type();
+    match field.attrs.format {
+        // The pairs of a flattened map are part of the surrounding dictionary
+        Format::Plain if field.attrs.flatten => {
+            quote!(<#ty as ::bendy::decoding::FromBencode>::EXPECTED_RECURSION_DEPTH - 1)
+        },
+        Format::Plain => quote!(<#ty as ::bendy::decoding::FromBencode>::EXPECTED_RECURSION_DEPTH),
+        Format::AsString => quote!(0),
+        Format::Bytes => quote!(1),
+    }
+}
+
+/// An expression decoding `object` into the value type of `field`
+fn decode_value(field: &Field, object: TokenStream) -> TokenStream {
+    let ty = field.value_type();
+    match field.attrs.format {
+        Format::Plain => {
+            quote!(<#ty as ::bendy::decoding::FromBencode>::decode_bencode_object(#object)?)
+        },
+        Format::AsString => quote!(::bendy::derive_support::decode_as_string::<#ty>(#object)?),
+        Format::Bytes => quote!(::bendy::derive_support::decode_byte_strings::<#ty, _>(#object)?),
+    }
+}
+
+/// An expression producing the value of a field that wasn't decoded
+fn default_value(field: &Field) -> TokenStream {
+    match &field.attrs.default {
+        Some(DefaultValue::Trait) => quote!(::core::default::Default::default()),
+        Some(DefaultValue::Path(path)) => quote!(#path()),
+        None if field.attrs.skip => quote!(::core::default::Default::default()),
+        None if field.is_option() => quote!(::core::option::Option::None),
+        None => {
+            let key = field.key_name();
+            quote!(return ::core::result::Result::Err(::bendy::decoding::Error::missing_field(#key)))
+        },
+    }
+}
+
+/// An expression decoding the dictionary `object` into `constructor` with `fields`.
+/// The pair under the key `ignored` is never collected by a flattened field.
+fn decode_dict(
+    fields: &[Field],
+    object: TokenStream,
+    constructor: TokenStream,
+    ignored: Option<&[u8]>,
+) -> TokenStream {
+    let slots = model::bindings(fields);
+    let pairs: Vec<_> = fields
+        .iter()
+        .zip(&slots)
+        .filter(|(field, _)| field.is_pair())
+        .collect();
+    let unknown = fields
+        .iter()
+        .zip(&slots)
+        .find(|(field, _)| field.attrs.flatten);
+
+    let declarations = pairs.iter().map(|(field, slot)| {
+        let ty = field.value_type();
+        quote!(let mut #slot: ::core::option::Option<#ty> = ::core::option::Option::None;)
+    });
+
+    let unknown_declaration = unknown.map(|(field, slot)| {
+        let ty = field.ty;
+        quote!(let mut #slot: #ty = ::core::default::Default::default();)
+    });
+
+    let arms = pairs.iter().map(|(field, slot)| {
+        let key = byte_str(&field.key);
+        let value = decode_value(field, quote!(value));
+        quote! {
+            #key => {
+                #slot = ::core::option::Option::Some(#value);
+            },
+        }
+    });
+
+    let unknown_arms = match unknown {
+        Some((_, slot)) => {
+            let ignored = ignored.map(|key| {
+                let key = byte_str(key);
+                quote!(#key => (),)
+            });
+            quote! {
+                #ignored
+                _ => {
+                    let size = ::bendy::derive_support::decode_unknown(&mut #slot, key, value)?;
+                    dict.charge_allocation(size)?;
+                },
+            }
+        },
+        None => quote! {
+            // Unknown keys are ignored
+            _ => (),
+        },
+    };
+
+    let initializers = fields.iter().zip(&slots).map(|(field, slot)| {
+        let member = &field.member;
+        let default = default_value(field);
+        if field.attrs.flatten {
+            quote!(#member: #slot,)
+        } else if field.attrs.skip {
+            quote!(#member: #default,)
+        } else if field.is_option() && field.attrs.default.is_none() {
+            quote!(#member: #slot,)
+        } else if field.is_option() {
+            quote! {
+                #member: match #slot {
+                    ::core::option::Option::Some(value) => ::core::option::Option::Some(value),
+                    ::core::option::Option::None => #default,
+                },
+            }
+        } else {
+            quote! {
+                #member: match #slot {
+                    ::core::option::Option::Some(value) => value,
+                    ::core::option::Option::None => #default,
+                },
+            }
+        }
+    });
+
+    let read_pairs = if pairs.is_empty() && unknown.is_none() {
+        quote!(while dict.next_pair()?.is_some() {})
+    } else {
+        quote! {
+            loop {
+                let (key, value) = match dict.next_pair()? {
+                    ::core::option::Option::Some(pair) => pair,
+                    ::core::option::Option::None => break,
+                };
+
+                match key {
+                    #(#arms)*
+                    #unknown_arms
+                }
+            }
+        }
+    };
+
+    quote! {{
+        #(#declarations)*
+        #unknown_declaration
+
+        let mut dict = #object.try_into_dictionary()?;
+        #read_pairs
+
+        #constructor {
+            #(#initializers)*
+        }
+    }}
+}
+
+fn decode_enum(variants: &[Variant], tagging: &Tagging) -> TokenStream {
+    match tagging {
+        Tagging::External => decode_externally_tagged(variants),
+        Tagging::Internal { tag } => {
+            let arms = variants.iter().map(|variant| {
+                let variant_tag = byte_str(&variant.tag);
+                let ident = &variant.ident;
+                let value = match &variant.kind {
+                    VariantKind::Struct(fields) => {
+                        decode_dict(fields, quote!(object), quote!(Self::#ident), Some(tag))
+                    },
+                    // The tag is the only key, all others are ignored like for structs
+                    _ => decode_dict(&[], quote!(object), quote!(Self::#ident), None),
+                };
+                quote!(#variant_tag => #value,)
+            });
+
+            decode_tagged(variants, tag, quote!(object), quote!(), quote!(#(#arms)*))
+        },
+        Tagging::Adjacent { tag, content } => {
+            let content_name = String::from_utf8_lossy(content).into_owned();
+            let content = byte_str(content);
+            let arms = variants.iter().map(|variant| {
+                let tag = byte_str(&variant.tag);
+                let value = match decode_content(variant, quote!(value)) {
+                    // Any content of a unit variant is ignored
+                    None => {
+                        let ident = &variant.ident;
+                        quote!(Self::#ident)
+                    },
+                    Some(value) => quote! {
+                        loop {
+                            match dict.next_pair()? {
+                                ::core::option::Option::Some((#content, value)) => break #value,
+                                ::core::option::Option::Some(_) => (),
+                                ::core::option::Option::None => {
+                                    return ::core::result::Result::Err(
+                                        ::bendy::decoding::Error::missing_field(#content_name),
+                                    )
+                                },
+                            }
+                        }
+                    },
+                };
+                quote!(#tag => #value,)
+            });
+
+            let has_content = variants
+                .iter()
+                .any(|variant| !matches!(variant.kind, VariantKind::Unit));
+            let (object, prelude) = if has_content {
+                (
+                    quote!(object),
+                    quote!(let mut dict = object.try_into_dictionary()?;),
+                )
+            } else {
+                (quote!(_), quote!())
+            };
+
+            decode_tagged(variants, tag, object, prelude, quote!(#(#arms)*))
+        },
+    }
+}
+
+/// An expression decoding the content of `variant` from `object`, unless it has none
+fn decode_content(variant: &Variant, object: TokenStream) -> Option<TokenStream> {
+    let ident = &variant.ident;
+    match &variant.kind {
+        VariantKind::Unit => None,
+        VariantKind::Newtype(field) => {
+            let value = decode_value(field, object);
+            Some(quote!(Self::#ident(#value)))
+        },
+        VariantKind::Struct(fields) => {
+            Some(decode_dict(fields, object, quote!(Self::#ident), None))
+        },
+    }
+}
+
+/// Decode an enum whose tag is stored under `key` in a dictionary. The dictionary is
+/// bound to `object`, then `prelude` runs before `arms` match the tag to a variant.
+fn decode_tagged(
+    variants: &[Variant],
+    key: &[u8],
+    object: TokenStream,
+    prelude: TokenStream,
+    arms: TokenStream,
+) -> TokenStream {
+    let key = byte_str(key);
+    let expected = model::expected_tags(variants.iter());
+
+    quote! {
+        ::bendy::derive_support::decode_tagged(
+            object,
+            #key,
+            |tag, #object| {
+                #prelude
+                ::core::result::Result::Ok(match tag {
+                    #arms
+                    _ => {
+                        return ::core::result::Result::Err(
+                            ::bendy::derive_support::unknown_variant(tag, #expected),
+                        )
+                    },
+                })
+            },
+        )
+    }
+}
+
+fn decode_externally_tagged(variants: &[Variant]) -> TokenStream {
+    let (units, others): (Vec<&Variant>, Vec<&Variant>) = variants
+        .iter()
+        .partition(|variant| matches!(variant.kind, VariantKind::Unit));
+
+    let mut arms = Vec::new();
+    let mut expected_kinds = Vec::new();
+
+    if !units.is_empty() {
+        let expected = model::expected_tags(units.iter().copied());
+        let unit_arms = units.iter().map(|variant| {
+            let tag = byte_str(&variant.tag);
+            let ident = &variant.ident;
+            quote!(#tag => ::core::result::Result::Ok(Self::#ident),)
+        });
+
+        arms.push(quote! {
+            ::bendy::decoding::Object::Bytes(tag) => match tag {
+                #(#unit_arms)*
+                _ => ::core::result::Result::Err(
+                    ::bendy::derive_support::unknown_variant(tag, #expected),
+                ),
+            },
+        });
+        expected_kinds.push("String");
+    }
+
+    if !others.is_empty() {
+        let expected = model::expected_tags(others.iter().copied());
+        let content_arms = others.iter().map(|variant| {
+            let tag = byte_str(&variant.tag);
+            let value = decode_content(variant, quote!(value));
+            quote!(#tag => #value,)
+        });
+
+        arms.push(quote! {
+            ::bendy::decoding::Object::Dict(mut dict) => {
+                let (tag, value) = match dict.next_pair()? {
+                    ::core::option::Option::Some(pair) => pair,
+                    ::core::option::Option::None => {
+                        return ::core::result::Result::Err(
+                            ::bendy::decoding::Error::unexpected_token(#expected, "empty Dict"),
+                        )
+                    },
+                };
+
+                let variant = match tag {
+                    #(#content_arms)*
+                    _ => {
+                        return ::core::result::Result::Err(
+                            ::bendy::derive_support::unknown_variant(tag, #expected),
+                        )
+                    },
+                };
+
+                // The tag has to be the only key
+                if let ::core::option::Option::Some((key, _)) = dict.next_pair()? {
+                    return ::core::result::Result::Err(
+                        ::bendy::derive_support::unexpected_key(key),
+                    );
+                }
+
+                ::core::result::Result::Ok(variant)
+            },
+        });
+        expected_kinds.push("Dict");
+    }
+
+    let expected_kinds = expected_kinds.join(" or ");
+    quote! {
+        match object {
+            #(#arms)*
+            other => ::core::result::Result::Err(::bendy::decoding::Error::unexpected_token(
+                #expected_kinds,
+                other.into_token().name(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Derive macros for the [`ToBencode`] and [`FromBencode`] traits of bendy, and the
+//! [`bencode!`] and [`bencode_bytes!`] macros to write values as literals.
+//!
+//! Use them through the `derive` feature of bendy, which re-exports the derive macros
+//! next to the traits they implement and the other macros at its crate root.
+//!
+//! Structs with named fields are encoded as dictionaries with one key per field, and
+//! newtypes are encoded like the value they wrap. Enums are covered [below](#enums).
+//! Fields of type `Option<T>` are left out of the dictionary if they are `None`, and are
+//! `None` if their key is missing. Keys that don't belong to any field are ignored while
+//! decoding, unless a field is flattened.
+//!
+//! The encoding of a field can be customized with `#[bencode(...)]` attributes:
+//!
+//! - `rename = "key"` uses `key` instead of the field name as dictionary key. Keys can
+//!   also be given as byte strings, like `rename = b"\xff"`.
+//! - `default` fills in `Default::default()` if the key is missing, and
+//!   `default = "path"` calls the function at `path` instead.
+//! - `skip` neither encodes nor decodes the field. It is always `Default::default()`
+//!   after decoding, or the result of the function given with `default = "path"`.
+//! - `skip_serializing_if = "path"` leaves the field out of the dictionary if the
+//!   function at `path` returns `true` for a reference to it.
+//! - `as_string` encodes a byte container like `Vec<u8>` as a single byte string
+//!   instead of a list of integers. The field type has to implement `AsRef<[u8]>` and
+//!   `From<&[u8]>`.
+//! - `bytes` encodes a collection of byte containers like `Vec<Vec<u8>>` as a list of
+//!   byte strings.
+//! - `flatten` collects the pairs whose keys don't belong to any other field while
+//!   decoding, and merges them back in between the other fields while encoding, so
+//!   unknown keys survive a round trip. The field has to be a `BTreeMap<K, V>` with keys
+//!   like `Vec<u8>` that sort like their bytes, typically `BTreeMap<Vec<u8>, Value>`.
+//!   At most one field can be flattened, and it takes no other attributes.
+//!
+//! ```
+//! use bendy::{decoding::FromBencode, encoding::ToBencode};
+//!
+//! #[derive(ToBencode, FromBencode, PartialEq, Debug)]
+//! struct Info {
+//!     name: String,
+//!     #[bencode(rename = "piece length")]
+//!     piece_length: u64,
+//!     #[bencode(as_string)]
+//!     pieces: Vec<u8>,
+//!     #[bencode(default, skip_serializing_if = "Vec::is_empty")]
+//!     files: Vec<String>,
+//! }
+//!
+//! let info = Info {
+//!     name: "debian.iso".to_owned(),
+//!     piece_length: 262_144,
+//!     pieces: vec![0xab; 4],
+//!     files: Vec::new(),
+//! };
+//!
+//! let encoded = info.to_bencode().unwrap();
+//! assert_eq!(
+//!     encoded,
+//!     &b"d4:name10:debian.iso12:piece lengthi262144e6:pieces4:\xab\xab\xab\xabe"[..]
+//! );
+//! assert_eq!(Info::from_bencode(&encoded).unwrap(), info);
+//! ```
+//!
+//!
+//! # Enums
+//!
+//! Every variant is identified by a byte string, its tag. The tag is the name of the
+//! variant unless it is set with `#[bencode(rename = "tag")]` on the variant, which
+//! accepts byte strings as well. Variants may have named fields, which take the same
+//! attributes as the fields of structs, a single unnamed field, or no fields at all.
+//!
+//! Attributes on the enum select where the tag is stored:
+//!
+//! - By default, enums are externally tagged. Variants without fields are encoded as
+//!   their tag, all others as a dictionary with the tag as its only key and the content
+//!   of the variant as its value.
+//! - `tag = "key"` stores the tag under `key`, next to the fields of the variant in a
+//!   single dictionary. Variants with a single unnamed field aren't supported.
+//! - `tag = "key", content = "other"` stores the tag under `key` and the content of the
+//!   variant under `other`. Variants without fields have no content.
+//!
+//! ```
+//! use bendy::{decoding::FromBencode, encoding::ToBencode};
+//!
+//! #[derive(ToBencode, FromBencode, PartialEq, Debug)]
+//! #[bencode(tag = "y")]
+//! enum Message {
+//!     #[bencode(rename = "q")]
+//!     Query {
+//!         #[bencode(rename = "t", as_string)]
+//!         transaction: Vec<u8>,
+//!         #[bencode(rename = "q")]
+//!         method: String,
+//!     },
+//!     #[bencode(rename = "e")]
+//!     Error {
+//!         #[bencode(rename = "t", as_string)]
+//!         transaction: Vec<u8>,
+//!         #[bencode(rename = "e")]
+//!         message: String,
+//!     },
+//! }
+//!
+//! let query = Message::Query {
+//!     transaction: b"aa".to_vec(),
+//!     method: "ping".to_owned(),
+//! };
+//!
+//! let encoded = query.to_bencode().unwrap();
+//! assert_eq!(encoded, &b"d1:q4:ping1:t2:aa1:y1:qe"[..]);
+//! assert_eq!(Message::from_bencode(&encoded).unwrap(), query);
+//! ```
+//!
+//! [`ToBencode`]: https://docs.rs/bendy/latest/bendy/encoding/trait.ToBencode.html
+//! [`FromBencode`]: https://docs.rs/bendy/latest/bendy/decoding/trait.FromBencode.html
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod attr;
+mod from_bencode;
+mod literal;
+mod model;
+mod to_bencode;
+
+/// Derive `bendy::encoding::ToBencode`. See the [crate documentation](crate) for the
+/// supported attributes.
+#[proc_macro_derive(ToBencode, attributes(bencode))]
+pub fn derive_to_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_bencode::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `bendy::decoding::FromBencode`. See the [crate documentation](crate) for the
+/// supported attributes.
+#[proc_macro_derive(FromBencode, attributes(bencode))]
+pub fn derive_from_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_bencode::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Construct a `bendy::value::Value` from a literal written like JSON.
+///
+/// - Integers of any size, like `42` or `-7`, become `Value::Integer`.
+/// - Strings like `"text"` and byte strings like `b"\x00\x01"` become `Value::Bytes`.
+/// - `[...]` holds the items of a `Value::List`.
+/// - `{...}` holds the `key: value` pairs of a `Value::Dict`. Keys have to be string or
+///   byte string literals, and using the same key twice is a compile error.
+/// - Any other expression is converted with `Value::from`. Wrap it in parentheses if
+///   it contains commas.
+///
+/// Literal strings and byte strings are borrowed, so a value without interpolated
+/// expressions is a `Value<'static>`.
+///
+/// ```
+/// use bendy::{bencode, encoding::ToBencode};
+///
+/// let name = "debian.iso";
+/// let torrent = bencode!({
+///     "info": { "name": name, "piece length": 262144 },
+///     "url-list": [b"http://\xff", "http://mirror"],
+/// });
+///
+/// assert_eq!(torrent["info"]["name"], bencode!("debian.iso"));
+/// assert_eq!(
+///     torrent.to_bencode().unwrap(),
+///     &b"d4:infod4:name10:debian.iso12:piece lengthi262144ee\
+///        8:url-listl8:http://\xff13:http://mirroree"[..]
+/// );
+/// ```
+///
+/// ```compile_fail
+/// let value = bendy::bencode!({ "a": 1, b"a": 2 });
+/// ```
+#[proc_macro]
+pub fn bencode(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as literal::Node).to_value().into()
+}
+
+/// Encode a literal written like JSON at compile time, and expand to a byte string
+/// literal holding the result.
+///
+/// Accepts the same syntax as [`bencode!`], except that all values have to be literals.
+/// Dictionary keys are sorted, so the result is always canonical.
+///
+/// ```
+/// use bendy::bencode_bytes;
+///
+/// assert_eq!(
+///     bencode_bytes!({ "foo": [1, 2, 3], "bar": -1 }),
+///     b"d3:bari-1e3:fooli1ei2ei3eee"
+/// );
+/// ```
+#[proc_macro]
+pub fn bencode_bytes(input: TokenStream) -> TokenStream {
+    let node = parse_macro_input!(input as literal::Node);
+    literal::expand_bytes(&node)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
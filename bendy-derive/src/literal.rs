@@ -0,0 +1,228 @@
+use proc_macro2::{Delimiter, Literal, Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    Lit, LitByteStr, LitInt, LitStr, Token,
+};
+
+/// A value written in the syntax of `bencode!`
+pub enum Node {
+    /// An integer literal, as decimal digits without leading zeros
+    Integer {
+        negative: bool,
+        digits: String,
+    },
+    /// A string or byte string literal
+    Bytes(Vec<u8>),
+    List(Vec<Node>),
+    /// A dictionary, with its keys sorted and unique
+    Dict(Vec<(Vec<u8>, Node)>),
+    /// Any other expression, converted into a `Value` at runtime
+    Expr(TokenStream),
+}
+
+impl Parse for Node {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let node = parse_value(input)?;
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after the value"));
+        }
+        Ok(node)
+    }
+}
+
+/// Parse the tokens up to the next `,` at the current level as a value
+fn parse_value(input: ParseStream) -> syn::Result<Node> {
+    let span = input.span();
+    let mut tokens = Vec::new();
+    while !input.is_empty() && !input.peek(Token![,]) {
+        tokens.push(input.parse::<TokenTree>()?);
+    }
+
+    match tokens.as_slice() {
+        [] => Err(syn::Error::new(span, "expected a value")),
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => {
+            syn::parse2(group.stream()).map(|List(items)| Node::List(items))
+        },
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Brace => {
+            syn::parse2(group.stream()).map(|Dict(pairs)| Node::Dict(pairs))
+        },
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Parenthesis => {
+            Ok(Node::Expr(group.stream()))
+        },
+        [TokenTree::Literal(literal)] => Ok(literal_node(false, literal)
+            .unwrap_or_else(|| Node::Expr(tokens.into_iter().collect()))),
+        [TokenTree::Punct(minus), TokenTree::Literal(literal)] if minus.as_char() == '-' => {
+            match literal_node(true, literal) {
+                Some(node @ Node::Integer { .. }) => Ok(node),
+                _ => Ok(Node::Expr(tokens.into_iter().collect())),
+            }
+        },
+        _ => Ok(Node::Expr(tokens.into_iter().collect())),
+    }
+}
+
+/// The node for an integer, string or byte string literal
+fn literal_node(negative: bool, literal: &Literal) -> Option<Node> {
+    match Lit::new(literal.clone()) {
+        Lit::Int(int) => {
+            let digits = int.base10_digits().trim_start_matches('0');
+            let digits = if digits.is_empty() { "0" } else { digits };
+            Some(Node::Integer {
+                negative: negative && digits != "0",
+                digits: digits.to_owned(),
+            })
+        },
+        Lit::Str(string) if !negative => Some(Node::Bytes(string.value().into_bytes())),
+        Lit::ByteStr(bytes) if !negative => Some(Node::Bytes(bytes.value())),
+        _ => None,
+    }
+}
+
+/// The comma separated items within `[...]`
+struct List(Vec<Node>);
+
+impl Parse for List {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            items.push(parse_value(input)?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(List(items))
+    }
+}
+
+/// The comma separated `key: value` pairs within `{...}`
+struct Dict(Vec<(Vec<u8>, Node)>);
+
+impl Parse for Dict {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut pairs: Vec<(Vec<u8>, Node)> = Vec::new();
+        while !input.is_empty() {
+            let key = parse_key(input)?;
+            input.parse::<Token![:]>()?;
+            let value = parse_value(input)?;
+
+            match pairs.binary_search_by(|(other, _)| other.cmp(&key.0)) {
+                Ok(_) => {
+                    return Err(syn::Error::new(
+                        key.1,
+                        format!(
+                            "duplicate key `{}` in dictionary",
+                            String::from_utf8_lossy(&key.0)
+                        ),
+                    ))
+                },
+                Err(position) => pairs.insert(position, (key.0, value)),
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(Dict(pairs))
+    }
+}
+
+fn parse_key(input: ParseStream) -> syn::Result<(Vec<u8>, Span)> {
+    if input.peek(LitStr) {
+        let key = input.parse::<LitStr>()?;
+        Ok((key.value().into_bytes(), key.span()))
+    } else if input.peek(LitByteStr) {
+        let key = input.parse::<LitByteStr>()?;
+        Ok((key.value(), key.span()))
+    } else {
+        Err(input.error("dictionary keys have to be string or byte string literals"))
+    }
+}
+
+impl Node {
+    /// The expression building the `Value` this node describes
+    pub fn to_value(&self) -> TokenStream {
+        match self {
+            Node::Integer { negative, digits } => {
+                let sign = if *negative { "-" } else { "" };
+                let integer = format!("{}{}", sign, digits);
+                if integer.parse::<i128>().is_ok() {
+                    let sign = if *negative { Some(quote!(-)) } else { None };
+                    let digits = LitInt::new(&format!("{}i128", digits), Span::call_site());
+                    quote!(::bendy::value::Value::from(#sign #digits))
+                } else {
+                    quote!(::bendy::derive_support::big_integer(#integer))
+                }
+            },
+            Node::Bytes(bytes) => {
+                let bytes = LitByteStr::new(bytes, Span::call_site());
+                quote!(::bendy::value::Value::from(#bytes))
+            },
+            Node::List(items) => {
+                let items = items.iter().map(Node::to_value);
+                quote!(::bendy::derive_support::list([#(#items),*]))
+            },
+            Node::Dict(pairs) => {
+                let pairs = pairs.iter().map(|(key, value)| {
+                    let key = LitByteStr::new(key, Span::call_site());
+                    let value = value.to_value();
+                    quote!((&#key[..], #value))
+                });
+                quote!(::bendy::derive_support::dict([#(#pairs),*]))
+            },
+            Node::Expr(expr) => quote!(::bendy::value::Value::from(#expr)),
+        }
+    }
+
+    /// Append the encoding of this node to `buffer`. Fails for expressions, which are
+    /// only known at runtime.
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> syn::Result<()> {
+        match self {
+            Node::Integer { negative, digits } => {
+                buffer.push(b'i');
+                if *negative {
+                    buffer.push(b'-');
+                }
+                buffer.extend_from_slice(digits.as_bytes());
+                buffer.push(b'e');
+            },
+            Node::Bytes(bytes) => encode_bytes(bytes, buffer),
+            Node::List(items) => {
+                buffer.push(b'l');
+                for item in items {
+                    item.encode(buffer)?;
+                }
+                buffer.push(b'e');
+            },
+            Node::Dict(pairs) => {
+                buffer.push(b'd');
+                for (key, value) in pairs {
+                    encode_bytes(key, buffer);
+                    value.encode(buffer)?;
+                }
+                buffer.push(b'e');
+            },
+            Node::Expr(expr) => {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "`bencode_bytes!` only accepts literals, use `bencode!` to interpolate \
+                     expressions",
+                ))
+            },
+        }
+        Ok(())
+    }
+}
+
+fn encode_bytes(bytes: &[u8], buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(bytes.len().to_string().as_bytes());
+    buffer.push(b':');
+    buffer.extend_from_slice(bytes);
+}
+
+/// The byte string literal holding the encoding of `node`
+pub fn expand_bytes(node: &Node) -> syn::Result<TokenStream> {
+    let mut buffer = Vec::new();
+    node.encode(&mut buffer)?;
+    Ok(LitByteStr::new(&buffer, Span::call_site()).into_token_stream())
+}
@@ -0,0 +1,403 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Data, DataEnum, DeriveInput, Fields, FieldsNamed, GenericArgument, Generics,
+    Ident, LitByteStr, Member, PathArguments, Type,
+};
+
+use crate::attr::{ContainerAttrs, FieldAttrs, VariantAttrs};
+
+/// A field of the type a trait is derived for
+pub struct Field<'a> {
+    pub member: Member,
+    pub ty: &'a Type,
+    /// The dictionary key of the field, empty for newtypes
+    pub key: Vec<u8>,
+    pub attrs: FieldAttrs,
+}
+
+/// The bencode representation of the type a trait is derived for
+pub enum Shape<'a> {
+    /// A struct with named fields, encoded as dictionary
+    Dict(Vec<Field<'a>>),
+    /// A tuple struct with a single field, encoded like that field
+    Newtype(Box<Field<'a>>),
+    /// An enum, encoded as one of its variants along with their tag
+    Enum(Vec<Variant<'a>>, Tagging),
+}
+
+/// A variant of an enum
+pub struct Variant<'a> {
+    pub ident: Ident,
+    /// The byte string identifying the variant
+    pub tag: Vec<u8>,
+    pub kind: VariantKind<'a>,
+}
+
+pub enum VariantKind<'a> {
+    /// A variant without fields, encoded as nothing but its tag
+    Unit,
+    /// A tuple variant with a single field, encoded like that field
+    Newtype(Box<Field<'a>>),
+    /// A variant with named fields, encoded as dictionary
+    Struct(Vec<Field<'a>>),
+}
+
+/// Where the tag of an enum variant is stored
+pub enum Tagging {
+    /// In a dictionary with the tag as only key and the content as its value. Unit
+    /// variants are encoded as their tag alone.
+    External,
+    /// Under the `tag` key, next to the fields of the variant
+    Internal { tag: Vec<u8> },
+    /// Under the `tag` key, next to the content under the `content` key
+    Adjacent { tag: Vec<u8>, content: Vec<u8> },
+}
+
+impl<'a> Field<'a> {
+    /// The type of the encoded value, which is the content of an `Option`
+    pub fn value_type(&self) -> &'a Type {
+        option_content(self.ty).unwrap_or(self.ty)
+    }
+
+    pub fn is_option(&self) -> bool {
+        option_content(self.ty).is_some()
+    }
+
+    /// Whether the field is encoded as a pair of the dictionary, with its own key
+    pub fn is_pair(&self) -> bool {
+        !self.attrs.skip && !self.attrs.flatten
+    }
+
+    /// The dictionary key of the field as readable string, for error messages
+    pub fn key_name(&self) -> String {
+        String::from_utf8_lossy(&self.key).into_owned()
+    }
+}
+
+pub fn parse(input: &DeriveInput) -> syn::Result<Shape<'_>> {
+    let attrs = ContainerAttrs::from_attrs(&input.attrs)?;
+
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        Data::Enum(data) => return parse_enum(input, data, attrs),
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "bencode can only be derived for structs and enums",
+            ))
+        },
+    };
+
+    if attrs.tag.is_some() || attrs.content.is_some() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "`tag` and `content` only apply to enums",
+        ));
+    }
+
+    match &data.fields {
+        Fields::Named(named) => Ok(Shape::Dict(parse_named(named)?)),
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => Ok(Shape::Newtype(Box::new(
+            parse_newtype(&unnamed.unnamed[0])?,
+        ))),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "bencode can only be derived for structs with named fields and newtypes",
+        )),
+    }
+}
+
+fn parse_enum<'a>(
+    input: &'a DeriveInput,
+    data: &'a DataEnum,
+    attrs: ContainerAttrs,
+) -> syn::Result<Shape<'a>> {
+    let tagging = match (attrs.tag, attrs.content) {
+        (None, None) => Tagging::External,
+        (Some(tag), None) => Tagging::Internal { tag },
+        (Some(tag), Some(content)) if tag != content => Tagging::Adjacent { tag, content },
+        (Some(_), Some(_)) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`tag` and `content` have to be different keys",
+            ))
+        },
+        (None, Some(_)) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`content` requires `tag`",
+            ))
+        },
+    };
+
+    if data.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "bencode can't be derived for enums without variants",
+        ));
+    }
+
+    let mut variants: Vec<Variant> = Vec::new();
+    for variant in &data.variants {
+        let attrs = VariantAttrs::from_attrs(&variant.attrs)?;
+        let tag = match attrs.rename {
+            Some(rename) => rename,
+            None => unraw(&variant.ident).into_bytes(),
+        };
+
+        if variants.iter().any(|other| other.tag == tag) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                format!("duplicate bencode tag `{}`", String::from_utf8_lossy(&tag)),
+            ));
+        }
+
+        let kind = match &variant.fields {
+            Fields::Unit => VariantKind::Unit,
+            Fields::Named(named) => VariantKind::Struct(parse_named(named)?),
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                VariantKind::Newtype(Box::new(parse_newtype(&unnamed.unnamed[0])?))
+            },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "bencode can only be derived for unit variants, variants with named \
+                     fields and newtype variants",
+                ))
+            },
+        };
+
+        if let Tagging::Internal { tag: tag_key } = &tagging {
+            match &kind {
+                VariantKind::Newtype(_) => {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "internally tagged enums can't have newtype variants",
+                    ))
+                },
+                VariantKind::Struct(fields)
+                    if fields
+                        .iter()
+                        .any(|field| field.is_pair() && field.key == *tag_key) =>
+                {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "a field uses the same key as the tag",
+                    ))
+                },
+                _ => (),
+            }
+        }
+
+        variants.push(Variant {
+            ident: variant.ident.clone(),
+            tag,
+            kind,
+        });
+    }
+
+    Ok(Shape::Enum(variants, tagging))
+}
+
+fn parse_named(named: &FieldsNamed) -> syn::Result<Vec<Field<'_>>> {
+    let mut fields: Vec<Field> = Vec::new();
+    for field in &named.named {
+        let ident = field
+            .ident
+            .clone()
+            .expect("named fields have an identifier");
+        let attrs = FieldAttrs::from_attrs(&field.attrs)?;
+        let key = match &attrs.rename {
+            Some(rename) => rename.clone(),
+            None => unraw(&ident).into_bytes(),
+        };
+
+        if attrs.flatten {
+            if attrs.has_options() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "`flatten` can't be combined with other attributes",
+                ));
+            }
+            if fields.iter().any(|other| other.attrs.flatten) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "only one field can be flattened",
+                ));
+            }
+        } else if !attrs.skip
+            && fields
+                .iter()
+                .any(|other| other.is_pair() && other.key == key)
+        {
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("duplicate bencode key `{}`", String::from_utf8_lossy(&key)),
+            ));
+        }
+
+        fields.push(Field {
+            member: Member::Named(ident),
+            ty: &field.ty,
+            key,
+            attrs,
+        });
+    }
+    Ok(fields)
+}
+
+fn parse_newtype(field: &syn::Field) -> syn::Result<Field<'_>> {
+    let attrs = FieldAttrs::from_attrs(&field.attrs)?;
+    if attrs.rename.is_some()
+        || attrs.default.is_some()
+        || attrs.skip
+        || attrs.skip_serializing_if.is_some()
+        || attrs.flatten
+    {
+        return Err(syn::Error::new_spanned(
+            field,
+            "only `as_string` and `bytes` apply to the field of a newtype",
+        ));
+    }
+
+    Ok(Field {
+        member: Member::from(0),
+        ty: &field.ty,
+        key: Vec::new(),
+        attrs,
+    })
+}
+
+/// The name of `ident` without the `r#` prefix of raw identifiers
+fn unraw(ident: &Ident) -> String {
+    ident.to_string().trim_start_matches("r#").to_owned()
+}
+
+/// Require `bound` for every type parameter
+pub fn add_bounds(generics: &Generics, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<_> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+
+    generics
+}
+
+/// A constant expression for the largest of `depths`, or zero if there are none
+pub fn max_depth(depths: impl Iterator<Item = TokenStream>) -> TokenStream {
+    let depths: Vec<_> = depths.collect();
+    if depths.is_empty() {
+        return quote!(0);
+    }
+
+    quote! {{
+        let mut depth = 0;
+        #(
+            if #depths > depth {
+                depth = #depths;
+            }
+        )*
+        depth
+    }}
+}
+
+/// The depth of a dictionary holding the fields that aren't skipped, given the depth
+/// of the value of each field
+pub fn dict_depth(fields: &[Field], value_depth: fn(&Field) -> TokenStream) -> TokenStream {
+    let decoded = fields.iter().filter(|field| !field.attrs.skip);
+    let depth = max_depth(decoded.map(value_depth));
+    quote!(#depth + 1)
+}
+
+/// The depth of an enum, given the depth of the value of each field
+pub fn enum_depth(
+    variants: &[Variant],
+    tagging: &Tagging,
+    value_depth: fn(&Field) -> TokenStream,
+) -> TokenStream {
+    max_depth(variants.iter().map(|variant| {
+        let content = match &variant.kind {
+            VariantKind::Unit => quote!(0),
+            VariantKind::Newtype(field) => value_depth(field),
+            VariantKind::Struct(fields) => dict_depth(fields, value_depth),
+        };
+
+        match (tagging, &variant.kind) {
+            (Tagging::External, VariantKind::Unit) => quote!(0),
+            (Tagging::Internal { .. }, VariantKind::Unit) => quote!(1),
+            (Tagging::Internal { .. }, _) => content,
+            _ => quote!(#content + 1),
+        }
+    }))
+}
+
+/// The names the values of `fields` are bound to in generated code
+pub fn bindings(fields: &[Field]) -> Vec<Ident> {
+    (0..fields.len())
+        .map(|index| format_ident!("field_{}", index))
+        .collect()
+}
+
+/// A pattern matching `variant` that binds the fields which aren't skipped to the
+/// names returned by [`bindings`]
+pub fn variant_pattern(variant: &Variant) -> TokenStream {
+    let ident = &variant.ident;
+    match &variant.kind {
+        VariantKind::Unit => quote!(Self::#ident),
+        VariantKind::Newtype(_) => quote!(Self::#ident(field_0)),
+        VariantKind::Struct(fields) => {
+            let bound = fields
+                .iter()
+                .zip(bindings(fields))
+                .filter(|(field, _)| !field.attrs.skip)
+                .map(|(field, binding)| {
+                    let member = &field.member;
+                    quote!(#member: #binding,)
+                });
+            quote!(Self::#ident { #(#bound)* .. })
+        },
+    }
+}
+
+/// A byte string literal for a key or tag
+pub fn byte_str(bytes: &[u8]) -> LitByteStr {
+    LitByteStr::new(bytes, Span::call_site())
+}
+
+/// A readable list of the tags of `variants`, for error messages
+pub fn expected_tags<'v, 'a: 'v>(variants: impl Iterator<Item = &'v Variant<'a>>) -> String {
+    let tags: Vec<_> = variants
+        .map(|variant| format!("`{}`", String::from_utf8_lossy(&variant.tag)))
+        .collect();
+    format!("one of {}", tags.join(", "))
+}
+
+/// The `T` of an `Option<T>`
+fn option_content(ty: &Type) -> Option<&Type> {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match &arguments.args[0] {
+                GenericArgument::Type(content) => Some(content),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,267 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::{
+    attr::Format,
+    model::{self, byte_str, Field, Shape, Tagging, Variant, VariantKind},
+};
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let shape = model::parse(input)?;
+
+    let name = &input.ident;
+    let generics = model::add_bounds(&input.generics, quote!(::bendy::encoding::ToBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (depth, body) = match &shape {
+        Shape::Dict(fields) => {
+            let values: Vec<_> = fields
+                .iter()
+                .map(|field| {
+                    let member = &field.member;
+                    quote!(&self.#member)
+                })
+                .collect();
+            (
+                model::dict_depth(fields, value_depth),
+                encode_fields(fields, &values, Vec::new()),
+            )
+        },
+        Shape::Newtype(field) => {
+            let member = &field.member;
+            (
+                value_depth(field),
+                encode_value(field, quote!(&self.#member)),
+            )
+        },
+        Shape::Enum(variants, tagging) => (
+            model::enum_depth(variants, tagging, value_depth),
+            encode_enum(variants, tagging),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::bendy::encoding::ToBencode for #name #ty_generics #where_clause {
+            const MAX_DEPTH: usize = #depth;
+
+            fn encode(
+                &self,
+                encoder: ::bendy::encoding::SingleItemEncoder,
+            ) -> ::core::result::Result<(), ::bendy::encoding::Error> {
+                #body
+            }
+        }
+    })
+}
+
+/// The maximum depth of the encoded value of `field`
+fn value_depth(field: &Field) -> TokenStream {
+    let ty = field.value_type();
+    match field.attrs.format {
+        // The pairs of a flattened map are part of the surrounding dictionary
+        Format::Plain if field.attrs.flatten => {
+            quote!(<#ty as ::bendy::encoding::ToBencode>::MAX_DEPTH - 1)
+        },
+        Format::Plain => quote!(<#ty as ::bendy::encoding::ToBencode>::MAX_DEPTH),
+        Format::AsString => quote!(0),
+        Format::Bytes => quote!(1),
+    }
+}
+
+/// A dictionary entry, consisting of its key and the statements emitting it into `dict`
+struct Entry {
+    key: Vec<u8>,
+    emit: TokenStream,
+}
+
+/// The entries of the fields that aren't skipped. `values` holds an expression for a
+/// reference to each field.
+fn field_entries(fields: &[Field], values: &[TokenStream]) -> Vec<Entry> {
+    fields
+        .iter()
+        .zip(values)
+        .filter(|(field, _)| field.is_pair())
+        .map(|(field, value)| {
+            let emit = emit_pair(field, &field.key, quote!(value));
+            let emit = if field.is_option() {
+                quote! {
+                    if let ::core::option::Option::Some(value) = #value {
+                        #emit
+                    }
+                }
+            } else {
+                quote! {
+                    let value = #value;
+                    #emit
+                }
+            };
+
+            let emit = match &field.attrs.skip_serializing_if {
+                Some(predicate) => quote! {
+                    if !#predicate(#value) {
+                        #emit
+                    }
+                },
+                None => quote!({ #emit }),
+            };
+
+            Entry {
+                key: field.key.clone(),
+                emit,
+            }
+        })
+        .collect()
+}
+
+/// The entry holding the tag of an enum variant
+fn tag_entry(key: &[u8], tag: &[u8]) -> Entry {
+    let key_literal = byte_str(key);
+    let tag = byte_str(tag);
+    Entry {
+        key: key.to_vec(),
+        emit: quote!(dict.emit_pair_with(#key_literal, |encoder| encoder.emit_bytes(#tag))?;),
+    }
+}
+
+/// A statement emitting `value`, a reference to the value of `field`, into `dict`
+fn emit_pair(field: &Field, key: &[u8], value: TokenStream) -> TokenStream {
+    let key = byte_str(key);
+    match field.attrs.format {
+        Format::Plain => quote!(dict.emit_pair(#key, #value)?;),
+        Format::AsString => quote!(dict.emit_pair(#key, ::bendy::encoding::AsString(#value))?;),
+        Format::Bytes => quote! {
+            dict.emit_pair_with(#key, |encoder| {
+                ::bendy::derive_support::encode_byte_strings(encoder, #value)
+            })?;
+        },
+    }
+}
+
+/// An expression emitting `value`, a reference to the value of `field`, into `encoder`
+fn encode_value(field: &Field, value: TokenStream) -> TokenStream {
+    match field.attrs.format {
+        Format::Plain => quote!(encoder.emit(#value)),
+        Format::AsString => quote!(encoder.emit(&::bendy::encoding::AsString(#value))),
+        Format::Bytes => quote!(::bendy::derive_support::encode_byte_strings(encoder, #value)),
+    }
+}
+
+/// An expression emitting a dictionary with `fields` and the additional `entries` into
+/// `encoder`. `values` holds an expression for a reference to each field.
+fn encode_fields(fields: &[Field], values: &[TokenStream], mut entries: Vec<Entry>) -> TokenStream {
+    entries.extend(field_entries(fields, values));
+    let unknown = fields
+        .iter()
+        .zip(values)
+        .find(|(field, _)| field.attrs.flatten)
+        .map(|(_, value)| value);
+
+    encode_dict(entries, unknown)
+}
+
+/// An expression emitting a dictionary with `entries` into `encoder`. The pairs of the
+/// map `unknown` refers to are merged in between them.
+fn encode_dict(mut entries: Vec<Entry>, unknown: Option<&TokenStream>) -> TokenStream {
+    if entries.is_empty() && unknown.is_none() {
+        return quote!(encoder.emit_dict(|_| ::core::result::Result::Ok(())));
+    }
+
+    // Keys have to be emitted in sorted order
+    entries.sort_by(|lhs, rhs| lhs.key.cmp(&rhs.key));
+
+    let emits = entries.iter().map(|entry| match unknown {
+        Some(_) => {
+            let key = byte_str(&entry.key);
+            let emit = &entry.emit;
+            quote! {
+                unknown.emit_before(&mut dict, #key)?;
+                #emit
+            }
+        },
+        None => entry.emit.clone(),
+    });
+
+    // Without other entries, the remaining pairs are emitted right away
+    let mutability = if entries.is_empty() {
+        quote!()
+    } else {
+        quote!(mut)
+    };
+
+    match unknown {
+        Some(unknown) => quote! {
+            encoder.emit_dict(|mut dict| {
+                let #mutability unknown = ::bendy::derive_support::UnknownPairs::new(#unknown);
+                #(#emits)*
+                unknown.emit_rest(&mut dict)
+            })
+        },
+        None => quote! {
+            encoder.emit_dict(|mut dict| {
+                #(#emits)*
+                ::core::result::Result::Ok(())
+            })
+        },
+    }
+}
+
+fn encode_enum(variants: &[Variant], tagging: &Tagging) -> TokenStream {
+    let arms = variants.iter().map(|variant| {
+        let pattern = model::variant_pattern(variant);
+        let tag = &variant.tag;
+
+        let body = match (tagging, &variant.kind) {
+            (Tagging::External, VariantKind::Unit) => {
+                let tag = byte_str(tag);
+                quote!(encoder.emit_bytes(#tag))
+            },
+            (Tagging::External, _) => encode_dict(vec![content_entry(tag, &variant.kind)], None),
+            (Tagging::Internal { tag: key }, VariantKind::Struct(fields)) => {
+                encode_fields(fields, &binding_values(fields), vec![tag_entry(key, tag)])
+            },
+            (Tagging::Internal { tag: key }, _)
+            | (Tagging::Adjacent { tag: key, .. }, VariantKind::Unit) => {
+                encode_dict(vec![tag_entry(key, tag)], None)
+            },
+            (Tagging::Adjacent { tag: key, content }, kind) => encode_dict(
+                vec![tag_entry(key, tag), content_entry(content, kind)],
+                None,
+            ),
+        };
+
+        quote!(#pattern => #body,)
+    });
+
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
+
+/// The entry holding the content of a newtype or struct variant under `key`
+fn content_entry(key: &[u8], kind: &VariantKind) -> Entry {
+    let emit = match kind {
+        VariantKind::Unit => unreachable!("unit variants have no content"),
+        VariantKind::Newtype(field) => emit_pair(field, key, quote!(field_0)),
+        VariantKind::Struct(fields) => {
+            let key = byte_str(key);
+            let dict = encode_fields(fields, &binding_values(fields), Vec::new());
+            quote!(dict.emit_pair_with(#key, |encoder| #dict)?;)
+        },
+    };
+
+    Entry {
+        key: key.to_vec(),
+        emit,
+    }
+}
+
+/// The bindings of the fields of a variant, which are references already
+fn binding_values(fields: &[Field]) -> Vec<TokenStream> {
+    model::bindings(fields)
+        .into_iter()
+        .map(|binding| quote!(#binding))
+        .collect()
+}
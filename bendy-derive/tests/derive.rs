@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+
+use bendy::{
+    bencode, bencode_bytes,
+    decoding::{DecodeLimits, Decoder, ErrorKind, FromBencode, PathSegment},
+    encoding::ToBencode,
+    state_tracker::{LimitExceeded, StructureError},
+    value::Value,
+};
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct MetaInfo {
+    announce: String,
+    info: Info,
+    comment: Option<String>,
+    #[bencode(rename = "creation date")]
+    creation_date: Option<u64>,
+    #[bencode(rename = "url-list", bytes, default)]
+    url_list: Vec<Vec<u8>>,
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct Info {
+    #[bencode(rename = "piece length")]
+    piece_length: u64,
+    #[bencode(as_string)]
+    pieces: Vec<u8>,
+    name: String,
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct Defaults {
+    #[bencode(default = "default_port")]
+    port: u16,
+    #[bencode(default, skip_serializing_if = "is_zero")]
+    private: u8,
+    #[bencode(skip)]
+    cache: Vec<u8>,
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct PeerId(#[bencode(as_string)] Vec<u8>);
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct Empty {}
+
+fn default_port() -> u16 {
+    6881
+}
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
+
+fn torrent() -> MetaInfo {
+    MetaInfo {
+        announce: "http://tracker".to_owned(),
+        info: Info {
+            piece_length: 16,
+            pieces: vec![1, 2, 3],
+            name: "file".to_owned(),
+        },
+        comment: None,
+        creation_date: Some(1),
+        url_list: vec![b"a".to_vec(), b"bc".to_vec()],
+    }
+}
+
+#[test]
+fn keys_should_be_sorted() {
+    let encoded = torrent().to_bencode().unwrap();
+    assert_eq!(
+        encoded,
+        bencode_bytes!({
+            "announce": "http://tracker",
+            "creation date": 1,
+            "info": { "name": "file", "piece length": 16, "pieces": b"\x01\x02\x03" },
+            "url-list": ["a", "bc"],
+        })
+    );
+}
+
+#[test]
+fn values_should_round_trip() {
+    let encoded = torrent().to_bencode().unwrap();
+    assert_eq!(MetaInfo::from_bencode(&encoded).unwrap(), torrent());
+
+    let mut torrent = torrent();
+    torrent.comment = Some("hello".to_owned());
+    torrent.creation_date = None;
+    let encoded = torrent.to_bencode().unwrap();
+    assert_eq!(MetaInfo::from_bencode(&encoded).unwrap(), torrent);
+}
+
+#[test]
+fn missing_fields_should_fail() {
+    let err = MetaInfo::from_bencode(b"d8:announce1:xe").unwrap_err();
+    match err.kind() {
+        ErrorKind::MissingField(field) => assert_eq!(field, "info"),
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn invalid_fields_should_report_their_path() {
+    let input = b"d8:announce1:x4:infod4:name1:x12:piece lengthi-1e6:pieces0:ee";
+    let err = MetaInfo::from_bencode(input).unwrap_err();
+    assert_eq!(
+        err.path(),
+        &[
+            PathSegment::Key(b"info".to_vec()),
+            PathSegment::Key(b"piece length".to_vec()),
+        ][..]
+    );
+}
+
+#[test]
+fn unknown_keys_should_be_ignored() {
+    let decoded = Defaults::from_bencode(b"d5:extra3:abc4:porti1ee").unwrap();
+    assert_eq!(decoded.port, 1);
+}
+
+#[test]
+fn defaults_and_skipped_fields_should_be_used() {
+    let decoded = Defaults::from_bencode(b"de").unwrap();
+    assert_eq!(
+        decoded,
+        Defaults {
+            port: 6881,
+            private: 0,
+            cache: Vec::new(),
+        }
+    );
+
+    let value = Defaults {
+        port: 1,
+        private: 0,
+        cache: vec![1],
+    };
+    assert_eq!(value.to_bencode().unwrap(), &b"d4:porti1ee"[..]);
+
+    let value = Defaults {
+        port: 1,
+        private: 1,
+        cache: vec![1],
+    };
+    assert_eq!(value.to_bencode().unwrap(), &b"d4:porti1e7:privatei1ee"[..]);
+}
+
+#[test]
+fn newtypes_should_be_transparent() {
+    let id = PeerId(b"-BD0300-".to_vec());
+    let encoded = id.to_bencode().unwrap();
+
+    assert_eq!(encoded, &b"8:-BD0300-"[..]);
+    assert_eq!(PeerId::from_bencode(&encoded).unwrap(), id);
+}
+
+#[test]
+fn generic_and_empty_structs_should_be_supported() {
+    let value = Wrapper {
+        inner: vec![1u8, 2],
+    };
+    let encoded = value.to_bencode().unwrap();
+
+    assert_eq!(encoded, &b"d5:innerli1ei2eee"[..]);
+    assert_eq!(Wrapper::from_bencode(&encoded).unwrap(), value);
+
+    assert_eq!(Empty {}.to_bencode().unwrap(), &b"de"[..]);
+    assert_eq!(Empty::from_bencode(b"d1:ai1ee").unwrap(), Empty {});
+}
+
+#[test]
+fn depth_should_account_for_nesting() {
+    assert_eq!(
+        <Info as ToBencode>::MAX_DEPTH,
+        <u64 as ToBencode>::MAX_DEPTH + 1
+    );
+    assert_eq!(
+        <MetaInfo as ToBencode>::MAX_DEPTH,
+        <Info as ToBencode>::MAX_DEPTH + 1
+    );
+    assert_eq!(
+        <MetaInfo as FromBencode>::EXPECTED_RECURSION_DEPTH,
+        <Info as FromBencode>::EXPECTED_RECURSION_DEPTH + 1
+    );
+    assert_eq!(<Empty as ToBencode>::MAX_DEPTH, 1);
+    assert_eq!(<PeerId as ToBencode>::MAX_DEPTH, 0);
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+#[bencode(tag = "y")]
+enum Message {
+    #[bencode(rename = "q")]
+    Query {
+        #[bencode(rename = "t", as_string)]
+        transaction: Vec<u8>,
+        #[bencode(rename = "q")]
+        method: String,
+    },
+    #[bencode(rename = "r")]
+    Response {
+        #[bencode(rename = "t", as_string)]
+        transaction: Vec<u8>,
+        #[bencode(rename = "r")]
+        values: Vec<u64>,
+    },
+    #[bencode(rename = "e")]
+    Error {
+        #[bencode(rename = "t", as_string)]
+        transaction: Vec<u8>,
+        #[bencode(rename = "e")]
+        message: String,
+    },
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+enum Command {
+    Ping,
+    #[bencode(rename = b"get")]
+    Get(#[bencode(as_string)] Vec<u8>),
+    Put {
+        key: String,
+        value: u64,
+    },
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+#[bencode(tag = "type", content = "data")]
+enum Event {
+    Started,
+    Progress(u64),
+    Finished {
+        total: u64,
+        #[bencode(default)]
+        failed: Option<u64>,
+    },
+}
+
+#[test]
+fn internally_tagged_enums_should_round_trip() {
+    let query = Message::Query {
+        transaction: b"aa".to_vec(),
+        method: "ping".to_owned(),
+    };
+    let encoded = query.to_bencode().unwrap();
+    assert_eq!(encoded, &b"d1:q4:ping1:t2:aa1:y1:qe"[..]);
+    assert_eq!(Message::from_bencode(&encoded).unwrap(), query);
+
+    let response = Message::from_bencode(b"d1:rli1ei2ee1:t2:aa1:y1:re").unwrap();
+    assert_eq!(
+        response,
+        Message::Response {
+            transaction: b"aa".to_vec(),
+            values: vec![1, 2],
+        }
+    );
+    assert_eq!(
+        response.to_bencode().unwrap(),
+        &b"d1:rli1ei2ee1:t2:aa1:y1:re"[..]
+    );
+}
+
+#[test]
+fn internally_tagged_enums_should_check_the_tag() {
+    let err = Message::from_bencode(b"d1:t2:aa1:y1:xe").unwrap_err();
+    match err.kind() {
+        ErrorKind::UnexpectedToken(expected, found) => {
+            assert_eq!(expected, "one of `q`, `r`, `e`");
+            assert_eq!(found, "x");
+        },
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+
+    let err = Message::from_bencode(b"d1:e4:oops1:t2:aae").unwrap_err();
+    match err.kind() {
+        ErrorKind::MissingField(field) => assert_eq!(field, "y"),
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn externally_tagged_enums_should_round_trip() {
+    let commands = vec![
+        (Command::Ping, &b"4:Ping"[..]),
+        (Command::Get(b"key".to_vec()), &b"d3:get3:keye"[..]),
+        (
+            Command::Put {
+                key: "a".to_owned(),
+                value: 1,
+            },
+            &b"d3:Putd3:key1:a5:valuei1eee"[..],
+        ),
+    ];
+
+    for (command, expected) in commands {
+        let encoded = command.to_bencode().unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(Command::from_bencode(&encoded).unwrap(), command);
+    }
+}
+
+#[test]
+fn externally_tagged_enums_should_only_hold_the_tag() {
+    let err = Command::from_bencode(b"d3:get3:key3:put1:xe").unwrap_err();
+    match err.kind() {
+        ErrorKind::UnexpectedField(field) => assert_eq!(field, "put"),
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+
+    assert!(Command::from_bencode(b"4:Pong").is_err());
+    assert!(Command::from_bencode(b"i1e").is_err());
+}
+
+#[test]
+fn adjacently_tagged_enums_should_round_trip() {
+    let events = vec![
+        (Event::Started, &b"d4:type7:Startede"[..]),
+        (Event::Progress(5), &b"d4:datai5e4:type8:Progresse"[..]),
+        (
+            Event::Finished {
+                total: 5,
+                failed: None,
+            },
+            &b"d4:datad5:totali5ee4:type8:Finishede"[..],
+        ),
+    ];
+
+    for (event, expected) in events {
+        let encoded = event.to_bencode().unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(Event::from_bencode(&encoded).unwrap(), event);
+    }
+
+    let err = Event::from_bencode(b"d4:type8:Progresse").unwrap_err();
+    match err.kind() {
+        ErrorKind::MissingField(field) => assert_eq!(field, "data"),
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn tagged_enums_should_nest_in_structs() {
+    let value = Wrapper {
+        inner: Event::Progress(1),
+    };
+    let encoded = value.to_bencode().unwrap();
+
+    assert_eq!(encoded, &b"d5:innerd4:datai1e4:type8:Progressee"[..]);
+    assert_eq!(Wrapper::from_bencode(&encoded).unwrap(), value);
+
+    let err = Wrapper::<Event>::from_bencode(b"d5:innerd4:data1:x4:type8:Progressee").unwrap_err();
+    assert_eq!(err.path(), &[PathSegment::Key(b"inner".to_vec())][..]);
+}
+
+#[test]
+fn tagged_enums_should_keep_the_limits_of_the_decoder() {
+    let input = b"d5:innerd1:rli1ei2ee1:t2:aa1:y1:ree";
+    let limits = DecodeLimits::new().with_max_allocation(16);
+    let err = Wrapper::<Message>::from_bencode_with_limits(input, limits).unwrap_err();
+
+    match err.kind() {
+        ErrorKind::StructureError(StructureError::LimitExceeded(limit)) => {
+            assert_eq!(*limit, LimitExceeded::Allocation { limit: 16 })
+        },
+        kind => panic!("Unexpected error {:?}", kind),
+    }
+    assert_eq!(err.offset(), Some(16));
+
+    let limits = DecodeLimits::new().with_max_allocation(64);
+    assert!(Wrapper::<Message>::from_bencode_with_limits(input, limits).is_ok());
+}
+
+#[test]
+fn tagged_enums_should_keep_the_leniency_of_the_decoder() {
+    let mut decoder = Decoder::new(b"d1:y1:r1:t2:aa1:rli1eee").with_lenient(true);
+    let object = decoder.next_object().unwrap().unwrap();
+
+    assert_eq!(
+        Message::decode_bencode_object(object).unwrap(),
+        Message::Response {
+            transaction: b"aa".to_vec(),
+            values: vec![1],
+        }
+    );
+    assert_eq!(decoder.violations().len(), 2);
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+struct Torrent {
+    announce: String,
+    info: Info,
+    #[bencode(flatten)]
+    unknown: BTreeMap<Vec<u8>, Value<'static>>,
+}
+
+#[derive(ToBencode, FromBencode, PartialEq, Debug)]
+#[bencode(tag = "y")]
+enum Packet {
+    #[bencode(rename = "q")]
+    Query {
+        #[bencode(rename = "q")]
+        method: String,
+        #[bencode(flatten)]
+        arguments: BTreeMap<Vec<u8>, Value<'static>>,
+    },
+}
+
+#[test]
+fn flattened_fields_should_collect_unknown_keys() {
+    let input = bencode_bytes!({
+        "announce": "x",
+        "created by": "bendy",
+        "info": { "name": "file", "piece length": 16, "pieces": "" },
+        "infox": 1,
+        "private": 1,
+        "webs": {},
+    });
+    let torrent = Torrent::from_bencode(input).unwrap();
+
+    let keys: Vec<_> = torrent.unknown.keys().map(Vec::as_slice).collect();
+    assert_eq!(keys, [&b"created by"[..], b"infox", b"private", b"webs"]);
+    assert_eq!(torrent.unknown[&b"webs"[..]], bencode!({}));
+    assert_eq!(torrent.to_bencode().unwrap(), input);
+}
+
+#[test]
+fn flattened_fields_should_not_collect_the_tag() {
+    let input = &b"d1:ai1e1:q4:ping1:y1:qe"[..];
+    let packet = Packet::from_bencode(input).unwrap();
+
+    let Packet::Query { method, arguments } = &packet;
+    assert_eq!(method, "ping");
+    assert_eq!(arguments.len(), 1);
+    assert_eq!(packet.to_bencode().unwrap(), input);
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use bendy::{
+    bencode, bencode_bytes,
+    encoding::ToBencode,
+    value::{Integer, Value},
+};
+
+#[test]
+fn literals_should_build_values() {
+    let mut dict = BTreeMap::new();
+    dict.insert(b"bar".to_vec(), Value::from(b"\x00\x01"));
+    dict.insert(
+        b"foo".to_vec(),
+        Value::List(vec![Value::from(1), Value::from(-2)]),
+    );
+
+    assert_eq!(
+        bencode!({ "foo": [1, -2], "bar": b"\x00\x01" }),
+        Value::from(dict)
+    );
+    assert_eq!(bencode!([]), Value::List(Vec::new()));
+    assert_eq!(bencode!({}), Value::Dict(BTreeMap::new()));
+    assert_eq!(bencode!("text"), Value::from("text"));
+}
+
+#[test]
+fn literals_should_encode_like_their_values() {
+    let value = bencode!({
+        "list": [[], {}, 0, -0, 007, 0x10],
+        b"\xff": "max",
+        "": { "nested": ["a", b"b"] },
+    });
+    let encoded = bencode_bytes!({
+        "list": [[], {}, 0, -0, 007, 0x10],
+        b"\xff": "max",
+        "": { "nested": ["a", b"b"] },
+    });
+
+    assert_eq!(
+        &encoded[..],
+        &b"d0:d6:nestedl1:a1:bee4:listlledei0ei0ei7ei16ee1:\xff3:maxe"[..]
+    );
+    assert_eq!(value.to_bencode().unwrap(), &encoded[..]);
+}
+
+#[test]
+fn integers_should_not_be_limited_in_size() {
+    let big = bencode!([
+        -170141183460469231731687303715884105728,
+        340282366920938463463374607431768211456
+    ]);
+
+    assert_eq!(big[0], Value::from(i128::MIN));
+    assert_eq!(
+        big[1],
+        Value::from(
+            "340282366920938463463374607431768211456"
+                .parse::<Integer>()
+                .unwrap()
+        )
+    );
+    assert_eq!(
+        big.to_bencode().unwrap(),
+        &bencode_bytes!([
+            -170141183460469231731687303715884105728,
+            340282366920938463463374607431768211456
+        ])[..]
+    );
+}
+
+#[test]
+fn expressions_should_be_converted() {
+    let name = String::from("file");
+    let length = 5u64;
+    let value = bencode!({
+        "length": length,
+        "name": name.as_str(),
+        "path": (vec![Value::from("a"), Value::from("b")]),
+        "size": length * 2,
+    });
+
+    assert_eq!(
+        value.to_bencode().unwrap(),
+        bencode_bytes!({ "length": 5, "name": "file", "path": ["a", "b"], "size": 10 })
+    );
+}
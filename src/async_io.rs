@@ -0,0 +1,281 @@
+//! Decoding from [`AsyncRead`] and encoding to [`AsyncWrite`] implementations of the
+//! `futures` ecosystem.
+//!
+//! Reading never consumes bytes beyond the end of the value that is being decoded, so
+//! several values (or a value followed by arbitrary other data) can be read from the
+//! same source one after another. To achieve this, the reader is only ever asked for the
+//! number of bytes that are known to be missing from the current value, which may result
+//! in many small reads. Wrap unbuffered sources in a [`BufReader`] to avoid this.
+//!
+//! ```
+//! use bendy::async_io::{read_value, write_value};
+//! # use futures::{executor::block_on, io::Cursor};
+//!
+//! # block_on(async {
+//! let mut buffer = Cursor::new(Vec::new());
+//! write_value(&mut buffer, &vec![1, 2, 3]).await.unwrap();
+//! write_value(&mut buffer, &"done").await.unwrap();
+//!
+//! buffer.set_position(0);
+//! let numbers: Vec<u32> = read_value(&mut buffer).await.unwrap();
+//! let status: String = read_value(&mut buffer).await.unwrap();
+//!
+//! assert_eq!(numbers, vec![1, 2, 3]);
+//! assert_eq!(status, "done");
+//! # });
+//! ```
+//!
+//! [`BufReader`]: futures_util::io::BufReader
+
+use std::io;
+
+use futures_util::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    decoding::{self, DecodeLimits, FromBencode, Progress, PushDecoder},
+    encoding::{self, ToBencode},
+    state_tracker::StructureError,
+};
+
+/// Upper bound for a single read, so hostile length prefixes can't trigger huge
+/// allocations before any data has arrived.
+const MAX_READ_SIZE: usize = 64 * 1024;
+
+/// Read exactly one complete top level value from `reader` and return its raw
+/// encoding.
+///
+/// The value is validated using the given maximum nesting depth. Returns `Ok(None)`
+/// if the reader is exhausted before the first byte of a value was read.
+pub async fn read_message<R>(
+    reader: &mut R,
+    max_depth: usize,
+) -> Result<Option<Vec<u8>>, decoding::Error>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    read_message_with_limits(reader, max_depth, DecodeLimits::default()).await
+}
+
+/// Read exactly one complete top level value from `reader` like [`read_message`], but
+/// also enforce the given resource limits while the value is read. See
+/// [`PushDecoder::with_limits`].
+pub async fn read_message_with_limits<R>(
+    reader: &mut R,
+    max_depth: usize,
+    limits: DecodeLimits,
+) -> Result<Option<Vec<u8>>, decoding::Error>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut decoder = PushDecoder::new()
+        .with_max_depth(max_depth)
+        .with_limits(limits);
+    let mut chunk = Vec::new();
+
+    loop {
+        let missing = match decoder.next_message()? {
+            Progress::Message(message) => return Ok(Some(message)),
+            Progress::NeedMoreData(missing) => missing,
+        };
+
+        chunk.resize(missing.min(MAX_READ_SIZE), 0);
+        let count = match reader.read(&mut chunk).await {
+            Ok(count) => count,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(decoding::Error::io(err)),
+        };
+
+        if count == 0 {
+            return if decoder.buffered().is_empty() {
+                Ok(None)
+            } else {
+                Err(decoding::Error::from(StructureError::UnexpectedEof))
+            };
+        }
+
+        decoder.push(&chunk[..count]);
+    }
+}
+
+/// Read exactly one value from `reader` and decode it.
+///
+/// Reaching the end of the input before the value is complete results in an error.
+pub async fn read_value<T, R>(reader: &mut R) -> Result<T, decoding::Error>
+where
+    T: FromBencode,
+    R: AsyncRead + Unpin + ?Sized,
+{
+    read_value_with_limits(reader, DecodeLimits::default()).await
+}
+
+/// Read exactly one value from `reader` and decode it like [`read_value`], but enforce
+/// the given resource limits while the value is read and decoded.
+pub async fn read_value_with_limits<T, R>(
+    reader: &mut R,
+    limits: DecodeLimits,
+) -> Result<T, decoding::Error>
+where
+    T: FromBencode,
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let message = read_message_with_limits(reader, T::EXPECTED_RECURSION_DEPTH, limits)
+        .await?
+        .ok_or(StructureError::UnexpectedEof)?;
+
+    T::from_bencode_with_limits(&message, limits)
+}
+
+/// Encode `value` and write it to `writer`.
+///
+/// The complete encoding is produced before anything is written, so an encoding error
+/// never results in a partially written value.
+pub async fn write_value<T, W>(writer: &mut W, value: &T) -> Result<(), encoding::Error>
+where
+    T: ToBencode + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let encoded = value.to_bencode()?;
+    writer
+        .write_all(&encoded)
+        .await
+        .map_err(encoding::Error::io)
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{
+        channel::mpsc,
+        executor::block_on,
+        io::{AsyncReadExt, Cursor},
+        SinkExt, TryStreamExt,
+    };
+
+    use super::*;
+
+    #[test]
+    fn values_can_be_read_from_a_fragmented_pipe() {
+        let (mut sender, receiver) = mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let mut reader = receiver.into_async_read();
+
+        block_on(async {
+            for chunk in &[
+                &b"d3:bar"[..],
+                b"li1e",
+                b"i2ee3:foo5:he",
+                b"llo",
+                b"e4:spam",
+            ] {
+                sender.send(Ok(chunk.to_vec())).await.unwrap();
+            }
+            sender.close_channel();
+
+            let message = read_message(&mut reader, 2).await.unwrap();
+            assert_eq!(message.unwrap(), b"d3:barli1ei2ee3:foo5:helloe");
+
+            let value: String = read_value(&mut reader).await.unwrap();
+            assert_eq!(value, "spam");
+
+            assert_eq!(read_message(&mut reader, 2).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn reading_stops_at_the_end_of_the_value() {
+        block_on(async {
+            let mut reader = Cursor::new(b"li1ei2eeBINARY PAYLOAD".to_vec());
+
+            let value: Vec<i32> = read_value(&mut reader).await.unwrap();
+            assert_eq!(value, vec![1, 2]);
+
+            let mut payload = Vec::new();
+            reader.read_to_end(&mut payload).await.unwrap();
+            assert_eq!(payload, b"BINARY PAYLOAD");
+        });
+    }
+
+    #[test]
+    fn truncated_values_should_fail() {
+        block_on(async {
+            let mut reader = Cursor::new(b"l5:ab".to_vec());
+            let err = read_value::<Vec<String>, _>(&mut reader).await.unwrap_err();
+            assert!(err.to_string().contains("EOF"));
+
+            let mut reader = Cursor::new(Vec::new());
+            assert!(read_value::<String, _>(&mut reader).await.is_err());
+        });
+    }
+
+    #[test]
+    fn invalid_values_should_fail() {
+        block_on(async {
+            let mut reader = Cursor::new(b"d3:fooi1e3:bari2ee".to_vec());
+            let err = read_message(&mut reader, 2).await.unwrap_err();
+            assert!(err.to_string().contains("Keys were not sorted"));
+        });
+    }
+
+    #[test]
+    fn limits_are_enforced_while_reading() {
+        block_on(async {
+            let limits = DecodeLimits::new().with_max_string_length(4);
+
+            let mut reader = Cursor::new(b"4:spam1000000:".to_vec());
+            let value: String = read_value_with_limits(&mut reader, limits).await.unwrap();
+            assert_eq!(value, "spam");
+
+            let err = read_value_with_limits::<String, _>(&mut reader, limits)
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("byte string of 1000000 bytes"),
+                "{}",
+                err
+            );
+        });
+    }
+
+    #[test]
+    fn values_can_be_written() {
+        block_on(async {
+            let mut writer = Cursor::new(Vec::new());
+            write_value(&mut writer, &vec!["foo", "bar"]).await.unwrap();
+            write_value(&mut writer, &5).await.unwrap();
+
+            assert_eq!(writer.into_inner(), b"l3:foo3:barei5e");
+        });
+    }
+
+    #[test]
+    fn write_errors_are_reported() {
+        struct Broken;
+
+        impl AsyncWrite for Broken {
+            fn poll_write(
+                self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context,
+                _buf: &[u8],
+            ) -> std::task::Poll<io::Result<usize>> {
+                std::task::Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+            }
+
+            fn poll_flush(
+                self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context,
+            ) -> std::task::Poll<io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+
+            fn poll_close(
+                self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context,
+            ) -> std::task::Poll<io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+        }
+
+        block_on(async {
+            let err = write_value(&mut Broken, &1).await.unwrap_err();
+            assert!(err.to_string().contains("encoding failed"));
+        });
+    }
+}
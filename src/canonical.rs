@@ -0,0 +1,314 @@
+//! Rewriting of non-canonical bencode into its canonical form.
+//!
+//! Many encoders in the wild don't sort dictionary keys, repeat keys or pad integers
+//! with leading zeros. [`canonicalize`] reads such input with a lenient [`Decoder`] and
+//! writes the canonical encoding of the same structure.
+//!
+//! ```
+//! use bendy::canonical::{canonicalize, Canonicalizer, DuplicateKeyPolicy};
+//!
+//! let canonical = canonicalize(b"d3:fooi007e3:bari-0ee").unwrap();
+//! assert_eq!(canonical, b"d3:bari0e3:fooi7ee");
+//!
+//! let input = b"d3:fooi1e3:fooi2ee";
+//! assert!(canonicalize(input).is_err());
+//!
+//! let canonical = Canonicalizer::new()
+//!     .with_duplicate_keys(DuplicateKeyPolicy::LastWins)
+//!     .canonicalize(input)
+//!     .unwrap();
+//! assert_eq!(canonical, b"d3:fooi2ee");
+//! ```
+//!
+//! [`Decoder`]: crate::decoding::Decoder
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use crate::{
+    decoding::{self, Decoder, Object, PathSegment},
+    state_tracker::{InvalidState, StructureError, SyntaxError, Token},
+};
+
+/// How to handle a key that appears more than once in the same dictionary
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum DuplicateKeyPolicy {
+    /// Keep the value of the first occurrence of the key.
+    FirstWins,
+    /// Keep the value of the last occurrence of the key.
+    LastWins,
+    /// Fail with a [`InvalidState::DuplicateKey`] error.
+    #[default]
+    Error,
+}
+
+/// Rewrite `input` into canonical bencode, rejecting duplicate keys.
+///
+/// See [`Canonicalizer`] for how to configure the conversion.
+pub fn canonicalize(input: &[u8]) -> Result<Vec<u8>, decoding::Error> {
+    Canonicalizer::new().canonicalize(input)
+}
+
+/// A configurable conversion of non-canonical bencode into canonical bencode
+///
+/// Dictionary keys are sorted, integers and string lengths lose their leading zeros and
+/// negative zero becomes zero. Lists and atoms are passed through token by token. The
+/// values of a dictionary can't be written before all of its keys are known, so each
+/// value is canonicalized into a buffer of its own as it is read, and the buffers are
+/// written in the order of their keys once the dictionary is complete. The input is read
+/// only once.
+///
+/// Input that is structurally invalid, e.g. truncated or followed by trailing data, is
+/// still rejected.
+#[derive(Clone, Debug, Default)]
+pub struct Canonicalizer {
+    duplicate_keys: DuplicateKeyPolicy,
+    max_depth: Option<usize>,
+}
+
+impl Canonicalizer {
+    /// Create a new canonicalizer that rejects duplicate keys
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set how keys that appear more than once in the same dictionary are handled
+    pub fn with_duplicate_keys(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Set the maximum nesting depth of the input. See
+    /// [`Decoder::with_max_depth`](crate::decoding::Decoder::with_max_depth).
+    pub fn with_max_depth(mut self, new_max_depth: usize) -> Self {
+        self.max_depth = Some(new_max_depth);
+        self
+    }
+
+    /// Rewrite `input`, which must contain exactly one value, into canonical bencode
+    pub fn canonicalize(&self, input: &[u8]) -> Result<Vec<u8>, decoding::Error> {
+        let mut decoder = Decoder::new(input).with_lenient(true);
+        if let Some(max_depth) = self.max_depth {
+            decoder = decoder.with_max_depth(max_depth);
+        }
+
+        let mut output = Vec::with_capacity(input.len());
+        match decoder.next_object()? {
+            Some(object) => self.write_object(object, &mut output, &mut Vec::new())?,
+            None => return Err(StructureError::UnexpectedEof.into()),
+        }
+
+        if decoder.offset() != input.len() {
+            let offset = decoder.offset();
+            let error = StructureError::from(SyntaxError::TrailingData { offset });
+            return Err(decoding::Error::from(error).at(offset, Vec::new()));
+        }
+
+        Ok(output)
+    }
+
+    /// Append the canonical form of `object` to `output`
+    fn write_object(
+        &self,
+        object: Object,
+        output: &mut Vec<u8>,
+        path: &mut Vec<PathSegment>,
+    ) -> Result<(), decoding::Error> {
+        match object {
+            Object::Integer(text) => write_token(Token::Num(&normalize_integer(text)), output),
+            Object::Bytes(bytes) => write_token(Token::String(bytes), output),
+            Object::List(mut list) => {
+                write_token(Token::List, output);
+
+                let mut index = 0;
+                while let Some(item) = list.next_object()? {
+                    path.push(PathSegment::Index(index));
+                    self.write_object(item, output, path)?;
+                    path.pop();
+                    index += 1;
+                }
+
+                write_token(Token::End, output);
+            },
+            Object::Dict(mut dict) => {
+                let mut entries = BTreeMap::new();
+
+                while let Some((key, key_span, value)) = dict.next_spanned_pair()? {
+                    let mut buffer = Vec::new();
+                    path.push(PathSegment::Key(key.to_vec()));
+                    self.write_object(value.object, &mut buffer, path)?;
+
+                    let duplicate = entries.contains_key(key);
+                    match self.duplicate_keys {
+                        _ if !duplicate => {
+                            entries.insert(key, buffer);
+                        },
+                        DuplicateKeyPolicy::FirstWins => (),
+                        DuplicateKeyPolicy::LastWins => {
+                            entries.insert(key, buffer);
+                        },
+                        DuplicateKeyPolicy::Error => {
+                            let error = InvalidState::DuplicateKey(key.to_vec());
+                            let error = decoding::Error::from(StructureError::from(error));
+                            return Err(error.at(key_span.start, path.clone()));
+                        },
+                    }
+                    path.pop();
+                }
+
+                write_token(Token::Dict, output);
+                for (key, buffer) in entries {
+                    write_token(Token::String(key), output);
+                    output.extend_from_slice(&buffer);
+                }
+                write_token(Token::End, output);
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Append the encoding of a single token to `output`
+fn write_token(token: Token, output: &mut Vec<u8>) {
+    match token {
+        Token::List => output.push(b'l'),
+        Token::Dict => output.push(b'd'),
+        Token::String(bytes) => {
+            output.extend_from_slice(bytes.len().to_string().as_bytes());
+            output.push(b':');
+            output.extend_from_slice(bytes);
+        },
+        Token::Num(num) => {
+            output.push(b'i');
+            output.extend_from_slice(num.as_bytes());
+            output.push(b'e');
+        },
+        Token::End => output.push(b'e'),
+    }
+}
+
+/// Strip leading zeros from an integer that has been validated by the lexer, and turn
+/// negative zero into zero
+fn normalize_integer(text: &str) -> String {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+
+    match digits.trim_start_matches('0') {
+        "" => String::from("0"),
+        digits => format!("{}{}", sign, digits),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonical_input_should_be_unchanged() {
+        let input = b"d0:le3:bari1e3:fooli2e3:baze4:spamdee";
+        assert_eq!(canonicalize(input).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn nested_dicts_should_be_sorted() {
+        let input = b"d1:bd1:yi1e1:xi2ee1:ali3ed1:di4e1:ci5eeee";
+        assert_eq!(
+            canonicalize(input).unwrap(),
+            &b"d1:ali3ed1:ci5e1:di4eee1:bd1:xi2e1:yi1eee"[..]
+        );
+    }
+
+    #[test]
+    fn integers_should_be_normalized() {
+        let input = b"li007ei-0ei-012ei0e002:abe";
+        assert_eq!(canonicalize(input).unwrap(), &b"li7ei0ei-12ei0e2:abe"[..]);
+        assert_eq!(normalize_integer("000"), "0");
+        assert_eq!(normalize_integer("-100"), "-100");
+    }
+
+    #[test]
+    fn duplicate_keys_should_follow_the_policy() {
+        let input = b"d1:ai1e1:bi2e1:ai3ee";
+
+        let err = canonicalize(input).unwrap_err();
+        assert_eq!(err.offset(), Some(13));
+        assert_eq!(err.path(), &[PathSegment::Key(b"a".to_vec())][..]);
+
+        let first = Canonicalizer::new().with_duplicate_keys(DuplicateKeyPolicy::FirstWins);
+        assert_eq!(first.canonicalize(input).unwrap(), &b"d1:ai1e1:bi2ee"[..]);
+
+        let last = Canonicalizer::new().with_duplicate_keys(DuplicateKeyPolicy::LastWins);
+        assert_eq!(last.canonicalize(input).unwrap(), &b"d1:ai3e1:bi2ee"[..]);
+    }
+
+    #[test]
+    fn nested_errors_should_report_input_offsets() {
+        let err = canonicalize(b"d1:ad1:bi1e1:bi2eee").unwrap_err();
+        assert_eq!(err.offset(), Some(11));
+        assert_eq!(
+            err.path(),
+            &[
+                PathSegment::Key(b"a".to_vec()),
+                PathSegment::Key(b"b".to_vec())
+            ][..]
+        );
+    }
+
+    #[test]
+    fn deeply_nested_dicts_should_be_sorted() {
+        // Every level is decoded once, no matter how deeply it is nested
+        let depth = 500;
+        let mut input = Vec::new();
+        let mut expected = Vec::new();
+        for _ in 0..depth {
+            input.extend_from_slice(b"d1:bi2e1:a");
+            expected.extend_from_slice(b"d1:a");
+        }
+        input.extend_from_slice(b"i1e");
+        expected.extend_from_slice(b"i1e");
+        input.resize(input.len() + depth, b'e');
+        for _ in 0..depth {
+            expected.extend_from_slice(b"1:bi2ee");
+        }
+
+        assert_eq!(canonicalize(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_input_should_fail() {
+        assert!(canonicalize(b"").is_err());
+        assert!(canonicalize(b"li1e").is_err());
+        assert!(canonicalize(b"i1ei2e").is_err());
+        assert!(canonicalize(b"di1ei2ee").is_err());
+        assert!(Canonicalizer::new()
+            .with_max_depth(1)
+            .canonicalize(b"llee")
+            .is_err());
+    }
+
+    #[test]
+    fn errors_should_report_their_path() {
+        let err = canonicalize(b"d1:bl1:xd1:ai1e1:ai2eee1:ai1ee").unwrap_err();
+        assert_eq!(
+            err.path(),
+            &[
+                PathSegment::Key(b"b".to_vec()),
+                PathSegment::Index(1),
+                PathSegment::Key(b"a".to_vec())
+            ][..]
+        );
+
+        let err = canonicalize(&[b'l'; 3]).unwrap_err();
+        assert_eq!(err.offset(), Some(3));
+    }
+}
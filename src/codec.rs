@@ -0,0 +1,377 @@
+//! A [`tokio_util::codec`] implementation for streams of concatenated bencode values.
+//!
+//! Many protocols send bencoded messages back to back without any length prefix or
+//! delimiter. [`BencodeCodec`] determines where each top level value ends and yields
+//! the raw bytes of the value together with its decoded [`Value`].
+
+use std::{
+    error::Error as StdError,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    decoding::{
+        self,
+        push::{MessageScanner, Scan},
+        DecodeLimits, FromBencode,
+    },
+    encoding::{self, ToBencode},
+    state_tracker::StructureError,
+    value::Value,
+};
+
+/// The default upper limit for the size of a single frame
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+/// The default maximum nesting depth of a single frame
+pub const DEFAULT_MAX_DEPTH: usize = 2048;
+
+/// A single top level value received by [`BencodeCodec`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The exact bytes the value was received as
+    pub raw: Bytes,
+    /// The decoded value
+    pub value: Value<'static>,
+}
+
+/// An enumeration of potential errors that appear while framing a stream.
+#[derive(Debug)]
+pub enum Error {
+    /// A received value could not be decoded.
+    Decoding(decoding::Error),
+    /// A value could not be encoded.
+    Encoding(encoding::Error),
+    /// A value would exceed the maximum frame size. Contains the minimum size of the
+    /// value and the configured maximum.
+    FrameTooLarge(usize, usize),
+    /// Reading from or writing to the underlying stream failed.
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Decoding(err) => write!(f, "{}", err),
+            Error::Encoding(err) => write!(f, "{}", err),
+            Error::FrameTooLarge(size, max) => write!(
+                f,
+                "frame of at least {} bytes exceeds the maximum of {} bytes",
+                size, max
+            ),
+            Error::Io(err) => write!(f, "i/o error: {}", err),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<decoding::Error> for Error {
+    fn from(err: decoding::Error) -> Self {
+        Error::Decoding(err)
+    }
+}
+
+impl From<encoding::Error> for Error {
+    fn from(err: encoding::Error) -> Self {
+        Error::Encoding(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Splits a byte stream into consecutive bencode values and encodes values into one.
+///
+/// Every received value is validated to be canonical bencode before it is handed out.
+/// Validation is incremental, so partially received frames are not scanned again when
+/// more data arrives. Once the stream turned out to be invalid, every further call
+/// fails with the same error, as there is no way to find the start of the next value.
+///
+/// ```
+/// use bendy::{codec::BencodeCodec, value::Value};
+/// use bytes::BytesMut;
+/// use tokio_util::codec::Decoder;
+///
+/// let mut codec = BencodeCodec::new();
+/// let mut buffer = BytesMut::from(&b"i1e4:sp"[..]);
+///
+/// let frame = codec.decode(&mut buffer).unwrap().unwrap();
+/// assert_eq!(frame.raw, &b"i1e"[..]);
+/// assert_eq!(frame.value, Value::Integer(1.into()));
+///
+/// assert!(codec.decode(&mut buffer).unwrap().is_none());
+/// ```
+#[derive(Debug)]
+pub struct BencodeCodec {
+    scanner: MessageScanner,
+    max_frame_size: usize,
+    max_depth: usize,
+    limits: DecodeLimits,
+    /// Position of the start of the read buffer in the complete stream
+    offset: usize,
+}
+
+impl Default for BencodeCodec {
+    fn default() -> Self {
+        let mut scanner = MessageScanner::new();
+        scanner.set_max_depth(DEFAULT_MAX_DEPTH);
+
+        BencodeCodec {
+            scanner,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: DecodeLimits::default(),
+            offset: 0,
+        }
+    }
+}
+
+impl BencodeCodec {
+    /// Create a new codec with the default limits
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set the maximum size of a received frame in bytes. The limit is checked before
+    /// the frame is buffered completely, so a too large length prefix is rejected
+    /// immediately.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Set the maximum nesting depth of received frames. See
+    /// [`Decoder::with_max_depth`](crate::decoding::Decoder::with_max_depth).
+    pub fn with_max_depth(mut self, new_max_depth: usize) -> Self {
+        self.scanner.set_max_depth(new_max_depth);
+        self.max_depth = new_max_depth;
+        self
+    }
+
+    /// Set the resource limits that apply to each received frame. See
+    /// [`DecodeLimits`](crate::decoding::DecodeLimits) for details.
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.scanner.set_limits(limits);
+        self.limits = limits;
+        self
+    }
+
+    /// The maximum size of a received frame in bytes
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl Decoder for BencodeCodec {
+    type Error = Error;
+    type Item = Frame;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+        match self.scanner.scan(src, self.offset)? {
+            Scan::Complete(len) => {
+                if len > self.max_frame_size {
+                    return Err(Error::FrameTooLarge(len, self.max_frame_size));
+                }
+
+                self.offset += len;
+                let raw = src.split_to(len).freeze();
+                let mut decoder = decoding::Decoder::new(&raw)
+                    .with_max_depth(self.max_depth)
+                    .with_limits(self.limits);
+                let object = decoder.next_object()?;
+                let value = object
+                    .map_or(
+                        Err(decoding::Error::from(StructureError::UnexpectedEof)),
+                        Value::decode_bencode_object,
+                    )
+                    .map_err(|err| decoder.locate_error(err))?;
+
+                Ok(Some(Frame { raw, value }))
+            },
+            Scan::NeedMoreData(missing) => {
+                let required = src.len().saturating_add(missing);
+                if required > self.max_frame_size {
+                    return Err(Error::FrameTooLarge(required, self.max_frame_size));
+                }
+
+                src.reserve(missing);
+                Ok(None)
+            },
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => Err(decoding::Error::from(StructureError::UnexpectedEof).into()),
+        }
+    }
+}
+
+impl<T: ToBencode> Encoder<T> for BencodeCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Error> {
+        let encoded = item.to_bencode()?;
+        dst.extend_from_slice(&encoded);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{executor::block_on, StreamExt};
+    use tokio_util::codec::FramedRead;
+
+    use super::*;
+
+    #[test]
+    fn frames_are_split_at_value_boundaries() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::new();
+        let mut frames = Vec::new();
+
+        for byte in b"d3:fooli1eee4:spami-3e".iter() {
+            buffer.extend_from_slice(&[*byte]);
+            while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+                frames.push(frame.raw);
+            }
+        }
+
+        assert_eq!(
+            frames,
+            vec![&b"d3:fooli1eee"[..], &b"4:spam"[..], &b"i-3e"[..]]
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn frames_contain_decoded_values() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::from(&b"li1e3:abce"[..]);
+
+        let frame = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(
+            frame.value,
+            Value::List(vec![
+                Value::Integer(1.into()),
+                Value::Bytes(b"abc".to_vec().into())
+            ])
+        );
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected_early() {
+        let mut codec = BencodeCodec::new().with_max_frame_size(16);
+        let mut buffer = BytesMut::from(&b"1000:"[..]);
+
+        match codec.decode(&mut buffer) {
+            Err(Error::FrameTooLarge(1005, 16)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn buffered_oversized_frames_are_rejected() {
+        let mut codec = BencodeCodec::new().with_max_frame_size(4);
+        let mut buffer = BytesMut::from(&b"l3:fooi1ee"[..]);
+
+        match codec.decode(&mut buffer) {
+            Err(Error::FrameTooLarge(10, 4)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let mut codec = BencodeCodec::new().with_max_depth(2);
+        let mut buffer = BytesMut::from(&b"lleelllee"[..]);
+
+        let frame = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(frame.value, Value::List(vec![Value::List(vec![])]));
+        assert!(codec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn limits_apply_to_each_frame() {
+        let limits = DecodeLimits::new()
+            .with_max_container_items(2)
+            .with_max_allocation(16 + 4 * std::mem::size_of::<Value>());
+        let mut codec = BencodeCodec::new().with_limits(limits);
+        let mut buffer = BytesMut::from(&b"li1ei2eeli3ei4eeli5ei6ei7ee"[..]);
+
+        assert!(codec.decode(&mut buffer).unwrap().is_some());
+        assert!(codec.decode(&mut buffer).unwrap().is_some());
+        assert!(codec.decode(&mut buffer).is_err());
+
+        let mut codec = BencodeCodec::new().with_limits(limits);
+        let mut buffer = BytesMut::from(&b"l100:"[..]);
+        buffer.resize(buffer.len() + 100, b'a');
+        buffer.extend_from_slice(b"e");
+        assert!(codec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn invalid_frames_should_fail() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::from(&b"i1ed3:fooi1e3:bari2ee"[..]);
+
+        assert!(codec.decode(&mut buffer).unwrap().is_some());
+        let err = codec.decode(&mut buffer).unwrap_err().to_string();
+        assert!(err.contains("Keys were not sorted"), "{}", err);
+    }
+
+    #[test]
+    fn truncated_streams_should_fail() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::from(&b"li1e"[..]);
+
+        assert!(codec.decode_eof(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn values_can_be_encoded() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::new();
+
+        codec.encode(vec![1, 2], &mut buffer).unwrap();
+        codec.encode("foo", &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..], b"li1ei2ee3:foo");
+    }
+
+    #[test]
+    fn framed_reader_yields_all_frames() {
+        let input: &[u8] = b"i1ei2ei3e";
+        let frames = block_on(FramedRead::new(input, BencodeCodec::new()).collect::<Vec<_>>());
+
+        let values = frames
+            .into_iter()
+            .map(|frame| frame.unwrap().value)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                Value::Integer(1.into()),
+                Value::Integer(2.into()),
+                Value::Integer(3.into())
+            ]
+        );
+    }
+}
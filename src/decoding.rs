@@ -45,6 +45,26 @@
 //! };
 //! ```
 //!
+//! # Decoding from a reader
+//!
+//! If the input is too large to be buffered completely, a [`StreamDecoder`] can pull it
+//! from any [`std::io::Read`] on demand. It offers the same token and object interface, but
+//! only buffers the token that is currently being decoded.
+//!
+//! # Decoding partial input
+//!
+//! When the input arrives in fragments, e.g. from a network socket, a [`PushDecoder`] can
+//! be used to find the boundaries of complete values without having to decode them again
+//! from the start whenever more data arrives. See its documentation for details.
+//!
+//! # Byte positions
+//!
+//! [`Decoder::spanned_tokens`] yields the range of bytes occupied by every token, and
+//! [`Decoder::next_spanned_object`] and its counterparts on [`ListDecoder`] and
+//! [`DictDecoder`] return a [`SpannedObject`] that records where an object starts. This
+//! allows pointing at the exact location of a problem, or hashing a nested structure in
+//! its original encoding.
+//!
 //! # Error handling
 //!
 //! Once an error is encountered, the decoder won't try to muddle through it; instead, every future
@@ -62,15 +82,49 @@
 //! #
 //! # assert!(syntax_check(b"i18e"));
 //! ```
+//!
+//! Errors in the structure of the input record the byte offset at which they were detected
+//! ([`Error::offset`]) and the dictionary keys and list indices leading to it
+//! ([`Error::path`]). The [`StructureError`] returned by [`Error::kind`] describes the
+//! problem itself, e.g. which characters would have been valid instead.
+//!
+//! Errors returned by [`FromBencode::decode_bencode_object`] implementations, e.g. a
+//! number out of range or invalid UTF-8, carry the path of the value that failed to
+//! decode when they are returned through [`FromBencode::from_bencode`]. Unless the
+//! implementation attached its own context, the path is also shown in the message, like
+//! `info.files.3.length`.
+//!
+//! [`StructureError`]: crate::state_tracker::StructureError
 
 mod decoder;
 mod error;
-mod from_bencode;
+pub(crate) mod from_bencode;
+mod from_bencode_borrowed;
+mod lexer;
+mod limits;
 mod object;
+mod pointer;
+pub(crate) mod push;
+#[cfg(feature = "std")]
+mod stream;
+mod validate;
+mod violation;
 
 pub use self::{
-    decoder::{Decoder, DictDecoder, ListDecoder, Tokens},
-    error::{Error, ErrorKind, ResultExt},
+    decoder::{Decoder, DictDecoder, ListDecoder, RawValues, SpannedTokens, Tokens},
+    error::{Error, ErrorKind, PathSegment, ResultExt},
     from_bencode::FromBencode,
-    object::Object,
+    from_bencode_borrowed::FromBencodeBorrowed,
+    limits::DecodeLimits,
+    object::{Object, SpannedObject},
+    pointer::resolve_pointer,
+    push::{Progress, PushDecoder},
+    validate::{validate, validate_with_max_depth, Statistics},
+    violation::{Violation, ViolationKind},
 };
+
+#[cfg(feature = "derive")]
+pub use bendy_derive::FromBencode;
+
+#[cfg(feature = "std")]
+pub use self::stream::{StreamDecoder, StreamDictDecoder, StreamListDecoder, StreamObject};
@@ -1,8 +1,14 @@
-use alloc::format;
-use core::str;
+use alloc::collections::BTreeSet;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::{mem, ops::Range};
 
 use crate::{
-    decoding::{Error, Object},
+    decoding::{
+        lexer::{lex_token_with, Lexed},
+        limits::Budget,
+        DecodeLimits, Error, Object, PathSegment, SpannedObject, Violation, ViolationKind,
+    },
     state_tracker::{StateTracker, StructureError, Token},
 };
 
@@ -15,6 +21,18 @@ pub struct Decoder<'a> {
     source: &'a [u8],
     offset: usize,
     state: StateTracker<&'a [u8], Error>,
+    budget: Budget,
+    /// Whether the byte strings handed out are copied by the values decoded from them,
+    /// and therefore charged against the allocation budget
+    copies_strings: bool,
+    lenient: bool,
+    violations: Vec<Violation>,
+    /// The keys seen so far in each open container that is a dict. Only tracked in
+    /// lenient mode, to tell duplicate keys from unsorted ones.
+    dict_keys: Vec<Option<BTreeSet<&'a [u8]>>>,
+    /// The location of the innermost value whose container was dropped before it was
+    /// read completely, which is most likely where decoding failed
+    abandoned_path: Option<Vec<PathSegment>>,
 }
 
 impl<'ser> Decoder<'ser> {
@@ -24,6 +42,12 @@ impl<'ser> Decoder<'ser> {
             source: buffer,
             offset: 0,
             state: StateTracker::new(),
+            budget: Budget::default(),
+            copies_strings: true,
+            lenient: false,
+            violations: Vec::new(),
+            dict_keys: Vec::new(),
+            abandoned_path: None,
         }
     }
 
@@ -35,148 +59,149 @@ impl<'ser> Decoder<'ser> {
         self
     }
 
-    fn take_byte(&mut self) -> Option<u8> {
-        if self.offset < self.source.len() {
-            let ret = Some(self.source[self.offset]);
-            self.offset += 1;
-            ret
-        } else {
-            None
-        }
+    /// Set the resource limits of the decoder. See [`DecodeLimits`] for details.
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.budget = Budget::new(limits);
+        self
+    }
+
+    /// Accept input that isn't canonical bencode, i.e. dictionaries with unsorted or
+    /// duplicate keys and integers with leading zeros. Every deviation is recorded in
+    /// [`Decoder::violations()`], so the caller can decide whether to reject the input,
+    /// warn about it or re-encode it canonically.
+    ///
+    /// Dictionaries with duplicate keys yield every occurrence of the key.
+    ///
+    /// ```
+    /// use bendy::decoding::{Decoder, ViolationKind};
+    ///
+    /// let mut decoder = Decoder::new(b"d3:fooi1e3:bari02ee").with_lenient(true);
+    /// decoder.next_object().unwrap().unwrap().try_into_dictionary().unwrap().consume_all().unwrap();
+    ///
+    /// let kinds: Vec<_> = decoder.violations().iter().map(|v| v.kind).collect();
+    /// assert_eq!(kinds, [ViolationKind::UnsortedKey, ViolationKind::NonCanonicalInteger]);
+    /// ```
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
     }
 
-    fn take_chunk(&mut self, count: usize) -> Option<&'ser [u8]> {
-        match self.offset.checked_add(count) {
-            Some(end_pos) if end_pos <= self.source.len() => {
-                let ret = &self.source[self.offset..end_pos];
-                self.offset = end_pos;
-                Some(ret)
+    /// The deviations from canonical bencode that were accepted so far, in the order
+    /// they appear in the input. Always empty unless the decoder is lenient.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Read the next token, along with whether it was encoded canonically
+    fn raw_next_token(&mut self) -> Result<(Token<'ser>, bool), StructureError> {
+        let remaining = &self.source[self.offset..];
+        match lex_token_with(remaining, self.offset, self.lenient)? {
+            (Lexed::Token(token, len), canonical) => {
+                let token = token.resolve(remaining);
+                self.offset += len;
+                Ok((token, canonical))
             },
-            _ => None,
+            (Lexed::Incomplete(_), _) => Err(StructureError::UnexpectedEof),
         }
     }
 
-    fn take_int(&mut self, expected_terminator: char) -> Result<&'ser str, StructureError> {
-        enum State {
-            Start,
-            Sign,
-            Zero,
-            Digits,
-        }
-
-        let mut curpos = self.offset;
-        let mut state = State::Start;
-
-        let mut success = false;
-        while curpos < self.source.len() {
-            let c = self.source[curpos] as char;
-            match state {
-                State::Start => {
-                    if c == '-' {
-                        state = State::Sign;
-                    } else if c == '0' {
-                        state = State::Zero;
-                    } else if c >= '1' && c <= '9' {
-                        state = State::Digits;
-                    } else {
-                        return Err(StructureError::unexpected("'-' or '0'..'9'", c, curpos));
-                    }
-                },
-                State::Zero => {
-                    if c == expected_terminator {
-                        success = true;
-                        break;
-                    } else {
-                        return Err(StructureError::unexpected(
-                            &format!("{:?}", expected_terminator),
-                            c,
-                            curpos,
-                        ));
-                    }
-                },
-                State::Sign => {
-                    if c >= '1' && c <= '9' {
-                        state = State::Digits;
-                    } else {
-                        return Err(StructureError::unexpected("'1'..'9'", c, curpos));
-                    }
-                },
-                State::Digits => {
-                    if c >= '0' && c <= '9' {
-                        // do nothing, this is ok
-                    } else if c == expected_terminator {
-                        success = true;
-                        break;
-                    } else {
-                        return Err(StructureError::unexpected(
-                            &format!("{:?} or '0'..'9'", expected_terminator),
-                            c,
-                            curpos,
-                        ));
-                    }
-                },
-            }
-            curpos += 1;
+    /// Read the next token. Returns Ok(Some(token)) if a token was successfully read,
+    fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
+        self.state.check_error()?;
+
+        if let Err(err) = self.budget.check_input_size(self.source.len()) {
+            let limit = self.budget.limits().max_input_size();
+            return self.state.latch_at(Err(err.into()), limit);
         }
 
-        if !success {
-            return Err(StructureError::UnexpectedEof);
+        if self.offset == self.source.len() {
+            self.state.observe_eof_at(self.offset)?;
+            return Ok(None);
         }
 
-        let slice = &self.source[self.offset..curpos];
-        self.offset = curpos + 1;
-        let ival = if cfg!(debug) {
-            str::from_utf8(slice).expect("We've already examined every byte in the string")
-        } else {
-            // Avoid a second UTF-8 check here
-            unsafe { str::from_utf8_unchecked(slice) }
+        let start = self.offset;
+        let (tok_result, offset) = match self.raw_next_token() {
+            Ok((tok, canonical)) => (
+                self.observe_token(&tok, start, canonical).map(|()| tok),
+                start,
+            ),
+            Err(err @ StructureError::UnexpectedEof) => (Err(err), self.source.len()),
+            Err(err) => (Err(err), start),
         };
+        let tok = self.state.latch_at(tok_result, offset)?;
 
-        Ok(ival)
+        Ok(Some(tok))
     }
 
-    fn raw_next_token(&mut self) -> Result<Token<'ser>, Error> {
-        let token = match self.take_byte().ok_or(StructureError::UnexpectedEof)? as char {
-            'e' => Token::End,
-            'l' => Token::List,
-            'd' => Token::Dict,
-            'i' => Token::Num(self.take_int('e')?),
-            c if c >= '0' && c <= '9' => {
-                self.offset -= 1;
+    fn observe_token(
+        &mut self,
+        token: &Token<'ser>,
+        offset: usize,
+        canonical: bool,
+    ) -> Result<(), StructureError> {
+        self.budget.observe(token)?;
+        if !self.lenient {
+            return self.state.transition(token);
+        }
+
+        let is_key = self.state.expects_key();
+        let ordering = self.state.transition_lenient(token)?;
+
+        // Dict keys may have a non-canonical length and be out of order at the same time
+        if !canonical {
+            self.violations.push(Violation {
+                kind: ViolationKind::NonCanonicalInteger,
+                offset,
+            });
+        }
 
-                let curpos = self.offset;
-                let ival = self.take_int(':')?;
-                let len = usize::from_str_radix(ival, 10).map_err(|_| {
-                    StructureError::SyntaxError(format!("Invalid integer at offset {}", curpos))
-                })?;
-                Token::String(self.take_chunk(len).ok_or(StructureError::UnexpectedEof)?)
+        let violation = match *token {
+            Token::List => {
+                self.dict_keys.push(None);
+                None
             },
-            tok => {
-                return Err(Error::from(StructureError::SyntaxError(format!(
-                    "Invalid token starting with {:?} at offset {}",
-                    tok,
-                    self.offset - 1
-                ))));
+            Token::Dict => {
+                self.dict_keys.push(Some(BTreeSet::new()));
+                None
             },
+            Token::End => {
+                self.dict_keys.pop();
+                None
+            },
+            Token::String(key) if is_key => {
+                let is_new = match self.dict_keys.last_mut() {
+                    Some(Some(keys)) => keys.insert(key),
+                    _ => true,
+                };
+                if !is_new {
+                    Some(ViolationKind::DuplicateKey)
+                } else if ordering.is_some() {
+                    Some(ViolationKind::UnsortedKey)
+                } else {
+                    None
+                }
+            },
+            _ => None,
         };
 
-        Ok(token)
-    }
-
-    /// Read the next token. Returns Ok(Some(token)) if a token was successfully read,
-    fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
-        self.state.check_error()?;
-
-        if self.offset == self.source.len() {
-            self.state.observe_eof()?;
-            return Ok(None);
+        if let Some(kind) = violation {
+            self.violations.push(Violation { kind, offset });
         }
+        Ok(())
+    }
 
-        let tok_result = self.raw_next_token();
-        let tok = self.state.latch_err(tok_result)?;
+    /// Account for `bytes` of memory allocated by a decoded value
+    fn charge_allocation(&mut self, bytes: usize) -> Result<(), Error> {
+        self.budget.allocate(bytes).map_err(|err| {
+            Error::from(StructureError::from(err)).at(self.offset, self.state.item_path())
+        })
+    }
 
-        self.state.observe_token(&tok)?;
-        Ok(Some(tok))
+    /// Set whether the byte strings handed out from now on are copied by the values
+    /// decoded from them, and return the previous setting. Only copied strings are
+    /// charged against the allocation budget; dict keys never are.
+    pub(crate) fn set_copies_strings(&mut self, copies: bool) -> bool {
+        mem::replace(&mut self.copies_strings, copies)
     }
 
     /// Iterate over the tokens in the input stream. This guarantees that the resulting stream
@@ -184,6 +209,34 @@ impl<'ser> Decoder<'ser> {
     pub fn tokens(self) -> Tokens<'ser> {
         Tokens(self)
     }
+
+    /// Iterate over the tokens in the input stream along with the range of bytes each of
+    /// them occupies. Otherwise, this behaves exactly like [`Decoder::tokens()`].
+    pub fn spanned_tokens(self) -> SpannedTokens<'ser> {
+        SpannedTokens(self)
+    }
+
+    /// The position of the next byte to be decoded, relative to the start of the input
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The part of the input that hasn't been decoded yet
+    ///
+    /// This allows decoding a bencoded header that is followed by data in another format,
+    /// e.g. the piece data after the dictionary of a `ut_metadata` message.
+    ///
+    /// ```
+    /// use bendy::decoding::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(b"d5:piecei0ee\x00\x01\x02");
+    /// decoder.next_object().unwrap().unwrap().try_into_dictionary().unwrap().consume_all().unwrap();
+    ///
+    /// assert_eq!(decoder.remaining(), b"\x00\x01\x02");
+    /// ```
+    pub fn remaining(&self) -> &'ser [u8] {
+        &self.source[self.offset..]
+    }
 }
 
 /// Iterator over the tokens in the input stream. This guarantees that the resulting stream
@@ -206,6 +259,47 @@ impl<'a> Iterator for Tokens<'a> {
     }
 }
 
+/// Iterator over the tokens in the input stream and the byte ranges they occupy. This
+/// guarantees that the resulting stream of tokens constitutes a valid bencoded structure.
+pub struct SpannedTokens<'a>(Decoder<'a>);
+
+impl<'a> Iterator for SpannedTokens<'a> {
+    type Item = Result<(Token<'a>, Range<usize>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Only report an error once
+        if self.0.state.check_error().is_err() {
+            return None;
+        }
+        let start = self.0.offset;
+        match self.0.next_token() {
+            Ok(Some(token)) => Some(Ok((token, start..self.0.offset))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Iterator over the complete top level values in the input stream, yielding the raw
+/// bytes of each value along with the range they occupy
+pub struct RawValues<'dec, 'ser: 'dec>(&'dec mut Decoder<'ser>);
+
+impl<'dec, 'ser: 'dec> Iterator for RawValues<'dec, 'ser> {
+    type Item = Result<(&'ser [u8], Range<usize>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Only report an error once
+        if self.0.state.check_error().is_err() {
+            return None;
+        }
+        match self.0.next_raw_value() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
 // High level interface
 
 impl<'ser> Decoder<'ser> {
@@ -219,14 +313,124 @@ impl<'ser> Decoder<'ser> {
     /// returned from this method, so you may still get an error while decoding the contents
     /// of the object
     pub fn next_object<'obj>(&'obj mut self) -> Result<Option<Object<'obj, 'ser>>, Error> {
+        Ok(self.next_spanned_object()?.map(|spanned| spanned.object))
+    }
+
+    /// Read the next object from the encoded stream along with its position in the input
+    ///
+    /// This behaves exactly like [`Decoder::next_object()`]. See [`SpannedObject`] for
+    /// how to obtain the end of lists and dictionaries.
+    pub fn next_spanned_object<'obj>(
+        &'obj mut self,
+    ) -> Result<Option<SpannedObject<'obj, 'ser>>, Error> {
+        self.abandoned_path = None;
+        self.read_object(true)
+    }
+
+    /// Read the next object. If `decoded` is set, i.e. if the object is handed out to be
+    /// decoded rather than skipped, byte strings are charged against the allocation
+    /// budget unless the values decoded from them borrow them.
+    fn read_object<'obj>(
+        &'obj mut self,
+        decoded: bool,
+    ) -> Result<Option<SpannedObject<'obj, 'ser>>, Error> {
         use self::Token::*;
-        Ok(match self.next_token()? {
-            None | Some(End) => None,
-            Some(List) => Some(Object::List(ListDecoder::new(self))),
-            Some(Dict) => Some(Object::Dict(DictDecoder::new(self))),
-            Some(String(s)) => Some(Object::Bytes(s)),
-            Some(Num(s)) => Some(Object::Integer(s)),
-        })
+        let start = self.offset;
+        let (end, object) = match self.next_token()? {
+            None | Some(End) => return Ok(None),
+            Some(List) => (None, Object::List(ListDecoder::new(self))),
+            Some(Dict) => (None, Object::Dict(DictDecoder::new(self))),
+            Some(String(s)) => {
+                if decoded && self.copies_strings {
+                    self.charge_allocation(s.len())?;
+                }
+                (Some(self.offset), Object::Bytes(s))
+            },
+            Some(Num(s)) => (Some(self.offset), Object::Integer(s)),
+        };
+
+        Ok(Some(SpannedObject { start, end, object }))
+    }
+
+    /// Iterate over the complete top level values in the input stream, e.g. a sequence
+    /// of concatenated messages. Each item contains the raw bytes of a value along with
+    /// the range of bytes it occupies.
+    ///
+    /// The iterator borrows the decoder, so [`Decoder::remaining()`] can be used to
+    /// inspect the rest of the input after taking some of the values.
+    ///
+    /// ```
+    /// use bendy::decoding::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(b"i1e3:fooli2ee");
+    /// let values: Vec<_> = decoder.raw_values().map(Result::unwrap).collect();
+    ///
+    /// assert_eq!(values[1], (&b"3:foo"[..], 3..8));
+    /// assert_eq!(values[2], (&b"li2ee"[..], 8..13));
+    /// ```
+    pub fn raw_values<'dec>(&'dec mut self) -> RawValues<'dec, 'ser> {
+        RawValues(self)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn next_raw_value(&mut self) -> Result<Option<(&'ser [u8], Range<usize>)>, Error> {
+        let span = match self.read_object(false)? {
+            None => return Ok(None),
+            Some(SpannedObject {
+                object: Object::List(list),
+                ..
+            }) => list.span()?,
+            Some(SpannedObject {
+                object: Object::Dict(dict),
+                ..
+            }) => dict.span()?,
+            Some(SpannedObject {
+                start,
+                end: Some(end),
+                ..
+            }) => start..end,
+            Some(SpannedObject { end: None, .. }) => {
+                unreachable!("only lists and dictionaries have no end")
+            },
+        };
+
+        Ok(Some((&self.source[span.clone()], span)))
+    }
+
+    /// Create a decoder that reads the input again from `start`, with the configuration
+    /// and the remaining budget of this decoder
+    fn replay(&self, start: usize) -> Decoder<'ser> {
+        let mut state = StateTracker::new();
+        state.set_max_depth(self.state.remaining_depth());
+
+        Decoder {
+            source: self.source,
+            offset: start,
+            state,
+            budget: self.budget.replay(),
+            copies_strings: self.copies_strings,
+            lenient: self.lenient,
+            violations: Vec::new(),
+            dict_keys: Vec::new(),
+            abandoned_path: None,
+        }
+    }
+
+    /// Remember the location of the current value, unless a more deeply nested one has
+    /// already been recorded
+    fn abandon(&mut self) {
+        if self.abandoned_path.is_none() {
+            self.abandoned_path = Some(self.state.item_path());
+        }
+    }
+
+    /// Attach the location of the value that was being decoded when `error` was raised.
+    /// Errors that already know their location are left untouched.
+    pub(crate) fn locate_error(&mut self, error: Error) -> Error {
+        match self.abandoned_path.take() {
+            Some(path) => error.with_path(path),
+            None => error,
+        }
     }
 }
 
@@ -261,17 +465,41 @@ impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
     pub fn next_pair<'item>(
         &'item mut self,
     ) -> Result<Option<(&'ser [u8], Object<'item, 'ser>)>, Error> {
+        let pair = self.next_spanned_pair()?;
+        Ok(pair.map(|(key, _, value)| (key, value.object)))
+    }
+
+    /// Parse the next key/value pair from the dictionary along with the byte range of the
+    /// key and the position of the value. Returns `Ok(None)` at the end of the dictionary
+    #[allow(clippy::type_complexity)]
+    pub fn next_spanned_pair<'item>(
+        &'item mut self,
+    ) -> Result<Option<(&'ser [u8], Range<usize>, SpannedObject<'item, 'ser>)>, Error> {
+        self.decoder.abandoned_path = None;
+        self.read_pair(true)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn read_pair<'item>(
+        &'item mut self,
+        decoded: bool,
+    ) -> Result<Option<(&'ser [u8], Range<usize>, SpannedObject<'item, 'ser>)>, Error> {
         if self.finished {
             return Ok(None);
         }
 
         // We convert to a token to release the mut ref to decoder
-        let key = self.decoder.next_object()?.map(Object::into_token);
+        let key_start = self.decoder.offset;
+        let key = self
+            .decoder
+            .read_object(false)?
+            .map(|spanned| spanned.object.into_token());
 
         if let Some(Token::String(k)) = key {
+            let key_span = key_start..self.decoder.offset;
             // This unwrap should be safe because None would produce an error here
-            let v = self.decoder.next_object()?.unwrap();
-            Ok(Some((k, v)))
+            let v = self.decoder.read_object(decoded)?.unwrap();
+            Ok(Some((k, key_span, v)))
         } else {
             // We can't have gotten anything but a string, as anything else would be
             // a state error
@@ -284,7 +512,7 @@ impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
     /// dictionary. This method should be used to check for encoding errors if
     /// [`DictDecoder::next_pair`] is not called until it returns `Ok(None)`.
     pub fn consume_all(&mut self) -> Result<(), Error> {
-        while let Some(_) = self.next_pair()? {
+        while let Some(_) = self.read_pair(false)? {
             // just drop the items
         }
         Ok(())
@@ -295,10 +523,53 @@ impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
         self.consume_all()?;
         Ok(&self.decoder.source[self.start_point..self.decoder.offset])
     }
+
+    /// Charge `bytes` of memory allocated for the decoded contents of this dictionary
+    /// against the allocation budget of the decoder. Fails once the budget set with
+    /// [`DecodeLimits::with_max_allocation`] is exhausted.
+    pub fn charge_allocation(&mut self, bytes: usize) -> Result<(), Error> {
+        self.decoder.charge_allocation(bytes)
+    }
+
+    /// Set whether the byte strings read from now on are copied by the values decoded
+    /// from them, and return the previous setting
+    pub(crate) fn set_copies_strings(&mut self, copies: bool) -> bool {
+        self.decoder.set_copies_strings(copies)
+    }
+
+    /// Consume the rest of the dictionary and get a decoder that reads it again from its
+    /// start, with the configuration and the remaining budget of this decoder. Offsets
+    /// reported by the new decoder refer to the complete input, and its violations
+    /// have been recorded here already.
+    pub(crate) fn replay(&mut self) -> Result<Decoder<'ser>, Error> {
+        self.consume_all()?;
+        Ok(self.decoder.replay(self.start_point))
+    }
+
+    /// Charge the memory allocated by values decoded from a decoder returned by
+    /// [`DictDecoder::replay`] against the allocation budget of this decoder
+    pub(crate) fn absorb(&mut self, replayed: &Decoder<'ser>) {
+        self.decoder.budget.absorb(&replayed.budget);
+    }
+
+    /// The position of the `d` that starts this dictionary
+    pub fn start(&self) -> usize {
+        self.start_point
+    }
+
+    /// Consume the rest of the dictionary and get the range of bytes it occupies,
+    /// including the leading `d` and the trailing `e`
+    pub fn span(mut self) -> Result<Range<usize>, Error> {
+        self.consume_all()?;
+        Ok(self.start_point..self.decoder.offset)
+    }
 }
 
 impl<'obj, 'ser: 'obj> Drop for DictDecoder<'obj, 'ser> {
     fn drop(&mut self) {
+        if !self.finished {
+            self.decoder.abandon();
+        }
         // we don't care about errors in drop; they'll be reported again in the parent
         self.consume_all().ok();
     }
@@ -316,11 +587,27 @@ impl<'obj, 'ser: 'obj> ListDecoder<'obj, 'ser> {
 
     /// Get the next item from the list. Returns `Ok(None)` at the end of the list
     pub fn next_object<'item>(&'item mut self) -> Result<Option<Object<'item, 'ser>>, Error> {
+        Ok(self.next_spanned_object()?.map(|spanned| spanned.object))
+    }
+
+    /// Get the next item from the list along with its position in the input. Returns
+    /// `Ok(None)` at the end of the list
+    pub fn next_spanned_object<'item>(
+        &'item mut self,
+    ) -> Result<Option<SpannedObject<'item, 'ser>>, Error> {
+        self.decoder.abandoned_path = None;
+        self.read_object(true)
+    }
+
+    fn read_object<'item>(
+        &'item mut self,
+        decoded: bool,
+    ) -> Result<Option<SpannedObject<'item, 'ser>>, Error> {
         if self.finished {
             return Ok(None);
         }
 
-        let item = self.decoder.next_object()?;
+        let item = self.decoder.read_object(decoded)?;
         if item.is_none() {
             self.finished = true;
         }
@@ -334,7 +621,7 @@ impl<'obj, 'ser: 'obj> ListDecoder<'obj, 'ser> {
     ///
     /// [`Ok(())`]: https://doc.rust-lang.org/std/result/enum.Result.html#variant.Ok
     pub fn consume_all(&mut self) -> Result<(), Error> {
-        while let Some(_) = self.next_object()? {
+        while let Some(_) = self.read_object(false)? {
             // just drop the items
         }
         Ok(())
@@ -345,10 +632,38 @@ impl<'obj, 'ser: 'obj> ListDecoder<'obj, 'ser> {
         self.consume_all()?;
         Ok(&self.decoder.source[self.start_point..self.decoder.offset])
     }
+
+    /// Charge `bytes` of memory allocated for the decoded contents of this list
+    /// against the allocation budget of the decoder. Fails once the budget set with
+    /// [`DecodeLimits::with_max_allocation`] is exhausted.
+    pub fn charge_allocation(&mut self, bytes: usize) -> Result<(), Error> {
+        self.decoder.charge_allocation(bytes)
+    }
+
+    /// Set whether the byte strings read from now on are copied by the values decoded
+    /// from them, and return the previous setting
+    pub(crate) fn set_copies_strings(&mut self, copies: bool) -> bool {
+        self.decoder.set_copies_strings(copies)
+    }
+
+    /// The position of the `l` that starts this list
+    pub fn start(&self) -> usize {
+        self.start_point
+    }
+
+    /// Consume the rest of the list and get the range of bytes it occupies, including the
+    /// leading `l` and the trailing `e`
+    pub fn span(mut self) -> Result<Range<usize>, Error> {
+        self.consume_all()?;
+        Ok(self.start_point..self.decoder.offset)
+    }
 }
 
 impl<'obj, 'ser: 'obj> Drop for ListDecoder<'obj, 'ser> {
     fn drop(&mut self) {
+        if !self.finished {
+            self.decoder.abandon();
+        }
         // we don't care about errors in drop; they'll be reported again in the parent
         self.consume_all().ok();
     }
@@ -358,12 +673,16 @@ impl<'obj, 'ser: 'obj> Drop for ListDecoder<'obj, 'ser> {
 mod test {
 
     #[cfg(not(feature = "std"))]
-    use alloc::{vec, vec::Vec};
+    use alloc::{format, vec, vec::Vec};
     use core::iter;
 
     use regex;
 
     use super::*;
+    use crate::{
+        decoding::{ErrorKind, PathSegment, ViolationKind},
+        state_tracker::{Expected, InvalidState, LimitExceeded, SyntaxError, TokenKind},
+    };
 
     static SIMPLE_MSG: &'static [u8] = b"d3:bari1e3:fooli2ei3eee";
 
@@ -518,6 +837,142 @@ mod test {
         assert_eq!(token, Token::Num("1000"));
     }
 
+    fn first_error(msg: &[u8]) -> Error {
+        Decoder::new(msg)
+            .tokens()
+            .find_map(Result::err)
+            .expect("Expected a decoding error")
+    }
+
+    fn structure_error(err: &Error) -> &StructureError {
+        match err.kind() {
+            ErrorKind::StructureError(err) => err,
+            other => panic!("Unexpected error kind: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_are_structured() {
+        let err = first_error(b"d3:fooli1ei01eee");
+
+        assert_eq!(
+            structure_error(&err),
+            &StructureError::SyntaxError(SyntaxError::UnexpectedCharacter {
+                expected: Expected::Terminator('e'),
+                found: '1',
+                offset: 12,
+            })
+        );
+        assert_eq!(err.offset(), Some(12));
+        assert_eq!(
+            err.path(),
+            &[PathSegment::Key(b"foo".to_vec()), PathSegment::Index(1)][..]
+        );
+    }
+
+    #[test]
+    fn state_errors_are_structured() {
+        let err = first_error(b"d3:fooi1e3:bari2ee");
+        assert_eq!(structure_error(&err), &StructureError::UnsortedKeys);
+        assert_eq!(err.offset(), Some(9));
+        assert!(err.path().is_empty());
+
+        let err = first_error(b"ldi1ei2eee");
+        let invalid_state = InvalidState::NonStringKey(TokenKind::Num);
+        assert_eq!(
+            structure_error(&err),
+            &StructureError::InvalidState(invalid_state.clone())
+        );
+        assert_eq!(invalid_state.expected(), Some(TokenKind::String));
+        assert_eq!(err.offset(), Some(2));
+        assert_eq!(err.path(), &[PathSegment::Index(0)][..]);
+    }
+
+    #[test]
+    fn eof_errors_point_at_the_end_of_the_input() {
+        let err = first_error(b"li1eld3:foo");
+        assert_eq!(structure_error(&err), &StructureError::UnexpectedEof);
+        assert_eq!(err.offset(), Some(11));
+        assert_eq!(
+            err.path(),
+            &[
+                PathSegment::Index(1),
+                PathSegment::Index(0),
+                PathSegment::Key(b"foo".to_vec())
+            ][..]
+        );
+    }
+
+    #[test]
+    fn spanned_tokens_should_cover_the_input() {
+        use self::Token::*;
+        let tokens = Decoder::new(b"d3:fooli-12eee")
+            .spanned_tokens()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Dict, 0..1),
+                (String(&b"foo"[..]), 1..6),
+                (List, 6..7),
+                (Num("-12"), 7..12),
+                (End, 12..13),
+                (End, 13..14),
+            ]
+        );
+    }
+
+    #[test]
+    fn spanned_objects_should_report_positions() {
+        let mut decoder = Decoder::new(b"i1ed3:barli2ee3:foo4:spame");
+
+        let first = decoder.next_spanned_object().unwrap().unwrap().atom_span();
+        assert_eq!(first, Some(0..3));
+
+        let mut dict = decoder
+            .next_spanned_object()
+            .unwrap()
+            .unwrap()
+            .object
+            .try_into_dictionary()
+            .unwrap();
+        assert_eq!(dict.start(), 3);
+
+        {
+            let (key, key_span, value) = dict.next_spanned_pair().unwrap().unwrap();
+            assert_eq!((key, key_span), (&b"bar"[..], 4..9));
+            assert_eq!((value.start, value.end), (9, None));
+            assert_eq!(value.object.try_into_list().unwrap().span().unwrap(), 9..14);
+        }
+
+        let (_, _, value) = dict.next_spanned_pair().unwrap().unwrap();
+        assert_eq!(value.atom_span(), Some(19..25));
+        drop(value);
+
+        assert_eq!(dict.span().unwrap(), 3..26);
+        assert_eq!(decoder.offset(), 26);
+    }
+
+    #[test]
+    fn span_should_consume_the_rest_of_the_list() {
+        let mut decoder = Decoder::new(b"lli1eei2eei3e");
+        let mut list = decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_list()
+            .unwrap();
+
+        let item = list.next_spanned_object().unwrap().unwrap();
+        assert_eq!(item.start, 1);
+        drop(item);
+
+        assert_eq!(list.span().unwrap(), 0..10);
+        assert_eq!(decoder.offset(), 10);
+    }
+
     #[test]
     fn bytes_or_should_work_on_bytes() {
         assert_eq!(
@@ -800,4 +1255,281 @@ mod test {
                 .unwrap_err()
         );
     }
+
+    fn limit_error(msg: &[u8], limits: DecodeLimits) -> Error {
+        Decoder::new(msg)
+            .with_limits(limits)
+            .tokens()
+            .find_map(Result::err)
+            .expect("Expected a decoding error")
+    }
+
+    fn limit_exceeded(err: &Error) -> LimitExceeded {
+        match structure_error(err) {
+            StructureError::LimitExceeded(limit) => *limit,
+            other => panic!("Unexpected structure error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn inputs_within_limits_should_decode() {
+        let limits = DecodeLimits::new()
+            .with_max_input_size(SIMPLE_MSG.len())
+            .with_max_string_length(3)
+            .with_max_container_items(2)
+            .with_max_tokens(9);
+
+        let tokens: Result<Vec<_>, _> = Decoder::new(SIMPLE_MSG)
+            .with_limits(limits)
+            .tokens()
+            .collect();
+        assert!(tokens.is_ok());
+    }
+
+    #[test]
+    fn oversized_input_should_fail_immediately() {
+        let limits = DecodeLimits::new().with_max_input_size(4);
+        let err = limit_error(b"i1ei2e", limits);
+
+        assert_eq!(limit_exceeded(&err), LimitExceeded::InputSize { limit: 4 });
+        assert_eq!(err.offset(), Some(4));
+    }
+
+    #[test]
+    fn long_strings_should_fail() {
+        let limits = DecodeLimits::new().with_max_string_length(3);
+        let err = limit_error(b"l3:foo4:spame", limits);
+
+        assert_eq!(
+            limit_exceeded(&err),
+            LimitExceeded::StringLength {
+                length: 4,
+                limit: 3
+            }
+        );
+        assert_eq!(err.offset(), Some(6));
+        assert_eq!(err.path(), &[PathSegment::Index(1)]);
+    }
+
+    #[test]
+    fn large_containers_should_fail() {
+        let limits = DecodeLimits::new().with_max_container_items(2);
+
+        assert!(Decoder::new(b"d1:ai1e1:bli1ei2eee")
+            .with_limits(limits)
+            .tokens()
+            .all(|token| token.is_ok()));
+
+        let err = limit_error(b"d1:ai1e1:bi2e1:ci3ee", limits);
+        assert_eq!(
+            limit_exceeded(&err),
+            LimitExceeded::ContainerItems { limit: 2 }
+        );
+        assert_eq!(err.offset(), Some(13));
+
+        let err = limit_error(b"lleleleleee", limits);
+        assert_eq!(
+            limit_exceeded(&err),
+            LimitExceeded::ContainerItems { limit: 2 }
+        );
+        assert_eq!(err.path(), &[PathSegment::Index(2)]);
+    }
+
+    #[test]
+    fn too_many_tokens_should_fail() {
+        let limits = DecodeLimits::new().with_max_tokens(3);
+        let err = limit_error(b"li1ei2ee", limits);
+
+        assert_eq!(limit_exceeded(&err), LimitExceeded::TokenCount { limit: 3 });
+        assert_eq!(err.offset(), Some(7));
+    }
+
+    #[test]
+    fn allocations_should_be_charged_against_the_budget() {
+        let limits = DecodeLimits::new().with_max_allocation(16);
+        let mut decoder = Decoder::new(b"li1ei2ei3ee").with_limits(limits);
+        let mut list = decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_list()
+            .unwrap();
+
+        list.next_object().unwrap();
+        list.charge_allocation(8).unwrap();
+        list.next_object().unwrap();
+        list.charge_allocation(8).unwrap();
+        list.next_object().unwrap();
+        let err = list.charge_allocation(8).unwrap_err();
+
+        assert_eq!(
+            limit_exceeded(&err),
+            LimitExceeded::Allocation { limit: 16 }
+        );
+        assert_eq!(err.path(), &[PathSegment::Index(2)]);
+    }
+
+    #[test]
+    fn lenient_decoding_records_violations() {
+        let msg = b"d3:fooi1e3:bari02e3:bari-0e3:fooi3ee";
+        assert!(Decoder::new(msg).tokens().any(|token| token.is_err()));
+
+        let mut decoder = Decoder::new(msg).with_lenient(true);
+        let mut keys = Vec::new();
+        {
+            let mut dict = decoder
+                .next_object()
+                .unwrap()
+                .unwrap()
+                .try_into_dictionary()
+                .unwrap();
+            while let Some((key, value)) = dict.next_pair().unwrap() {
+                keys.push((key, value.try_into_integer().unwrap()));
+            }
+        }
+
+        assert_eq!(
+            keys,
+            vec![
+                (&b"foo"[..], "1"),
+                (&b"bar"[..], "02"),
+                (&b"bar"[..], "-0"),
+                (&b"foo"[..], "3")
+            ]
+        );
+        assert_eq!(
+            decoder.violations(),
+            &[
+                Violation {
+                    kind: ViolationKind::UnsortedKey,
+                    offset: 9
+                },
+                Violation {
+                    kind: ViolationKind::NonCanonicalInteger,
+                    offset: 14
+                },
+                Violation {
+                    kind: ViolationKind::DuplicateKey,
+                    offset: 18
+                },
+                Violation {
+                    kind: ViolationKind::NonCanonicalInteger,
+                    offset: 23
+                },
+                Violation {
+                    kind: ViolationKind::DuplicateKey,
+                    offset: 27
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_decoding_checks_key_lengths() {
+        let mut decoder = Decoder::new(b"d03:fooi1e03:bari2ee").with_lenient(true);
+        decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_dictionary()
+            .unwrap()
+            .consume_all()
+            .unwrap();
+
+        assert_eq!(
+            decoder.violations(),
+            &[
+                Violation {
+                    kind: ViolationKind::NonCanonicalInteger,
+                    offset: 1
+                },
+                Violation {
+                    kind: ViolationKind::NonCanonicalInteger,
+                    offset: 10
+                },
+                Violation {
+                    kind: ViolationKind::UnsortedKey,
+                    offset: 10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_decoding_tracks_keys_per_dict() {
+        let mut decoder = Decoder::new(b"ld1:ai1eed1:ai1ee03:abce").with_lenient(true);
+        decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_list()
+            .unwrap()
+            .consume_all()
+            .unwrap();
+
+        assert_eq!(
+            decoder.violations(),
+            &[Violation {
+                kind: ViolationKind::NonCanonicalInteger,
+                offset: 17
+            }]
+        );
+    }
+
+    #[test]
+    fn lenient_decoding_still_rejects_invalid_structure() {
+        let mut decoder = Decoder::new(b"di1ei2ee").with_lenient(true);
+        assert!(decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_dictionary()
+            .unwrap()
+            .next_pair()
+            .is_err());
+
+        let mut decoder = Decoder::new(b"i-e").with_lenient(true);
+        assert!(decoder.next_object().is_err());
+    }
+
+    #[test]
+    fn raw_values_should_cover_all_top_level_values() {
+        let mut decoder = Decoder::new(b"d1:ai1ee4:spamli1eei-3e");
+        let values: Vec<_> = decoder.raw_values().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                (&b"d1:ai1ee"[..], 0..8),
+                (&b"4:spam"[..], 8..14),
+                (&b"li1ee"[..], 14..19),
+                (&b"i-3e"[..], 19..23),
+            ]
+        );
+        assert!(decoder.remaining().is_empty());
+    }
+
+    #[test]
+    fn raw_values_should_stop_after_an_error() {
+        let mut decoder = Decoder::new(b"i1eei2e");
+        let values: Vec<_> = decoder.raw_values().collect();
+
+        assert_eq!(values.len(), 2);
+        assert!(values[0].is_ok());
+        assert!(values[1].is_err());
+    }
+
+    #[test]
+    fn remaining_should_return_the_undecoded_input() {
+        let mut decoder = Decoder::new(b"d8:msg_typei1e5:piecei0eeXYZ");
+        {
+            let mut values = decoder.raw_values();
+            let (header, span) = values.next().unwrap().unwrap();
+            assert_eq!(header, b"d8:msg_typei1e5:piecei0ee");
+            assert_eq!(span.end, 25);
+        }
+
+        assert_eq!(decoder.offset(), 25);
+        assert_eq!(decoder.remaining(), b"XYZ");
+    }
 }
@@ -6,11 +6,12 @@ use core::num::ParseIntError;
 use alloc::{
     format,
     string::{String, ToString},
+    vec::Vec,
 };
 use core::fmt::{self, Display, Formatter};
 
 #[cfg(feature = "std")]
-use std::{error::Error as StdError, sync::Arc};
+use std::{error::Error as StdError, io, sync::Arc};
 
 use failure::Fail;
 
@@ -22,6 +23,26 @@ pub struct Error {
     context: Option<String>,
     #[fail(cause)]
     error: ErrorKind,
+    offset: Option<usize>,
+    path: Vec<PathSegment>,
+}
+
+/// A single step on the way from the outermost value to the location of an error
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PathSegment {
+    /// The value of the given dictionary key
+    Key(Vec<u8>),
+    /// The list item at the given index
+    Index(usize),
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{}", String::from_utf8_lossy(key)),
+            PathSegment::Index(index) => write!(f, "{}", index),
+        }
+    }
 }
 
 /// An enumeration of potential errors that appear during bencode deserialization.
@@ -35,6 +56,15 @@ pub enum ErrorKind {
     #[cfg(not(feature = "std"))]
     #[fail(display = "malformed content discovered")]
     MalformedContent,
+    /// Error that occurs if the underlying reader fails.
+    #[cfg(feature = "std")]
+    #[fail(display = "i/o error: {}", _0)]
+    Io(Arc<io::Error>),
+    /// Error that occurs if a pointer passed to [`resolve_pointer`] is malformed.
+    ///
+    /// [`resolve_pointer`]: crate::decoding::resolve_pointer
+    #[fail(display = "malformed pointer: {}", _0)]
+    MalformedPointer(String),
     /// Error that occurs if the serialized structure is incomplete.
     #[fail(display = "missing field: {}", _0)]
     MissingField(String),
@@ -64,6 +94,50 @@ impl Error {
         self
     }
 
+    /// Record where in the input the error was detected
+    pub(crate) fn at(mut self, offset: usize, path: Vec<PathSegment>) -> Self {
+        self.offset = Some(offset);
+        self.path = path;
+        self
+    }
+
+    /// Record the location of the value that failed to decode, if the error doesn't
+    /// know its location yet. The path is also added as context, unless some context was
+    /// provided already.
+    pub(crate) fn with_path(mut self, path: Vec<PathSegment>) -> Self {
+        if self.offset.is_some() || !self.path.is_empty() || path.is_empty() {
+            return self;
+        }
+
+        if self.context.is_none() {
+            let segments: Vec<String> = path.iter().map(ToString::to_string).collect();
+            self.context = Some(segments.join("."));
+        }
+        self.path = path;
+        self
+    }
+
+    /// The kind of error that occurred
+    pub fn kind(&self) -> &ErrorKind {
+        &self.error
+    }
+
+    /// The position in the input at which the error was detected, if it is known.
+    ///
+    /// Errors that are raised while validating the structure of the input always carry
+    /// an offset; errors raised by [`FromBencode`] implementations usually don't.
+    ///
+    /// [`FromBencode`]: crate::decoding::FromBencode
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The dictionary keys and list indices leading from the outermost value to the
+    /// location of the error. Empty if the location is unknown or at the top level.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
     /// Raised when there is a general error while deserializing a type.
     /// The message should not be capitalized and should not end with a period.
     #[cfg(feature = "std")]
@@ -77,6 +151,12 @@ impl Error {
         Self::from(ErrorKind::MalformedContent)
     }
 
+    /// Raised when reading from the underlying data source fails.
+    #[cfg(feature = "std")]
+    pub fn io(cause: io::Error) -> Error {
+        Self::from(ErrorKind::Io(Arc::new(cause)))
+    }
+
     /// Returns a `Error::MissingField` which contains the name of the field.
     pub fn missing_field(field_name: impl Display) -> Error {
         Self::from(ErrorKind::MissingField(field_name.to_string()))
@@ -116,6 +196,8 @@ impl From<ErrorKind> for Error {
         Self {
             context: None,
             error: kind,
+            offset: None,
+            path: Vec::new(),
         }
     }
 }
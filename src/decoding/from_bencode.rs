@@ -1,6 +1,8 @@
 #[cfg(not(feature = "std"))]
 use alloc::{collections::BTreeMap, rc::Rc, string::String, vec::Vec};
 
+use core::mem;
+
 #[cfg(feature = "std")]
 use std::{
     collections::{BTreeMap, HashMap},
@@ -9,7 +11,7 @@ use std::{
 };
 
 use crate::{
-    decoding::{Decoder, Error, Object},
+    decoding::{DecodeLimits, Decoder, Error, ListDecoder, Object},
     encoding::AsString,
     state_tracker::StructureError,
 };
@@ -24,13 +26,26 @@ pub trait FromBencode {
     where
         Self: Sized,
     {
-        let mut decoder = Decoder::new(bytes).with_max_depth(Self::EXPECTED_RECURSION_DEPTH);
+        Self::from_bencode_with_limits(bytes, DecodeLimits::default())
+    }
+
+    /// Deserialize an object from its byte representation, spending no more resources
+    /// than `limits` allow.
+    fn from_bencode_with_limits(bytes: &[u8], limits: DecodeLimits) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let mut decoder = Decoder::new(bytes)
+            .with_max_depth(Self::EXPECTED_RECURSION_DEPTH)
+            .with_limits(limits);
         let object = decoder.next_object()?;
 
-        object.map_or(
-            Err(Error::from(StructureError::UnexpectedEof)),
-            Self::decode_bencode_object,
-        )
+        object
+            .map_or(
+                Err(Error::from(StructureError::UnexpectedEof)),
+                Self::decode_bencode_object,
+            )
+            .map_err(|err| decoder.locate_error(err))
     }
 
     /// Deserialize an object from its intermediate bencode representation.
@@ -59,6 +74,77 @@ macro_rules! impl_from_bencode_for_integer {
 
 impl_from_bencode_for_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
 
+/// Push `item` onto `items` and charge the memory the vector allocates to make room
+/// for it against the allocation budget of `list`
+pub(crate) fn push_charged<T>(
+    list: &mut ListDecoder,
+    items: &mut Vec<T>,
+    item: T,
+) -> Result<(), Error> {
+    let capacity = items.capacity();
+    items.push(item);
+    list.charge_allocation((items.capacity() - capacity) * mem::size_of::<T>())
+}
+
+/// Decode a list with `decode_item`, which copies the byte strings it reads if
+/// `copies_strings` is set. Shared by the owned and the borrowed implementations.
+pub(crate) fn decode_vec<'ser, T>(
+    object: Object<'_, 'ser>,
+    copies_strings: bool,
+    mut decode_item: impl FnMut(Object<'_, 'ser>) -> Result<T, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut list = object.try_into_list()?;
+    let copied_strings = list.set_copies_strings(copies_strings);
+    let mut results = Vec::new();
+
+    loop {
+        // The item is decoded in its own statement to release the borrow of `list`
+        let item = match list.next_object()? {
+            Some(object) => decode_item(object)?,
+            None => break,
+        };
+        push_charged(&mut list, &mut results, item)?;
+    }
+
+    list.set_copies_strings(copied_strings);
+    Ok(results)
+}
+
+/// Decode the entries of a dict with `decode_key` and `decode_value` and hand them to
+/// `insert`, which returns the number of bytes it allocated to store them. The flags
+/// tell whether the keys and the byte strings read by `decode_value` are copied. Shared
+/// by the owned and the borrowed implementations.
+pub(crate) fn decode_map<'ser, K, V>(
+    object: Object<'_, 'ser>,
+    copies_keys: bool,
+    copies_strings: bool,
+    mut decode_key: impl FnMut(Object<'_, 'ser>) -> Result<K, Error>,
+    mut decode_value: impl FnMut(Object<'_, 'ser>) -> Result<V, Error>,
+    mut insert: impl FnMut(K, V) -> usize,
+) -> Result<(), Error> {
+    let mut dict = object.try_into_dictionary()?;
+    let copied_strings = dict.set_copies_strings(copies_strings);
+
+    loop {
+        let (key_length, key, value) = match dict.next_pair()? {
+            Some((key, value)) => (
+                key.len(),
+                decode_key(Object::Bytes(key))?,
+                decode_value(value)?,
+            ),
+            None => break,
+        };
+        let mut allocated = insert(key, value);
+        if copies_keys {
+            allocated += key_length;
+        }
+        dict.charge_allocation(allocated)?;
+    }
+
+    dict.set_copies_strings(copied_strings);
+    Ok(())
+}
+
 impl<ContentT: FromBencode> FromBencode for Vec<ContentT> {
     const EXPECTED_RECURSION_DEPTH: usize = ContentT::EXPECTED_RECURSION_DEPTH + 1;
 
@@ -66,15 +152,7 @@ impl<ContentT: FromBencode> FromBencode for Vec<ContentT> {
     where
         Self: Sized,
     {
-        let mut list = object.try_into_list()?;
-        let mut results = Vec::new();
-
-        while let Some(object) = list.next_object()? {
-            let item = ContentT::decode_bencode_object(object)?;
-            results.push(item);
-        }
-
-        Ok(results)
+        decode_vec(object, true, ContentT::decode_bencode_object)
     }
 }
 
@@ -103,15 +181,18 @@ where
     where
         Self: Sized,
     {
-        let mut dict = object.try_into_dictionary()?;
         let mut result = BTreeMap::default();
-
-        while let Some((key, value)) = dict.next_pair()? {
-            let key = K::decode_bencode_object(Object::Bytes(key))?;
-            let value = V::decode_bencode_object(value)?;
-
-            result.insert(key, value);
-        }
+        decode_map(
+            object,
+            true,
+            true,
+            K::decode_bencode_object,
+            V::decode_bencode_object,
+            |key, value| {
+                result.insert(key, value);
+                mem::size_of::<(K, V)>()
+            },
+        )?;
 
         Ok(result)
     }
@@ -130,15 +211,19 @@ where
     where
         Self: Sized,
     {
-        let mut dict = object.try_into_dictionary()?;
         let mut result = HashMap::default();
-
-        while let Some((key, value)) = dict.next_pair()? {
-            let key = K::decode_bencode_object(Object::Bytes(key))?;
-            let value = V::decode_bencode_object(value)?;
-
-            result.insert(key, value);
-        }
+        decode_map(
+            object,
+            true,
+            true,
+            K::decode_bencode_object,
+            V::decode_bencode_object,
+            |key, value| {
+                let capacity = result.capacity();
+                result.insert(key, value);
+                (result.capacity() - capacity) * mem::size_of::<(K, V)>()
+            },
+        )?;
 
         Ok(result)
     }
@@ -170,9 +255,12 @@ impl FromBencode for AsString<Vec<u8>> {
 mod test {
 
     #[cfg(not(feature = "std"))]
-    use alloc::{format, vec::Vec};
+    use alloc::{format, string::ToString, vec, vec::Vec};
 
-    use crate::encoding::AsString;
+    use crate::{
+        decoding::{PathSegment, ResultExt},
+        encoding::AsString,
+    };
 
     use super::*;
 
@@ -213,4 +301,130 @@ mod test {
     fn from_bencode_to_as_string_should_fail_for_dictionary() {
         AsString::<Vec<u8>>::from_bencode(&b"d1:a1:ae"[..]).unwrap();
     }
+
+    #[test]
+    fn errors_in_nested_values_should_report_their_path() {
+        let err = Vec::<BTreeMap<String, u8>>::from_bencode(b"ld1:ai1eed1:ai300eee").unwrap_err();
+
+        assert_eq!(
+            err.path(),
+            &[PathSegment::Index(1), PathSegment::Key(b"a".to_vec())][..]
+        );
+        assert!(err.to_string().ends_with(" in 1.a"), "{}", err);
+    }
+
+    #[test]
+    fn missing_fields_should_report_the_path_of_their_parent() {
+        #[derive(Debug)]
+        struct Foo;
+
+        impl FromBencode for Foo {
+            fn decode_bencode_object(object: Object) -> Result<Self, Error> {
+                let mut dict = object.try_into_dictionary()?;
+                while dict.next_pair()?.is_some() {}
+                Err(Error::missing_field("foo"))
+            }
+        }
+
+        let err = BTreeMap::<String, Vec<Foo>>::from_bencode(b"d1:xld3:bari1eeee").unwrap_err();
+        assert_eq!(
+            err.path(),
+            &[PathSegment::Key(b"x".to_vec()), PathSegment::Index(0)][..]
+        );
+    }
+
+    #[test]
+    fn skipped_values_should_not_leak_into_the_path() {
+        #[derive(Debug)]
+        struct First(i64);
+
+        impl FromBencode for First {
+            fn decode_bencode_object(object: Object) -> Result<Self, Error> {
+                let mut list = object.try_into_list()?;
+                // Drop the nested list without reading it
+                list.next_object()?;
+                let value = list
+                    .next_object()?
+                    .ok_or_else(|| Error::missing_field("1"))?;
+                let value = i64::decode_bencode_object(value)?;
+                list.consume_all()?;
+                Ok(First(value))
+            }
+        }
+
+        assert_eq!(First::from_bencode(b"lli1eei2ee").unwrap().0, 2);
+
+        let err = First::from_bencode(b"lli1ee3:fooe").unwrap_err();
+        assert_eq!(err.path(), &[PathSegment::Index(1)][..]);
+    }
+
+    #[test]
+    fn explicit_context_should_be_preserved() {
+        #[derive(Debug)]
+        struct Wrapper(u8);
+
+        impl FromBencode for Wrapper {
+            fn decode_bencode_object(object: Object) -> Result<Self, Error> {
+                let mut list = object.try_into_list()?;
+                let value = list
+                    .next_object()?
+                    .ok_or_else(|| Error::missing_field("0"))?;
+                u8::decode_bencode_object(value)
+                    .context("first")
+                    .map(Wrapper)
+            }
+        }
+
+        assert_eq!(Wrapper::from_bencode(b"li3ee").unwrap().0, 3);
+
+        let err = Wrapper::from_bencode(b"li300ee").unwrap_err();
+        assert_eq!(err.path(), &[PathSegment::Index(0)][..]);
+        assert!(err.to_string().ends_with(" in first"), "{}", err);
+
+        let err = Vec::<u8>::from_bencode(b"li1ei300ee")
+            .context("numbers")
+            .unwrap_err();
+        assert!(err.to_string().ends_with(" in numbers.1"), "{}", err);
+    }
+
+    #[test]
+    fn collections_should_respect_the_allocation_budget() {
+        // Vectors are charged for their capacity, which starts out with four items
+        let limits = DecodeLimits::new().with_max_allocation(4 * mem::size_of::<u64>());
+
+        let decoded = Vec::<u64>::from_bencode_with_limits(b"li1ei2ei3ei4ee", limits).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3, 4]);
+
+        let err = Vec::<u64>::from_bencode_with_limits(b"li1ei2ei3ei4ei5ee", limits).unwrap_err();
+        assert_eq!(err.path(), &[PathSegment::Index(4)][..]);
+
+        // The entry of the map is charged after its value has been decoded, and both
+        // keys are charged as they are copied
+        let limits = limits.with_max_allocation(
+            2 + 4 * mem::size_of::<u64>() + mem::size_of::<(String, Vec<u64>)>(),
+        );
+        let err = BTreeMap::<String, Vec<u64>>::from_bencode_with_limits(
+            b"d1:ali1ei2ee1:bli3ei4eee",
+            limits,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.path(),
+            &[PathSegment::Key(b"b".to_vec()), PathSegment::Index(0)][..]
+        );
+    }
+
+    #[test]
+    fn byte_strings_should_be_charged_against_the_allocation_budget() {
+        let limits = DecodeLimits::new().with_max_allocation(64);
+        let mut input = b"l100000:".to_vec();
+        input.resize(input.len() + 100_000, b'a');
+        input.push(b'e');
+
+        let err = Vec::<String>::from_bencode_with_limits(&input, limits).unwrap_err();
+        assert_eq!(err.path(), &[PathSegment::Index(0)][..]);
+        assert!(Vec::<AsString<Vec<u8>>>::from_bencode_with_limits(&input, limits).is_err());
+        assert!(String::from_bencode_with_limits(&input[1..input.len() - 1], limits).is_err());
+        assert!(String::from_bencode_with_limits(b"3:foo", limits).is_ok());
+    }
 }
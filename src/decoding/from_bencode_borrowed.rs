@@ -0,0 +1,301 @@
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::Cow, collections::BTreeMap, rc::Rc, string::String, vec::Vec};
+
+use core::{mem, str};
+
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+    rc::Rc,
+};
+
+use crate::{
+    decoding::{
+        from_bencode::{decode_map, decode_vec},
+        DecodeLimits, Decoder, Error, FromBencode, Object,
+    },
+    encoding::AsString,
+    state_tracker::StructureError,
+};
+
+/// Trait for bencode based value deserialization that may borrow from the input.
+///
+/// This is the zero-copy counterpart of [`FromBencode`]: implementations receive
+/// objects whose byte strings live for `'ser`, the lifetime of the input buffer, so
+/// they can keep references to them instead of copying. It is implemented for `&str`,
+/// `&[u8]` and `Cow`s of both in addition to the types that implement [`FromBencode`].
+///
+/// ```
+/// use bendy::decoding::{Error, FromBencodeBorrowed, Object};
+///
+/// struct Peer<'a> {
+///     id: &'a [u8],
+///     ip: &'a str,
+/// }
+///
+/// impl<'ser> FromBencodeBorrowed<'ser> for Peer<'ser> {
+///     fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+///         let mut id = None;
+///         let mut ip = None;
+///
+///         let mut dict = object.try_into_dictionary()?;
+///         while let Some(pair) = dict.next_pair()? {
+///             match pair {
+///                 (b"id", value) => id = Some(<&[u8]>::decode_borrowed_object(value)?),
+///                 (b"ip", value) => ip = Some(<&str>::decode_borrowed_object(value)?),
+///                 _ => (),
+///             }
+///         }
+///
+///         Ok(Peer {
+///             id: id.ok_or_else(|| Error::missing_field("id"))?,
+///             ip: ip.ok_or_else(|| Error::missing_field("ip"))?,
+///         })
+///     }
+/// }
+///
+/// let input = b"d2:id4:abcd2:ip9:127.0.0.1e";
+/// let peer = Peer::from_bencode_borrowed(input).unwrap();
+///
+/// assert_eq!(peer.ip, "127.0.0.1");
+/// assert_eq!(peer.id.as_ptr(), input[7..].as_ptr());
+/// ```
+pub trait FromBencodeBorrowed<'ser>: Sized {
+    /// Maximum allowed depth of nested structures before the decoding should be aborted.
+    const EXPECTED_RECURSION_DEPTH: usize = 2048;
+
+    /// Whether the values decoded from byte strings copy them instead of borrowing them.
+    /// Only copied byte strings are charged against the allocation budget set with
+    /// [`DecodeLimits::with_max_allocation`].
+    const COPIES_STRINGS: bool = false;
+
+    /// Deserialize an object from its byte representation, borrowing from `bytes`.
+    fn from_bencode_borrowed(bytes: &'ser [u8]) -> Result<Self, Error> {
+        Self::from_bencode_borrowed_with_limits(bytes, DecodeLimits::default())
+    }
+
+    /// Deserialize an object from its byte representation, borrowing from `bytes` and
+    /// spending no more resources than `limits` allow.
+    fn from_bencode_borrowed_with_limits(
+        bytes: &'ser [u8],
+        limits: DecodeLimits,
+    ) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(bytes)
+            .with_max_depth(Self::EXPECTED_RECURSION_DEPTH)
+            .with_limits(limits);
+        decoder.set_copies_strings(Self::COPIES_STRINGS);
+        let object = decoder.next_object()?;
+
+        object
+            .map_or(
+                Err(Error::from(StructureError::UnexpectedEof)),
+                Self::decode_borrowed_object,
+            )
+            .map_err(|err| decoder.locate_error(err))
+    }
+
+    /// Deserialize an object from its intermediate bencode representation.
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error>;
+}
+
+/// Implement [`FromBencodeBorrowed`] for types that never borrow by forwarding to their
+/// [`FromBencode`] implementation
+macro_rules! impl_from_bencode_borrowed_for_owned {
+    ($($type:ty)*) => {$(
+        impl<'ser> FromBencodeBorrowed<'ser> for $type {
+            const EXPECTED_RECURSION_DEPTH: usize = <$type as FromBencode>::EXPECTED_RECURSION_DEPTH;
+            const COPIES_STRINGS: bool = true;
+
+            fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+                <$type as FromBencode>::decode_bencode_object(object)
+            }
+        }
+    )*}
+}
+
+impl_from_bencode_borrowed_for_owned!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+impl_from_bencode_borrowed_for_owned!(String AsString<Vec<u8>>);
+
+impl<'ser> FromBencodeBorrowed<'ser> for &'ser [u8] {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        object.try_into_bytes()
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for &'ser str {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        let content = object.try_into_bytes()?;
+        let content = str::from_utf8(content)?;
+
+        Ok(content)
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for Cow<'ser, [u8]> {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        object.try_into_bytes().map(Cow::Borrowed)
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for Cow<'ser, str> {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        <&str>::decode_borrowed_object(object).map(Cow::Borrowed)
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for AsString<&'ser [u8]> {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        object.try_into_bytes().map(AsString)
+    }
+}
+
+impl<'ser, ContentT: FromBencodeBorrowed<'ser>> FromBencodeBorrowed<'ser> for Vec<ContentT> {
+    const EXPECTED_RECURSION_DEPTH: usize = ContentT::EXPECTED_RECURSION_DEPTH + 1;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        decode_vec(
+            object,
+            ContentT::COPIES_STRINGS,
+            ContentT::decode_borrowed_object,
+        )
+    }
+}
+
+impl<'ser, K, V> FromBencodeBorrowed<'ser> for BTreeMap<K, V>
+where
+    K: FromBencodeBorrowed<'ser> + Ord,
+    V: FromBencodeBorrowed<'ser>,
+{
+    const EXPECTED_RECURSION_DEPTH: usize = V::EXPECTED_RECURSION_DEPTH + 1;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        let mut result = BTreeMap::default();
+        decode_map(
+            object,
+            K::COPIES_STRINGS,
+            V::COPIES_STRINGS,
+            K::decode_borrowed_object,
+            V::decode_borrowed_object,
+            |key, value| {
+                result.insert(key, value);
+                mem::size_of::<(K, V)>()
+            },
+        )?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'ser, K, V, H> FromBencodeBorrowed<'ser> for HashMap<K, V, H>
+where
+    K: FromBencodeBorrowed<'ser> + Hash + Eq,
+    V: FromBencodeBorrowed<'ser>,
+    H: BuildHasher + Default,
+{
+    const EXPECTED_RECURSION_DEPTH: usize = V::EXPECTED_RECURSION_DEPTH + 1;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        let mut result = HashMap::default();
+        decode_map(
+            object,
+            K::COPIES_STRINGS,
+            V::COPIES_STRINGS,
+            K::decode_borrowed_object,
+            V::decode_borrowed_object,
+            |key, value| {
+                let capacity = result.capacity();
+                result.insert(key, value);
+                (result.capacity() - capacity) * mem::size_of::<(K, V)>()
+            },
+        )?;
+
+        Ok(result)
+    }
+}
+
+impl<'ser, T: FromBencodeBorrowed<'ser>> FromBencodeBorrowed<'ser> for Rc<T> {
+    const EXPECTED_RECURSION_DEPTH: usize = T::EXPECTED_RECURSION_DEPTH;
+    const COPIES_STRINGS: bool = T::COPIES_STRINGS;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, Error> {
+        T::decode_borrowed_object(object).map(Rc::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::decoding::PathSegment;
+
+    #[test]
+    fn byte_strings_should_be_borrowed() {
+        let input = b"l3:foo3:bare";
+        let decoded = Vec::<&[u8]>::from_bencode_borrowed(input).unwrap();
+
+        assert_eq!(decoded, vec![&b"foo"[..], &b"bar"[..]]);
+        assert_eq!(decoded[0].as_ptr(), input[3..].as_ptr());
+        assert_eq!(decoded[1].as_ptr(), input[8..].as_ptr());
+    }
+
+    #[test]
+    fn strings_should_be_borrowed() {
+        let input = b"d3:bar4:spam3:foo3:bazee";
+        let decoded = BTreeMap::<&str, Cow<str>>::from_bencode_borrowed(input).unwrap();
+
+        assert_eq!(decoded["bar"], "spam");
+        assert!(match decoded["foo"] {
+            Cow::Borrowed(value) => value == "baz",
+            Cow::Owned(_) => false,
+        });
+    }
+
+    #[test]
+    fn owned_types_should_be_supported() {
+        let decoded = BTreeMap::<&str, Vec<u8>>::from_bencode_borrowed(b"d1:ali1ei2eee").unwrap();
+        assert_eq!(decoded["a"], vec![1, 2]);
+
+        let decoded = String::from_bencode_borrowed(b"3:foo").unwrap();
+        assert_eq!(decoded, "foo");
+
+        let input = b"d1:ali1ei2ee1:b3:fooe";
+        assert!(BTreeMap::<&str, Vec<u8>>::from_bencode_borrowed(input).is_err());
+    }
+
+    #[test]
+    fn only_copied_strings_should_be_charged() {
+        let mut input = b"d1:a10000:".to_vec();
+        input.resize(input.len() + 10_000, b'a');
+        input.push(b'e');
+
+        let limits = DecodeLimits::new().with_max_allocation(64);
+        let decoded = BTreeMap::<&str, &[u8]>::from_bencode_borrowed_with_limits(&input, limits);
+        assert_eq!(decoded.unwrap()["a"].len(), 10_000);
+        let decoded = BTreeMap::<&str, String>::from_bencode_borrowed_with_limits(&input, limits);
+        assert_eq!(
+            decoded.unwrap_err().path(),
+            &[PathSegment::Key(b"a".to_vec())][..]
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_should_fail_with_its_path() {
+        let err = Vec::<&str>::from_bencode_borrowed(b"l3:foo2:\xff\xfee").unwrap_err();
+        assert_eq!(err.path(), &[PathSegment::Index(1)][..]);
+    }
+}
@@ -0,0 +1,306 @@
+use core::str;
+
+use crate::state_tracker::{Expected, SyntaxError, Token};
+
+/// A token located in a buffer, without borrowing the buffer itself. This allows
+/// decoders which own their buffer to keep mutating it until the token is handed out.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum RawToken {
+    List,
+    Dict,
+    End,
+    /// The start and end of the digits of a number
+    Num(usize, usize),
+    /// The start and end of the content of a byte string
+    String(usize, usize),
+}
+
+impl RawToken {
+    /// Move the positions of a token that was lexed from `buffer[by..]` so that they are
+    /// relative to `buffer` itself
+    #[cfg(feature = "std")]
+    pub fn shift(self, by: usize) -> Self {
+        match self {
+            RawToken::String(start, end) => RawToken::String(start + by, end + by),
+            RawToken::Num(start, end) => RawToken::Num(start + by, end + by),
+            token => token,
+        }
+    }
+
+    /// Resolve the token against the buffer it was lexed from
+    pub fn resolve(self, buffer: &[u8]) -> Token<'_> {
+        match self {
+            RawToken::List => Token::List,
+            RawToken::Dict => Token::Dict,
+            RawToken::End => Token::End,
+            RawToken::String(start, end) => Token::String(&buffer[start..end]),
+            RawToken::Num(start, end) => {
+                let slice = &buffer[start..end];
+                let ival = if cfg!(debug_assertions) {
+                    str::from_utf8(slice).expect("We've already examined every byte in the string")
+                } else {
+                    // Avoid a second UTF-8 check here
+                    unsafe { str::from_utf8_unchecked(slice) }
+                };
+                Token::Num(ival)
+            },
+        }
+    }
+}
+
+/// The result of trying to read a single token from the front of a buffer
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum Lexed {
+    /// A complete token, along with the number of bytes it occupies
+    Token(RawToken, usize),
+    /// The buffer ends before the token does. Contains the minimum number of
+    /// additional bytes required before lexing may succeed.
+    Incomplete(usize),
+}
+
+fn unexpected(expected: Expected, found: char, offset: usize) -> SyntaxError {
+    SyntaxError::UnexpectedCharacter {
+        expected,
+        found,
+        offset,
+    }
+}
+
+/// Scan an integer that starts at `start` and is terminated by `expected_terminator`.
+/// Returns the position of the terminator and whether the integer is canonical. Leading
+/// zeros and negative zero are only accepted if `lenient` is set.
+fn scan_int(
+    buffer: &[u8],
+    start: usize,
+    base_offset: usize,
+    expected_terminator: char,
+    lenient: bool,
+) -> Result<Option<(usize, bool)>, SyntaxError> {
+    enum State {
+        Start,
+        Sign,
+        Zero,
+        Digits,
+    }
+
+    let mut curpos = start;
+    let mut state = State::Start;
+    let mut canonical = true;
+
+    while curpos < buffer.len() {
+        let c = buffer[curpos] as char;
+        let offset = base_offset + curpos;
+        match state {
+            State::Start => {
+                if c == '-' {
+                    state = State::Sign;
+                } else if c == '0' {
+                    state = State::Zero;
+                } else if ('1'..='9').contains(&c) {
+                    state = State::Digits;
+                } else {
+                    return Err(unexpected(Expected::SignOrDigit, c, offset));
+                }
+            },
+            State::Zero => {
+                if c == expected_terminator {
+                    return Ok(Some((curpos, canonical)));
+                } else if lenient && c.is_ascii_digit() {
+                    canonical = false;
+                    state = State::Digits;
+                } else if lenient {
+                    return Err(unexpected(
+                        Expected::DigitOrTerminator(expected_terminator),
+                        c,
+                        offset,
+                    ));
+                } else {
+                    return Err(unexpected(
+                        Expected::Terminator(expected_terminator),
+                        c,
+                        offset,
+                    ));
+                }
+            },
+            State::Sign => {
+                if ('1'..='9').contains(&c) {
+                    state = State::Digits;
+                } else if lenient && c == '0' {
+                    canonical = false;
+                    state = State::Zero;
+                } else if lenient {
+                    return Err(unexpected(Expected::Digit, c, offset));
+                } else {
+                    return Err(unexpected(Expected::NonZeroDigit, c, offset));
+                }
+            },
+            State::Digits => {
+                if c.is_ascii_digit() {
+                    // do nothing, this is ok
+                } else if c == expected_terminator {
+                    return Ok(Some((curpos, canonical)));
+                } else {
+                    return Err(unexpected(
+                        Expected::DigitOrTerminator(expected_terminator),
+                        c,
+                        offset,
+                    ));
+                }
+            },
+        }
+        curpos += 1;
+    }
+
+    Ok(None)
+}
+
+/// Read a single token from the front of `buffer`. `base_offset` is the position of
+/// `buffer[0]` in the complete input and is only used for error reporting.
+///
+/// Syntax errors are reported as soon as they are visible, even if the token is not
+/// yet complete.
+pub(crate) fn lex_token(buffer: &[u8], base_offset: usize) -> Result<Lexed, SyntaxError> {
+    lex_token_with(buffer, base_offset, false).map(|(lexed, _)| lexed)
+}
+
+/// Read a single token like [`lex_token`], but accept integers and string lengths that
+/// aren't encoded canonically if `lenient` is set. Also returns whether the token was
+/// encoded canonically.
+pub(crate) fn lex_token_with(
+    buffer: &[u8],
+    base_offset: usize,
+    lenient: bool,
+) -> Result<(Lexed, bool), SyntaxError> {
+    let first = match buffer.first() {
+        Some(&first) => first as char,
+        None => return Ok((Lexed::Incomplete(1), true)),
+    };
+
+    let mut canonical = true;
+    let token = match first {
+        'e' => Lexed::Token(RawToken::End, 1),
+        'l' => Lexed::Token(RawToken::List, 1),
+        'd' => Lexed::Token(RawToken::Dict, 1),
+        'i' => match scan_int(buffer, 1, base_offset, 'e', lenient)? {
+            Some((end, is_canonical)) => {
+                canonical = is_canonical;
+                Lexed::Token(RawToken::Num(1, end), end + 1)
+            },
+            None => Lexed::Incomplete(1),
+        },
+        c if c.is_ascii_digit() => match scan_int(buffer, 0, base_offset, ':', lenient)? {
+            Some((colon, is_canonical)) => {
+                canonical = is_canonical;
+                // The length has been validated to consist of ASCII digits only
+                let len = str::from_utf8(&buffer[..colon])
+                    .ok()
+                    .and_then(|ival| ival.parse::<usize>().ok())
+                    .ok_or(SyntaxError::InvalidInteger {
+                        offset: base_offset,
+                    })?;
+                let start = colon + 1;
+                match start.checked_add(len) {
+                    Some(end) if end <= buffer.len() => {
+                        Lexed::Token(RawToken::String(start, end), end)
+                    },
+                    Some(end) => Lexed::Incomplete(end - buffer.len()),
+                    None => {
+                        return Err(SyntaxError::InvalidInteger {
+                            offset: base_offset,
+                        });
+                    },
+                }
+            },
+            None => Lexed::Incomplete(1),
+        },
+        tok => {
+            return Err(SyntaxError::InvalidToken {
+                found: tok,
+                offset: base_offset,
+            });
+        },
+    };
+
+    Ok((token, canonical))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn complete_tokens_report_their_length() {
+        assert_eq!(
+            lex_token(b"i-12eX", 0),
+            Ok(Lexed::Token(RawToken::Num(1, 4), 5))
+        );
+        assert_eq!(
+            lex_token(b"3:fooX", 0),
+            Ok(Lexed::Token(RawToken::String(2, 5), 5))
+        );
+        assert_eq!(lex_token(b"le", 0), Ok(Lexed::Token(RawToken::List, 1)));
+    }
+
+    #[test]
+    fn incomplete_tokens_report_missing_bytes() {
+        assert_eq!(lex_token(b"", 0), Ok(Lexed::Incomplete(1)));
+        assert_eq!(lex_token(b"i12", 0), Ok(Lexed::Incomplete(1)));
+        assert_eq!(lex_token(b"12", 0), Ok(Lexed::Incomplete(1)));
+        assert_eq!(lex_token(b"5:ab", 0), Ok(Lexed::Incomplete(3)));
+    }
+
+    #[test]
+    fn errors_are_reported_before_completion() {
+        assert!(lex_token(b"i01", 0).is_err());
+        assert!(lex_token(b"x", 0).is_err());
+    }
+
+    #[test]
+    fn errors_carry_expectations_and_offsets() {
+        assert_eq!(
+            lex_token(b"i-0e", 10),
+            Err(SyntaxError::UnexpectedCharacter {
+                expected: Expected::NonZeroDigit,
+                found: '0',
+                offset: 12,
+            })
+        );
+        assert_eq!(
+            lex_token(b"3x", 0),
+            Err(SyntaxError::UnexpectedCharacter {
+                expected: Expected::DigitOrTerminator(':'),
+                found: 'x',
+                offset: 1,
+            })
+        );
+        assert_eq!(
+            lex_token(b"x", 4),
+            Err(SyntaxError::InvalidToken {
+                found: 'x',
+                offset: 4
+            })
+        );
+    }
+
+    #[test]
+    fn lenient_lexing_accepts_leading_zeros() {
+        assert_eq!(
+            lex_token_with(b"i003e", 0, true),
+            Ok((Lexed::Token(RawToken::Num(1, 4), 5), false))
+        );
+        assert_eq!(
+            lex_token_with(b"i-0e", 0, true),
+            Ok((Lexed::Token(RawToken::Num(1, 3), 4), false))
+        );
+        assert_eq!(
+            lex_token_with(b"03:foo", 0, true),
+            Ok((Lexed::Token(RawToken::String(3, 6), 6), false))
+        );
+        assert_eq!(
+            lex_token_with(b"i10e", 0, true),
+            Ok((Lexed::Token(RawToken::Num(1, 3), 4), true))
+        );
+        assert!(lex_token_with(b"i003e", 0, false).is_err());
+        assert!(lex_token_with(b"i-e", 0, true).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::state_tracker::{LimitExceeded, Token};
+
+/// Upper bounds on the resources spent decoding a single input
+///
+/// The nesting depth alone doesn't protect against hostile input: a flat list of a
+/// million empty lists is only two megabytes of bencode, but may turn into much more
+/// memory once decoded. These limits are checked by [`Decoder`] as tokens are read, and
+/// the allocation budget is charged by [`FromBencode`] implementations through
+/// [`ListDecoder::charge_allocation`] and [`DictDecoder::charge_allocation`]. Byte
+/// strings are charged with their length when they are copied: the decoder charges the
+/// ones it hands out unless they are decoded by a [`FromBencodeBorrowed`] implementation
+/// that borrows them, and maps charge the keys they copy. Skipped values and raw values
+/// are not charged.
+///
+/// All limits are unlimited by default. The nesting depth is configured separately
+/// with [`Decoder::with_max_depth`].
+///
+/// ```
+/// use bendy::decoding::{DecodeLimits, FromBencode};
+///
+/// let limits = DecodeLimits::new()
+///     .with_max_input_size(1500)
+///     .with_max_container_items(2);
+///
+/// assert!(Vec::<u8>::from_bencode_with_limits(b"li1ei2ee", limits).is_ok());
+/// assert!(Vec::<u8>::from_bencode_with_limits(b"li1ei2ei3ee", limits).is_err());
+/// ```
+///
+/// [`Decoder`]: crate::decoding::Decoder
+/// [`Decoder::with_max_depth`]: crate::decoding::Decoder::with_max_depth
+/// [`FromBencode`]: crate::decoding::FromBencode
+/// [`FromBencodeBorrowed`]: crate::decoding::FromBencodeBorrowed
+/// [`ListDecoder::charge_allocation`]: crate::decoding::ListDecoder::charge_allocation
+/// [`DictDecoder::charge_allocation`]: crate::decoding::DictDecoder::charge_allocation
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct DecodeLimits {
+    max_input_size: usize,
+    max_string_length: usize,
+    max_container_items: usize,
+    max_tokens: usize,
+    max_allocation: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_input_size: usize::MAX,
+            max_string_length: usize::MAX,
+            max_container_items: usize::MAX,
+            max_tokens: usize::MAX,
+            max_allocation: usize::MAX,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Create a new set of limits that doesn't restrict anything
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set the maximum size of the complete input in bytes
+    pub fn with_max_input_size(mut self, max_input_size: usize) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
+    /// Set the maximum length of a single byte string
+    pub fn with_max_string_length(mut self, max_string_length: usize) -> Self {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    /// Set the maximum number of items in a single list or entries in a single dict
+    pub fn with_max_container_items(mut self, max_container_items: usize) -> Self {
+        self.max_container_items = max_container_items;
+        self
+    }
+
+    /// Set the maximum number of tokens in the complete input. Every atom, every start
+    /// of a list or dict and every end counts as one token.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Set the maximum number of bytes the decoded values may allocate in total
+    ///
+    /// Byte strings are charged with their length when they are handed out, and the
+    /// provided collections are charged with the memory they reserve as they grow.
+    pub fn with_max_allocation(mut self, max_allocation: usize) -> Self {
+        self.max_allocation = max_allocation;
+        self
+    }
+
+    /// The maximum size of the complete input in bytes
+    pub fn max_input_size(&self) -> usize {
+        self.max_input_size
+    }
+
+    /// The maximum length of a single byte string
+    pub fn max_string_length(&self) -> usize {
+        self.max_string_length
+    }
+
+    /// The maximum number of items in a single list or entries in a single dict
+    pub fn max_container_items(&self) -> usize {
+        self.max_container_items
+    }
+
+    /// The maximum number of tokens in the complete input
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// The maximum number of bytes the decoded values may allocate in total
+    pub fn max_allocation(&self) -> usize {
+        self.max_allocation
+    }
+}
+
+/// The resources spent so far on a single input
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    limits: DecodeLimits,
+    tokens: usize,
+    allocated: usize,
+    /// Whether each open container is a dict, and the number of tokens it contains
+    containers: Vec<(bool, usize)>,
+}
+
+impl Budget {
+    pub fn new(limits: DecodeLimits) -> Self {
+        Budget {
+            limits,
+            ..Budget::default()
+        }
+    }
+
+    pub fn limits(&self) -> &DecodeLimits {
+        &self.limits
+    }
+
+    pub fn check_input_size(&self, size: usize) -> Result<(), LimitExceeded> {
+        if size > self.limits.max_input_size {
+            return Err(LimitExceeded::InputSize {
+                limit: self.limits.max_input_size,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_string_length(&self, length: usize) -> Result<(), LimitExceeded> {
+        if length > self.limits.max_string_length {
+            return Err(LimitExceeded::StringLength {
+                length,
+                limit: self.limits.max_string_length,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check a token that can't be lexed until `missing` more bytes are read, where
+    /// `partial` holds the part of it that has already been read and `end` is the
+    /// position `partial` ends at. Used by the incremental decoders to reject oversized
+    /// input before buffering it.
+    pub fn check_incomplete(
+        &self,
+        partial: &[u8],
+        missing: usize,
+        end: usize,
+    ) -> Result<(), LimitExceeded> {
+        self.check_input_size(end.saturating_add(missing))?;
+
+        // The length prefix of a byte string is known before its content
+        match (partial.first(), partial.iter().position(|&b| b == b':')) {
+            (Some(first), Some(colon)) if first.is_ascii_digit() => {
+                self.check_string_length(partial.len() - colon - 1 + missing)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for a token that is about to be observed and ends at position `end` of
+    /// an input that is read incrementally
+    pub fn observe_until(&mut self, token: &Token, end: usize) -> Result<(), LimitExceeded> {
+        self.check_input_size(end)?;
+        self.observe(token)
+    }
+
+    /// Account for a token that is about to be observed
+    pub fn observe(&mut self, token: &Token) -> Result<(), LimitExceeded> {
+        self.tokens += 1;
+        if self.tokens > self.limits.max_tokens {
+            return Err(LimitExceeded::TokenCount {
+                limit: self.limits.max_tokens,
+            });
+        }
+
+        if let Token::String(bytes) = *token {
+            self.check_string_length(bytes.len())?;
+        }
+
+        if let Token::End = *token {
+            self.containers.pop();
+            return Ok(());
+        }
+
+        if let Some((is_dict, count)) = self.containers.last_mut() {
+            *count += 1;
+            // Dict entries consist of two tokens, the key being the first of them
+            let items = if *is_dict {
+                *count / 2 + *count % 2
+            } else {
+                *count
+            };
+            if items > self.limits.max_container_items {
+                return Err(LimitExceeded::ContainerItems {
+                    limit: self.limits.max_container_items,
+                });
+            }
+        }
+
+        match *token {
+            Token::List => self.containers.push((false, 0)),
+            Token::Dict => self.containers.push((true, 0)),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// A budget for reading input again that has been accounted for already. Only the
+    /// allocations are charged again, on top of the ones made so far.
+    pub fn replay(&self) -> Self {
+        Budget {
+            limits: self.limits.with_max_tokens(usize::MAX),
+            allocated: self.allocated,
+            ..Budget::default()
+        }
+    }
+
+    /// Take over the allocations charged against a budget created by [`Budget::replay`]
+    pub fn absorb(&mut self, replayed: &Budget) {
+        self.allocated = self.allocated.max(replayed.allocated);
+    }
+
+    /// Account for `bytes` of memory allocated by decoded values
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), LimitExceeded> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        if self.allocated > self.limits.max_allocation {
+            return Err(LimitExceeded::Allocation {
+                limit: self.limits.max_allocation,
+            });
+        }
+        Ok(())
+    }
+}
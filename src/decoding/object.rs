@@ -1,3 +1,9 @@
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::Cow, format};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
 use crate::{
     decoding::{DictDecoder, Error, ListDecoder},
     state_tracker::Token,
@@ -15,6 +21,29 @@ pub enum Object<'obj, 'ser: 'obj> {
     Bytes(&'ser [u8]),
 }
 
+/// An object read from a decoder, along with its position in the input
+///
+/// Integers and byte strings are decoded completely, so their end is known immediately.
+/// The end of a list or dictionary is only known once its content has been read; use
+/// [`ListDecoder::span`] or [`DictDecoder::span`] to obtain it.
+pub struct SpannedObject<'obj, 'ser: 'obj> {
+    /// The position of the first byte of the object
+    pub start: usize,
+    /// The position right after the last byte of the object. Always `None` for lists and
+    /// dictionaries.
+    pub end: Option<usize>,
+    /// The object itself
+    pub object: Object<'obj, 'ser>,
+}
+
+impl<'obj, 'ser: 'obj> SpannedObject<'obj, 'ser> {
+    /// The range of bytes occupied by an integer or byte string. Returns `None` for lists
+    /// and dictionaries.
+    pub fn atom_span(&self) -> Option<Range<usize>> {
+        self.end.map(|end| self.start..end)
+    }
+}
+
 impl<'obj, 'ser: 'obj> Object<'obj, 'ser> {
     pub fn into_token(self) -> Token<'ser> {
         match self {
@@ -391,4 +420,43 @@ impl<'obj, 'ser: 'obj> Object<'obj, 'ser> {
     pub fn try_into_dictionary(self) -> Result<DictDecoder<'obj, 'ser>, Error> {
         self.dictionary_or_else(|obj| Err(Error::unexpected_token("Dict", obj.into_token().name())))
     }
+
+    /// Get the exact encoding of this object.
+    ///
+    /// Lists and dictionaries are consumed and validated completely, and the returned
+    /// bytes are borrowed from the input. Integers and byte strings have already been
+    /// stripped of their framing, so their encoding is rebuilt. This is identical to the
+    /// input, except for byte strings whose length had leading zeros, which only a
+    /// [lenient](crate::decoding::Decoder::with_lenient) decoder accepts. Those come
+    /// back with a canonical length. Use [`Decoder::next_spanned_object`] to locate the
+    /// exact input instead.
+    ///
+    /// [`Decoder::next_spanned_object`]: crate::decoding::Decoder::next_spanned_object
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(b"l3:fooi-5ee");
+    /// let mut list = decoder.next_object().unwrap().unwrap().try_into_list().unwrap();
+    ///
+    /// let item = list.next_object().unwrap().unwrap();
+    /// assert_eq!(item.into_raw().unwrap(), &b"3:foo"[..]);
+    ///
+    /// let item = list.next_object().unwrap().unwrap();
+    /// assert_eq!(item.into_raw().unwrap(), &b"i-5e"[..]);
+    /// ```
+    pub fn into_raw(self) -> Result<Cow<'ser, [u8]>, Error> {
+        match self {
+            Object::List(list) => list.into_raw().map(Cow::Borrowed),
+            Object::Dict(dict) => dict.into_raw().map(Cow::Borrowed),
+            Object::Integer(number) => Ok(Cow::Owned(format!("i{}e", number).into_bytes())),
+            Object::Bytes(bytes) => {
+                let mut raw = format!("{}:", bytes.len()).into_bytes();
+                raw.extend_from_slice(bytes);
+                Ok(Cow::Owned(raw))
+            },
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use crate::{
+    decoding::{Decoder, Error, ErrorKind, Object, SpannedObject},
+    pointer::{self, Segments},
+    state_tracker::StructureError,
+};
+
+/// Look up a nested value by a pointer like `/info/files/0/length` directly in the
+/// encoded `buffer`, and return its raw bytes. See [`Value::pointer`] for the syntax of
+/// pointers.
+///
+/// Values that aren't on the way to the target are skipped by the [`Decoder`] without
+/// decoding or allocating anything. Returns `Ok(None)` if the value doesn't exist, and
+/// fails with [`ErrorKind::MalformedPointer`] if the pointer is malformed.
+///
+/// ```
+/// use bendy::decoding::resolve_pointer;
+///
+/// let torrent = b"d4:infod5:filesld6:lengthi5e4:pathl3:fooeeeee";
+///
+/// let path = resolve_pointer(torrent, "/info/files/0/path").unwrap();
+/// assert_eq!(path, Some(&b"l3:fooe"[..]));
+/// assert_eq!(resolve_pointer(torrent, "/info/name").unwrap(), None);
+/// assert!(resolve_pointer(torrent, "info").is_err());
+/// ```
+///
+/// [`Value::pointer`]: crate::value::Value::pointer
+pub fn resolve_pointer<'ser>(
+    buffer: &'ser [u8],
+    pointer: &str,
+) -> Result<Option<&'ser [u8]>, Error> {
+    let segments = match pointer::segments(pointer) {
+        Some(segments) => segments,
+        None => return Err(Error::from(ErrorKind::MalformedPointer(pointer.into()))),
+    };
+
+    let mut decoder = Decoder::new(buffer);
+    let object = decoder
+        .next_spanned_object()?
+        .ok_or_else(|| Error::from(StructureError::UnexpectedEof))?;
+
+    resolve(buffer, object, segments)
+}
+
+fn resolve<'ser>(
+    buffer: &'ser [u8],
+    spanned: SpannedObject<'_, 'ser>,
+    mut segments: Segments<'_>,
+) -> Result<Option<&'ser [u8]>, Error> {
+    let segment = match segments.next() {
+        Some(segment) => segment,
+        None => {
+            let span = match (spanned.object, spanned.end) {
+                (Object::List(list), _) => list.span()?,
+                (Object::Dict(dict), _) => dict.span()?,
+                (_, end) => spanned.start..end.expect("atoms know their end"),
+            };
+            return Ok(Some(&buffer[span]));
+        },
+    };
+
+    match spanned.object {
+        Object::Dict(mut dict) => {
+            while let Some((key, _, value)) = dict.next_spanned_pair()? {
+                if segment.matches(key) {
+                    return resolve(buffer, value, segments);
+                }
+            }
+        },
+        Object::List(mut list) => {
+            let index = match segment.index() {
+                Some(index) => index,
+                None => return Ok(None),
+            };
+
+            let mut position = 0;
+            while let Some(item) = list.next_spanned_object()? {
+                if position == index {
+                    return resolve(buffer, item, segments);
+                }
+                position += 1;
+            }
+        },
+        Object::Bytes(_) | Object::Integer(_) => (),
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod3:a/bi2e\
+        5:filesld6:lengthi5e4:pathl1:aeed6:lengthi7e4:pathl1:beee1:~i1e1:\xffi3eee";
+
+    #[test]
+    fn pointers_should_resolve_to_raw_values() {
+        let resolve = |pointer| resolve_pointer(TORRENT, pointer).unwrap();
+
+        assert_eq!(resolve(""), Some(TORRENT));
+        assert_eq!(resolve("/announce"), Some(&b"3:url"[..]));
+        assert_eq!(resolve("/info/files/1/length"), Some(&b"i7e"[..]));
+        assert_eq!(resolve("/info/files/0/path"), Some(&b"l1:ae"[..]));
+        assert_eq!(resolve("/info/~0"), Some(&b"i1e"[..]));
+        assert_eq!(resolve("/info/a~1b"), Some(&b"i2e"[..]));
+        assert_eq!(resolve("/info/~xff"), Some(&b"i3e"[..]));
+    }
+
+    #[test]
+    fn missing_values_should_resolve_to_none() {
+        let resolve = |pointer| resolve_pointer(TORRENT, pointer).unwrap();
+
+        assert_eq!(resolve("/comment"), None);
+        assert_eq!(resolve("/info/files/2"), None);
+        assert_eq!(resolve("/info/files/01"), None);
+        assert_eq!(resolve("/announce/0"), None);
+    }
+
+    #[test]
+    fn malformed_pointers_should_fail() {
+        for pointer in &["announce", "/info/~", "/info/~xf"] {
+            let err = resolve_pointer(TORRENT, pointer).unwrap_err();
+            match err.kind() {
+                ErrorKind::MalformedPointer(malformed) => assert_eq!(malformed, pointer),
+                kind => panic!("Unexpected error {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_input_should_fail() {
+        assert!(resolve_pointer(b"d1:ai1e", "/b").is_err());
+        assert!(resolve_pointer(b"", "").is_err());
+    }
+}
@@ -0,0 +1,359 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::{
+    decoding::{
+        lexer::{lex_token, Lexed},
+        limits::Budget,
+        DecodeLimits, Error,
+    },
+    state_tracker::{StateTracker, StructureError},
+};
+
+/// The outcome of scanning a partial input for a complete top level value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// The buffered input ends in the middle of a value. Contains the minimum number
+    /// of additional bytes that need to be pushed before the value may complete.
+    NeedMoreData(usize),
+    /// A complete, validated top level value
+    Message(Vec<u8>),
+}
+
+/// Incrementally validates a stream of top level values and reports where each of them
+/// ends. Already validated bytes are never scanned again.
+#[derive(Debug)]
+pub(crate) struct MessageScanner {
+    /// Number of bytes of the current message that have been validated
+    scanned: usize,
+    state: StateTracker<Vec<u8>, Error>,
+    /// The resources spent on the current message
+    budget: Budget,
+}
+
+/// The outcome of [`MessageScanner::scan`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Scan {
+    NeedMoreData(usize),
+    /// The first `n` bytes of the buffer form a complete value
+    Complete(usize),
+}
+
+impl MessageScanner {
+    pub fn new() -> Self {
+        MessageScanner {
+            scanned: 0,
+            state: StateTracker::new(),
+            budget: Budget::default(),
+        }
+    }
+
+    pub fn set_max_depth(&mut self, new_max_depth: usize) {
+        self.state.set_max_depth(new_max_depth);
+    }
+
+    /// Set the limits that apply to each message separately
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        self.budget = Budget::new(limits);
+    }
+
+    /// Continue scanning `buffer`, which has to start with the current message. The
+    /// bytes passed in on previous calls must not have been modified. `base_offset` is
+    /// the position of `buffer[0]` in the complete stream and is used for error reporting.
+    ///
+    /// After a message has been completed, the caller is expected to remove it from the
+    /// front of the buffer before calling this again.
+    pub fn scan(&mut self, buffer: &[u8], base_offset: usize) -> Result<Scan, Error> {
+        self.state.check_error()?;
+
+        loop {
+            let remaining = &buffer[self.scanned..];
+            if remaining.is_empty() {
+                return Ok(Scan::NeedMoreData(1));
+            }
+
+            let offset = base_offset + self.scanned;
+            let lexed = lex_token(remaining, offset).map_err(StructureError::from);
+            match self.state.latch_at(lexed, offset)? {
+                Lexed::Token(token, len) => {
+                    let token = token.resolve(remaining);
+                    let checked = self
+                        .budget
+                        .observe_until(&token, self.scanned + len)
+                        .map_err(StructureError::from);
+                    self.state.latch_at(checked, offset)?;
+                    self.state.observe_token_at(&token, offset)?;
+                    self.scanned += len;
+
+                    if self.state.is_at_top_level() {
+                        self.budget = Budget::new(*self.budget.limits());
+                        return Ok(Scan::Complete(mem::replace(&mut self.scanned, 0)));
+                    }
+                },
+                Lexed::Incomplete(missing) => {
+                    let checked = self
+                        .budget
+                        .check_incomplete(remaining, missing, buffer.len())
+                        .map_err(StructureError::from);
+                    self.state.latch_at(checked, offset)?;
+                    return Ok(Scan::NeedMoreData(missing));
+                },
+            }
+        }
+    }
+}
+
+/// A push style decoder for bencode values that arrive in arbitrary fragments
+///
+/// Chunks of input are handed to the decoder using [`PushDecoder::push`] as they arrive.
+/// [`PushDecoder::next_message`] then either returns the next complete top level value,
+/// or the minimum number of bytes that are still required to complete it. Validation
+/// resumes where it left off, so each byte is only examined once.
+///
+/// The returned messages are guaranteed to be valid, canonical bencode and can be
+/// decoded further using [`Decoder`] or [`FromBencode`].
+///
+/// ```
+/// use bendy::decoding::{Progress, PushDecoder};
+///
+/// let mut decoder = PushDecoder::new();
+///
+/// decoder.push(b"d3:foo");
+/// assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(1));
+///
+/// decoder.push(b"3:bare4:sp");
+/// assert_eq!(
+///     decoder.next_message().unwrap(),
+///     Progress::Message(b"d3:foo3:bare".to_vec())
+/// );
+/// assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(2));
+/// ```
+///
+/// [`Decoder`]: crate::decoding::Decoder
+/// [`FromBencode`]: crate::decoding::FromBencode
+#[derive(Debug)]
+pub struct PushDecoder {
+    buffer: Vec<u8>,
+    /// Number of bytes at the front of `buffer` that were already returned as messages.
+    /// They are only released on the next push, so that returning several messages from
+    /// a single chunk doesn't move the rest of the buffer each time.
+    consumed: usize,
+    /// Position of `buffer[0]` in the complete stream
+    offset: usize,
+    scanner: MessageScanner,
+}
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        PushDecoder {
+            buffer: Vec::new(),
+            consumed: 0,
+            offset: 0,
+            scanner: MessageScanner::new(),
+        }
+    }
+}
+
+impl PushDecoder {
+    /// Create a new decoder with an empty buffer
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set the maximum nesting depth of the decoder. See [`Decoder::with_max_depth`].
+    ///
+    /// [`Decoder::with_max_depth`]: crate::decoding::Decoder::with_max_depth
+    pub fn with_max_depth(mut self, new_max_depth: usize) -> Self {
+        self.scanner.set_max_depth(new_max_depth);
+        self
+    }
+
+    /// Append a chunk of input to the internal buffer
+    pub fn push(&mut self, chunk: &[u8]) {
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.offset += self.consumed;
+            self.consumed = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// The input that has been pushed but not yet returned as part of a message
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.consumed..]
+    }
+
+    /// Set the resource limits that apply to each message. The limits are checked while
+    /// a message is still incomplete, so oversized messages are rejected before they are
+    /// buffered completely. See [`DecodeLimits`] for details.
+    ///
+    /// The allocation budget doesn't apply, as messages aren't decoded.
+    ///
+    /// [`DecodeLimits`]: crate::decoding::DecodeLimits
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.scanner.set_limits(limits);
+        self
+    }
+
+    /// Try to complete the next top level value from the buffered input
+    ///
+    /// Once an error was encountered, every future call will return the same error.
+    pub fn next_message(&mut self) -> Result<Progress, Error> {
+        let start = self.consumed;
+        match self
+            .scanner
+            .scan(&self.buffer[start..], self.offset + start)?
+        {
+            Scan::NeedMoreData(missing) => Ok(Progress::NeedMoreData(missing)),
+            Scan::Complete(len) => {
+                self.consumed += len;
+                Ok(Progress::Message(self.buffer[start..start + len].to_vec()))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+
+    fn messages(decoder: &mut PushDecoder) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        while let Progress::Message(message) = decoder.next_message().unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn messages_can_arrive_byte_by_byte() {
+        let input = b"d3:bari1e3:fooli2ei3eee4:spam";
+        let mut decoder = PushDecoder::new();
+        let mut received = Vec::new();
+
+        for byte in input.iter() {
+            decoder.push(&[*byte]);
+            received.extend(messages(&mut decoder));
+        }
+
+        assert_eq!(
+            received,
+            vec![b"d3:bari1e3:fooli2ei3eee".to_vec(), b"4:spam".to_vec()]
+        );
+        assert!(decoder.buffered().is_empty());
+    }
+
+    #[test]
+    fn missing_bytes_are_reported() {
+        let mut decoder = PushDecoder::new();
+        assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(1));
+
+        decoder.push(b"l10:abc");
+        assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(7));
+
+        decoder.push(b"defghij");
+        assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(1));
+
+        decoder.push(b"e");
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Progress::Message(b"l10:abcdefghije".to_vec())
+        );
+    }
+
+    #[test]
+    fn multiple_messages_in_one_chunk() {
+        let mut decoder = PushDecoder::new();
+        decoder.push(b"i1ei2eli3ee");
+
+        assert_eq!(
+            messages(&mut decoder),
+            vec![b"i1e".to_vec(), b"i2e".to_vec(), b"li3ee".to_vec()]
+        );
+    }
+
+    #[test]
+    fn errors_are_latched_with_stream_offsets() {
+        let mut decoder = PushDecoder::new();
+        decoder.push(b"i1ei01e");
+
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Progress::Message(b"i1e".to_vec())
+        );
+        let err = decoder.next_message().unwrap_err().to_string();
+        assert!(err.contains("at offset 5"), "{}", err);
+
+        decoder.push(b"e");
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn released_messages_keep_stream_offsets() {
+        let mut decoder = PushDecoder::new();
+        decoder.push(b"i1ei2");
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Progress::Message(b"i1e".to_vec())
+        );
+        assert_eq!(decoder.buffered(), b"i2");
+
+        decoder.push(b"ei01e");
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Progress::Message(b"i2e".to_vec())
+        );
+        let err = decoder.next_message().unwrap_err();
+        assert_eq!(err.offset(), Some(8));
+    }
+
+    #[test]
+    fn canonicalization_is_enforced() {
+        let mut decoder = PushDecoder::new();
+        decoder.push(b"d3:fooi1e3:bar");
+
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn limits_apply_to_each_message() {
+        let limits = DecodeLimits::new()
+            .with_max_input_size(8)
+            .with_max_tokens(3);
+        let mut decoder = PushDecoder::new().with_limits(limits);
+        decoder.push(b"li1eeli2ee");
+        assert_eq!(messages(&mut decoder).len(), 2);
+
+        decoder.push(b"li1ei2ee");
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn oversized_strings_are_rejected_before_they_are_buffered() {
+        let limits = DecodeLimits::new().with_max_string_length(16);
+        let mut decoder = PushDecoder::new().with_limits(limits);
+        decoder.push(b"16:abc");
+        assert_eq!(decoder.next_message().unwrap(), Progress::NeedMoreData(13));
+
+        let mut decoder = PushDecoder::new().with_limits(limits);
+        decoder.push(b"100000:abc");
+        let err = decoder.next_message().unwrap_err();
+        assert_eq!(err.offset(), Some(0));
+
+        let mut decoder = PushDecoder::new().with_limits(limits.with_max_input_size(8));
+        decoder.push(b"l6:");
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn recursion_should_be_limited() {
+        let mut decoder = PushDecoder::new().with_max_depth(2);
+        decoder.push(b"lll");
+
+        assert!(decoder.next_message().is_err());
+    }
+}
@@ -0,0 +1,542 @@
+use std::io::{self, Read};
+
+use crate::{
+    decoding::{
+        lexer::{lex_token, Lexed, RawToken},
+        limits::Budget,
+        DecodeLimits, Error,
+    },
+    state_tracker::{StateTracker, StructureError, Token},
+};
+
+/// Number of bytes requested from the reader at a time
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// A bencode decoder that pulls its input from an [`io::Read`] on demand
+///
+/// Unlike [`Decoder`], the complete input doesn't need to be held in memory. Only the
+/// token that is currently being decoded is buffered, so memory usage is bounded by the
+/// largest single byte string in the input. The same canonicalization and nesting depth
+/// rules as for [`Decoder`] are enforced.
+///
+/// Tokens and atoms borrow from the internal buffer and are therefore only valid until
+/// the next call into the decoder. Dictionary keys are copied out of the buffer, as it
+/// is reused while the corresponding value is read.
+///
+/// ```
+/// # use bendy::decoding::{StreamDecoder, StreamObject};
+/// #
+/// # let file: &[u8] = b"d3:fooi1ee";
+/// let mut decoder = StreamDecoder::new(file).with_max_depth(3);
+///
+/// match decoder.next_object().unwrap() {
+///     Some(StreamObject::Dict(mut dict)) => {
+///         while let Some((key, value)) = dict.next_pair().unwrap() {
+///             assert_eq!(key, b"foo");
+///             assert_eq!(value.try_into_integer().unwrap(), "1");
+///         }
+///     },
+///     _ => panic!("expected a dict"),
+/// };
+/// ```
+///
+/// [`Decoder`]: crate::decoding::Decoder
+#[derive(Debug)]
+pub struct StreamDecoder<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Number of bytes at the front of `buffer` that belong to already returned tokens.
+    /// They are only released when the buffer is refilled, to avoid moving the rest of
+    /// the buffer for every token.
+    consumed: usize,
+    /// Position of `buffer[0]` in the input stream
+    offset: usize,
+    eof: bool,
+    state: StateTracker<Vec<u8>, Error>,
+    budget: Budget,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    /// Create a new decoder reading from the given reader
+    pub fn new(reader: R) -> Self {
+        StreamDecoder {
+            reader,
+            buffer: Vec::new(),
+            consumed: 0,
+            offset: 0,
+            eof: false,
+            state: StateTracker::new(),
+            budget: Budget::default(),
+        }
+    }
+
+    /// Set the maximum nesting depth of the decoder. See [`Decoder::with_max_depth`].
+    ///
+    /// [`Decoder::with_max_depth`]: crate::decoding::Decoder::with_max_depth
+    pub fn with_max_depth(mut self, new_max_depth: usize) -> Self {
+        self.state.set_max_depth(new_max_depth);
+        self
+    }
+
+    /// Set the resource limits of the decoder. See [`DecodeLimits`] for details.
+    ///
+    /// The limits apply to the complete stream and are checked before the content of a
+    /// token is buffered, so an oversized byte string is rejected by its length prefix.
+    /// The allocation budget doesn't apply, as the decoder hands out borrowed atoms only.
+    ///
+    /// [`DecodeLimits`]: crate::decoding::DecodeLimits
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.budget = Budget::new(limits);
+        self
+    }
+
+    /// Read from the underlying reader until at least `additional` more bytes are
+    /// buffered or the reader is exhausted.
+    fn fill_buffer(&mut self, additional: usize) -> Result<(), Error> {
+        // Release the bytes of already returned tokens
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.offset += self.consumed;
+            self.consumed = 0;
+        }
+
+        let target = self.buffer.len().saturating_add(additional);
+        let mut chunk = [0; READ_CHUNK_SIZE];
+
+        while self.buffer.len() < target && !self.eof {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return self.state.latch_err(Err(Error::io(err))),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the next token without borrowing it from the buffer. The positions within
+    /// the returned token are valid until the next call.
+    fn next_raw_token(&mut self) -> Result<Option<RawToken>, Error> {
+        self.state.check_error()?;
+
+        loop {
+            if self.consumed == self.buffer.len() {
+                self.fill_buffer(1)?;
+                if self.buffer.is_empty() {
+                    self.state.observe_eof_at(self.offset)?;
+                    return Ok(None);
+                }
+            }
+
+            let start = self.consumed;
+            let offset = self.offset + start;
+            let lexed = lex_token(&self.buffer[start..], offset).map_err(StructureError::from);
+            match self.state.latch_at(lexed, offset)? {
+                Lexed::Token(token, len) => {
+                    let token = token.shift(start);
+                    self.consumed += len;
+                    let resolved = token.resolve(&self.buffer);
+                    let checked = self
+                        .budget
+                        .observe_until(&resolved, self.offset + self.consumed)
+                        .map_err(StructureError::from);
+                    self.state.latch_at(checked, offset)?;
+                    self.state.observe_token_at(&resolved, offset)?;
+                    return Ok(Some(token));
+                },
+                Lexed::Incomplete(missing) => {
+                    let checked = self
+                        .budget
+                        .check_incomplete(
+                            &self.buffer[start..],
+                            missing,
+                            self.offset + self.buffer.len(),
+                        )
+                        .map_err(StructureError::from);
+                    self.state.latch_at(checked, offset)?;
+
+                    let available = self.buffer.len() - self.consumed;
+                    self.fill_buffer(missing)?;
+                    if self.buffer.len() - self.consumed == available {
+                        return self.state.latch_at(
+                            Err(StructureError::UnexpectedEof),
+                            self.offset + self.buffer.len(),
+                        );
+                    }
+                },
+            }
+        }
+    }
+
+    /// Read the next token from the input stream. Returns `Ok(None)` at the end of the
+    /// input. This guarantees that the resulting stream of tokens constitutes a valid
+    /// bencoded structure.
+    pub fn next_token(&mut self) -> Result<Option<Token<'_>>, Error> {
+        let token = self.next_raw_token()?;
+        Ok(token.map(move |token| token.resolve(&self.buffer)))
+    }
+
+    /// Read the next object from the input stream
+    ///
+    /// This behaves like [`Decoder::next_object`], except that lists and dicts pull
+    /// their content from the reader as they are traversed.
+    ///
+    /// [`Decoder::next_object`]: crate::decoding::Decoder::next_object
+    pub fn next_object<'obj>(&'obj mut self) -> Result<Option<StreamObject<'obj, R>>, Error> {
+        Ok(match self.next_raw_token()? {
+            None | Some(RawToken::End) => None,
+            Some(RawToken::List) => Some(StreamObject::List(StreamListDecoder::new(self))),
+            Some(RawToken::Dict) => Some(StreamObject::Dict(StreamDictDecoder::new(self))),
+            Some(token) => match token.resolve(&self.buffer) {
+                Token::String(s) => Some(StreamObject::Bytes(s)),
+                Token::Num(s) => Some(StreamObject::Integer(s)),
+                _ => unreachable!("lists, dicts and ends are handled above"),
+            },
+        })
+    }
+}
+
+/// An object read from a [`StreamDecoder`]
+pub enum StreamObject<'obj, R: Read + 'obj> {
+    /// A list of arbitrary objects
+    List(StreamListDecoder<'obj, R>),
+    /// A map of string-valued keys to arbitrary objects
+    Dict(StreamDictDecoder<'obj, R>),
+    /// An unparsed integer
+    Integer(&'obj str),
+    /// A byte string
+    Bytes(&'obj [u8]),
+}
+
+impl<'obj, R: Read + 'obj> StreamObject<'obj, R> {
+    /// Convert the object into the token that started it. Lists and dicts lose their
+    /// content, which is consumed from the input when they are dropped.
+    pub fn into_token(self) -> Token<'obj> {
+        match self {
+            StreamObject::List(_) => Token::List,
+            StreamObject::Dict(_) => Token::Dict,
+            StreamObject::Bytes(bytes) => Token::String(bytes),
+            StreamObject::Integer(num) => Token::Num(num),
+        }
+    }
+
+    /// Try to treat the object as a byte string. Any other variant results in an
+    /// [`ErrorKind::UnexpectedToken`].
+    ///
+    /// [`ErrorKind::UnexpectedToken`]: crate::decoding::ErrorKind::UnexpectedToken
+    pub fn try_into_bytes(self) -> Result<&'obj [u8], Error> {
+        match self {
+            StreamObject::Bytes(content) => Ok(content),
+            other => Err(Error::unexpected_token("String", other.into_token().name())),
+        }
+    }
+
+    /// Try to treat the object as an integer and return the internal string
+    /// representation. Any other variant results in an [`ErrorKind::UnexpectedToken`].
+    ///
+    /// [`ErrorKind::UnexpectedToken`]: crate::decoding::ErrorKind::UnexpectedToken
+    pub fn try_into_integer(self) -> Result<&'obj str, Error> {
+        match self {
+            StreamObject::Integer(content) => Ok(content),
+            other => Err(Error::unexpected_token("Num", other.into_token().name())),
+        }
+    }
+
+    /// Try to treat the object as a list and return the internal list content decoder.
+    /// Any other variant results in an [`ErrorKind::UnexpectedToken`].
+    ///
+    /// [`ErrorKind::UnexpectedToken`]: crate::decoding::ErrorKind::UnexpectedToken
+    pub fn try_into_list(self) -> Result<StreamListDecoder<'obj, R>, Error> {
+        match self {
+            StreamObject::List(content) => Ok(content),
+            other => Err(Error::unexpected_token("List", other.into_token().name())),
+        }
+    }
+
+    /// Try to treat the object as a dictionary and return the internal dictionary
+    /// content decoder. Any other variant results in an [`ErrorKind::UnexpectedToken`].
+    ///
+    /// [`ErrorKind::UnexpectedToken`]: crate::decoding::ErrorKind::UnexpectedToken
+    pub fn try_into_dictionary(self) -> Result<StreamDictDecoder<'obj, R>, Error> {
+        match self {
+            StreamObject::Dict(content) => Ok(content),
+            other => Err(Error::unexpected_token("Dict", other.into_token().name())),
+        }
+    }
+}
+
+/// A dictionary read from a [`StreamDecoder`]
+#[derive(Debug)]
+pub struct StreamDictDecoder<'obj, R: Read + 'obj> {
+    decoder: &'obj mut StreamDecoder<R>,
+    finished: bool,
+}
+
+/// A list read from a [`StreamDecoder`]
+#[derive(Debug)]
+pub struct StreamListDecoder<'obj, R: Read + 'obj> {
+    decoder: &'obj mut StreamDecoder<R>,
+    finished: bool,
+}
+
+impl<'obj, R: Read + 'obj> StreamDictDecoder<'obj, R> {
+    fn new(decoder: &'obj mut StreamDecoder<R>) -> Self {
+        StreamDictDecoder {
+            decoder,
+            finished: false,
+        }
+    }
+
+    /// Parse the next key/value pair from the dictionary. Returns `Ok(None)`
+    /// at the end of the dictionary
+    #[allow(clippy::type_complexity)]
+    pub fn next_pair<'item>(
+        &'item mut self,
+    ) -> Result<Option<(Vec<u8>, StreamObject<'item, R>)>, Error> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        if let Some(RawToken::String(start, end)) = self.decoder.next_raw_token()? {
+            let key = self.decoder.buffer[start..end].to_vec();
+            // This unwrap should be safe because None would produce an error here
+            let value = self.decoder.next_object()?.unwrap();
+            Ok(Some((key, value)))
+        } else {
+            // We can't have gotten anything but a string, as anything else would be
+            // a state error
+            self.finished = true;
+            Ok(None)
+        }
+    }
+
+    /// Consume (and validate the structure of) the rest of the items from the
+    /// dictionary. This method should be used to check for encoding errors if
+    /// [`StreamDictDecoder::next_pair`] is not called until it returns `Ok(None)`.
+    pub fn consume_all(&mut self) -> Result<(), Error> {
+        while self.next_pair()?.is_some() {
+            // just drop the items
+        }
+        Ok(())
+    }
+}
+
+impl<'obj, R: Read + 'obj> Drop for StreamDictDecoder<'obj, R> {
+    fn drop(&mut self) {
+        // we don't care about errors in drop; they'll be reported again in the parent
+        self.consume_all().ok();
+    }
+}
+
+impl<'obj, R: Read + 'obj> StreamListDecoder<'obj, R> {
+    fn new(decoder: &'obj mut StreamDecoder<R>) -> Self {
+        StreamListDecoder {
+            decoder,
+            finished: false,
+        }
+    }
+
+    /// Get the next item from the list. Returns `Ok(None)` at the end of the list
+    pub fn next_object<'item>(&'item mut self) -> Result<Option<StreamObject<'item, R>>, Error> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let item = self.decoder.next_object()?;
+        if item.is_none() {
+            self.finished = true;
+        }
+
+        Ok(item)
+    }
+
+    /// Consume (and validate the structure of) the rest of the items from the
+    /// list. This method should be used to check for encoding errors if
+    /// [`StreamListDecoder::next_object`] is not called until it returns `Ok(None)`.
+    pub fn consume_all(&mut self) -> Result<(), Error> {
+        while self.next_object()?.is_some() {
+            // just drop the items
+        }
+        Ok(())
+    }
+}
+
+impl<'obj, R: Read + 'obj> Drop for StreamListDecoder<'obj, R> {
+    fn drop(&mut self) {
+        // we don't care about errors in drop; they'll be reported again in the parent
+        self.consume_all().ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A reader that hands out a single byte per call to exercise buffer refills
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&byte, rest)), Some(slot)) => {
+                    *slot = byte;
+                    self.0 = rest;
+                    Ok(1)
+                },
+                _ => Ok(0),
+            }
+        }
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    fn decode_tokens(msg: &[u8]) -> Result<Vec<String>, Error> {
+        let mut decoder = StreamDecoder::new(Trickle(msg));
+        let mut tokens = Vec::new();
+        while let Some(token) = decoder.next_token()? {
+            tokens.push(format!("{:?}", token));
+        }
+        Ok(tokens)
+    }
+
+    #[test]
+    fn tokens_match_buffered_decoder() {
+        let msg = b"d3:bari1e3:fooli2ei3e5:quuxxee";
+        let expected: Vec<String> = crate::decoding::Decoder::new(msg)
+            .tokens()
+            .map(|token| format!("{:?}", token.unwrap()))
+            .collect();
+
+        assert_eq!(decode_tokens(msg).unwrap(), expected);
+    }
+
+    #[test]
+    fn objects_can_be_traversed() {
+        let mut decoder = StreamDecoder::new(Trickle(b"d3:bari1e3:fooli2e3:bazee"));
+        let mut dict = decoder.next_object().unwrap().unwrap();
+        let dict = match dict {
+            StreamObject::Dict(ref mut dict) => dict,
+            _ => panic!("expected a dict"),
+        };
+
+        let (key, value) = dict.next_pair().unwrap().unwrap();
+        assert_eq!(key, b"bar");
+        assert_eq!(value.try_into_integer().unwrap(), "1");
+
+        let (key, value) = dict.next_pair().unwrap().unwrap();
+        assert_eq!(key, b"foo");
+        let mut list = value.try_into_list().unwrap();
+        assert_eq!(
+            list.next_object().unwrap().unwrap().into_token(),
+            Token::Num("2")
+        );
+        assert_eq!(
+            list.next_object()
+                .unwrap()
+                .unwrap()
+                .try_into_bytes()
+                .unwrap(),
+            b"baz"
+        );
+        assert!(list.next_object().unwrap().is_none());
+        drop(list);
+
+        assert!(dict.next_pair().unwrap().is_none());
+    }
+
+    #[test]
+    fn dropped_objects_are_consumed() {
+        let mut decoder = StreamDecoder::new(Trickle(b"li1eli2eeei1000e"));
+        drop(decoder.next_object());
+
+        assert_eq!(decoder.next_token().unwrap(), Some(Token::Num("1000")));
+        assert_eq!(decoder.next_token().unwrap(), None);
+    }
+
+    #[test]
+    fn canonicalization_is_enforced() {
+        let err = decode_tokens(b"d3:fooi1e3:bari1ee").unwrap_err();
+        assert!(err.to_string().contains("Keys were not sorted"));
+
+        let err = decode_tokens(b"i01e").unwrap_err();
+        assert!(err.to_string().contains("got '1'"));
+    }
+
+    #[test]
+    fn truncated_input_should_fail() {
+        let err = decode_tokens(b"l5:ab").unwrap_err();
+        assert!(err.to_string().contains("EOF"));
+
+        let err = decode_tokens(b"l").unwrap_err();
+        assert!(err.to_string().contains("EOF"));
+    }
+
+    #[test]
+    fn offsets_should_count_released_tokens() {
+        let mut decoder = StreamDecoder::new(&b"li1e3:fooi01ee"[..]);
+        let err = loop {
+            if let Err(err) = decoder.next_token() {
+                break err;
+            }
+        };
+        assert_eq!(err.offset(), Some(11));
+
+        let mut decoder = StreamDecoder::new(Trickle(b"li1e5:ab"));
+        let err = loop {
+            if let Err(err) = decoder.next_token() {
+                break err;
+            }
+        };
+        assert_eq!(err.offset(), Some(8));
+    }
+
+    #[test]
+    fn recursion_should_be_limited() {
+        let mut decoder = StreamDecoder::new(&b"lllleeee"[..]).with_max_depth(3);
+        let err = loop {
+            if let Err(err) = decoder.next_token() {
+                break err;
+            }
+        };
+        assert!(err.to_string().contains("nesting depth"));
+    }
+
+    #[test]
+    fn limits_are_checked_before_buffering() {
+        // Only the length prefix is available, the content would be missing
+        let limits = DecodeLimits::new().with_max_string_length(1000);
+        let mut decoder = StreamDecoder::new(Trickle(b"l100000:")).with_limits(limits);
+        assert!(decoder.next_token().is_ok());
+        let err = decoder.next_token().unwrap_err();
+        assert!(
+            err.to_string().contains("byte string of 100000 bytes"),
+            "{}",
+            err
+        );
+        assert_eq!(err.offset(), Some(1));
+
+        let limits = DecodeLimits::new().with_max_tokens(3);
+        let mut decoder = StreamDecoder::new(&b"li1ei2ee"[..]).with_limits(limits);
+        let err = loop {
+            if let Err(err) = decoder.next_token() {
+                break err;
+            }
+        };
+        assert!(err.to_string().contains("tokens"), "{}", err);
+    }
+
+    #[test]
+    fn read_errors_are_reported_and_latched() {
+        let mut decoder = StreamDecoder::new(Failing);
+        let err = decoder.next_token().unwrap_err();
+        assert!(err.to_string().contains("broken pipe"));
+        assert!(decoder.next_token().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use core::cmp;
+
+use crate::{
+    decoding::{
+        lexer::{lex_token, Lexed},
+        Error,
+    },
+    state_tracker::{StateTracker, StructureError, Token},
+};
+
+/// Statistics about a single value, gathered by [`validate`]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct Statistics {
+    /// The number of bytes the value occupies
+    pub length: usize,
+    /// The deepest nesting of lists and dicts. Zero if the value is an atom.
+    pub max_depth: usize,
+    /// The number of dicts
+    pub dicts: usize,
+    /// The number of lists
+    pub lists: usize,
+    /// The number of integers
+    pub integers: usize,
+    /// The number of byte strings, including dictionary keys
+    pub strings: usize,
+    /// The length of the longest byte string in bytes
+    pub largest_string: usize,
+}
+
+/// Check that `buffer` starts with a valid, canonical bencode value and gather
+/// statistics about it, nesting no deeper than the default depth limit of [`Decoder`].
+///
+/// This is considerably cheaper than decoding the value, as it only looks at the tokens
+/// of the value without constructing any objects. Scanning stops at the end of the
+/// first top level value; compare [`Statistics::length`] to the length of the buffer to
+/// detect trailing data.
+///
+/// ```
+/// use bendy::decoding::validate;
+///
+/// let stats = validate(b"d3:fooli1ei2ee4:spam5:helloe").unwrap();
+/// assert_eq!(stats.length, 28);
+/// assert_eq!(stats.max_depth, 2);
+/// assert_eq!(stats.largest_string, 5);
+///
+/// assert!(validate(b"d3:fooi01ee").is_err());
+/// ```
+///
+/// [`Decoder`]: crate::decoding::Decoder
+pub fn validate(buffer: &[u8]) -> Result<Statistics, Error> {
+    validate_tokens(buffer, StateTracker::new())
+}
+
+/// Like [`validate`], but with the given nesting depth limit. See
+/// [`Decoder::with_max_depth`](crate::decoding::Decoder::with_max_depth).
+pub fn validate_with_max_depth(buffer: &[u8], max_depth: usize) -> Result<Statistics, Error> {
+    let mut state = StateTracker::new();
+    state.set_max_depth(max_depth);
+    validate_tokens(buffer, state)
+}
+
+fn validate_tokens<'a>(
+    buffer: &'a [u8],
+    mut state: StateTracker<&'a [u8], Error>,
+) -> Result<Statistics, Error> {
+    let mut stats = Statistics::default();
+    let mut depth = 0;
+    let mut offset = 0;
+
+    loop {
+        let remaining = &buffer[offset..];
+        let lexed = lex_token(remaining, offset).map_err(StructureError::from);
+        let token = match state.latch_at(lexed, offset)? {
+            Lexed::Token(token, len) => {
+                let token = token.resolve(remaining);
+                state.observe_token_at(&token, offset)?;
+                offset += len;
+                token
+            },
+            Lexed::Incomplete(_) => {
+                return state.latch_at(Err(StructureError::UnexpectedEof), buffer.len());
+            },
+        };
+
+        match token {
+            Token::List | Token::Dict => {
+                match token {
+                    Token::List => stats.lists += 1,
+                    _ => stats.dicts += 1,
+                }
+                depth += 1;
+                stats.max_depth = cmp::max(stats.max_depth, depth);
+            },
+            Token::End => depth -= 1,
+            Token::Num(_) => stats.integers += 1,
+            Token::String(bytes) => {
+                stats.strings += 1;
+                stats.largest_string = cmp::max(stats.largest_string, bytes.len());
+            },
+        }
+
+        if state.is_at_top_level() {
+            stats.length = offset;
+            return Ok(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn statistics_should_describe_the_first_value() {
+        let stats = validate(b"d1:ad1:bli1eleee1:c3:fooei42e").unwrap();
+
+        assert_eq!(
+            stats,
+            Statistics {
+                length: 25,
+                max_depth: 4,
+                dicts: 2,
+                lists: 2,
+                integers: 1,
+                strings: 4,
+                largest_string: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn atoms_should_have_no_depth() {
+        let stats = validate(b"i-12e").unwrap();
+        assert_eq!(stats.length, 5);
+        assert_eq!(stats.max_depth, 0);
+        assert_eq!(stats.integers, 1);
+
+        let stats = validate(b"0:").unwrap();
+        assert_eq!(stats.strings, 1);
+        assert_eq!(stats.largest_string, 0);
+    }
+
+    #[test]
+    fn invalid_values_should_fail() {
+        assert!(validate(b"").is_err());
+        assert!(validate(b"li1e").is_err());
+        assert!(validate(b"d1:bi1e1:ai2ee").is_err());
+        assert!(validate(b"5:abc").is_err());
+        assert!(validate(b"e").is_err());
+
+        let err = validate(b"li1ei-0ee").unwrap_err();
+        assert_eq!(err.offset(), Some(6));
+    }
+
+    #[test]
+    fn depth_limit_should_be_respected() {
+        assert!(validate_with_max_depth(b"llee", 2).is_ok());
+        assert!(validate_with_max_depth(b"llleee", 2).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use core::fmt::{self, Display, Formatter};
+
+/// A deviation from canonical bencode that was accepted by a lenient [`Decoder`]
+///
+/// [`Decoder`]: crate::decoding::Decoder
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Violation {
+    /// What was wrong with the input
+    pub kind: ViolationKind,
+    /// The position of the first byte of the offending token
+    pub offset: usize,
+}
+
+/// The kinds of non-canonical input a lenient [`Decoder`] accepts
+///
+/// [`Decoder`]: crate::decoding::Decoder
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ViolationKind {
+    /// A dictionary key that is smaller than the key before it.
+    UnsortedKey,
+    /// A dictionary key that already appeared in the same dictionary.
+    DuplicateKey,
+    /// An integer or string length with leading zeros, or a negative zero.
+    NonCanonicalInteger,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl Display for ViolationKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ViolationKind::UnsortedKey => f.write_str("Keys were not sorted"),
+            ViolationKind::DuplicateKey => f.write_str("Duplicate key"),
+            ViolationKind::NonCanonicalInteger => f.write_str("Non-canonical integer"),
+        }
+    }
+}
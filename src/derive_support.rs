@@ -0,0 +1,196 @@
+//! Helpers for the code generated by `#[derive(ToBencode, FromBencode)]` and
+//! `bencode!`. Not part of the public API.
+
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{btree_map, BTreeMap},
+    string::String,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::{btree_map, BTreeMap};
+
+use core::{iter::FromIterator, iter::Peekable, mem};
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, Object},
+    encoding::{self, SingleItemEncoder, SortedDictEncoder, ToBencode},
+    state_tracker::StructureError,
+    value::{Integer, Value},
+};
+
+/// Encode a collection of byte containers as a list of byte strings
+pub fn encode_byte_strings<I>(encoder: SingleItemEncoder, items: I) -> Result<(), encoding::Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    encoder.emit_list(|e| {
+        for item in items {
+            e.emit_bytes(item.as_ref())?;
+        }
+        Ok(())
+    })
+}
+
+/// Decode a byte string into a byte container
+pub fn decode_as_string<'ser, T>(object: Object<'_, 'ser>) -> Result<T, decoding::Error>
+where
+    T: From<&'ser [u8]>,
+{
+    object.try_into_bytes().map(T::from)
+}
+
+/// Decode a list of byte strings into a collection of byte containers
+pub fn decode_byte_strings<'ser, T, B>(object: Object<'_, 'ser>) -> Result<T, decoding::Error>
+where
+    T: FromIterator<B>,
+    B: From<&'ser [u8]>,
+{
+    let mut list = object.try_into_list()?;
+    let mut items = Vec::new();
+    while let Some(item) = list.next_object()? {
+        items.push(item.try_into_bytes()?);
+    }
+
+    Ok(items.into_iter().map(B::from).collect())
+}
+
+/// Decode a dictionary that holds the tag of an enum variant under `tag_key`, next to
+/// other entries that can only be decoded once the variant is known.
+///
+/// The dictionary is read twice: once to find the tag, and once more by `decode`,
+/// which gets the tag and the dictionary. Both passes keep the limits, the remaining
+/// allocation budget and the leniency of the decoder the dictionary was read from, and
+/// report offsets within the complete input.
+pub fn decode_tagged<'ser, T, F>(
+    object: Object<'_, 'ser>,
+    tag_key: &[u8],
+    decode: F,
+) -> Result<T, decoding::Error>
+where
+    F: FnOnce(&'ser [u8], Object<'_, 'ser>) -> Result<T, decoding::Error>,
+{
+    let mut dict = object.try_into_dictionary()?;
+
+    let tag = {
+        let mut decoder = dict.replay()?;
+        let mut dict = reread(&mut decoder)?.try_into_dictionary()?;
+        loop {
+            match dict.next_pair()? {
+                Some((key, value)) if key == tag_key => break value.try_into_bytes()?,
+                Some(_) => (),
+                None => return Err(decoding::Error::missing_field(lossy(tag_key))),
+            }
+        }
+    };
+
+    let mut decoder = dict.replay()?;
+    let result = decode(tag, reread(&mut decoder)?);
+    dict.absorb(&decoder);
+    result
+}
+
+fn reread<'obj, 'ser>(
+    decoder: &'obj mut Decoder<'ser>,
+) -> Result<Object<'obj, 'ser>, decoding::Error> {
+    decoder
+        .next_object()?
+        .ok_or_else(|| decoding::Error::from(StructureError::UnexpectedEof))
+}
+
+/// The error for a tag that doesn't belong to any variant. `expected` lists the known
+/// tags.
+pub fn unknown_variant(tag: &[u8], expected: &str) -> decoding::Error {
+    decoding::Error::unexpected_token(expected, lossy(tag))
+}
+
+/// The error for a key that must not be part of a dictionary
+pub fn unexpected_key(key: &[u8]) -> decoding::Error {
+    decoding::Error::unexpected_field(lossy(key))
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Add a pair whose key doesn't belong to any field to the map of a flattened field.
+/// Returns the number of bytes to charge against the allocation limit, including the
+/// copy of the key.
+pub fn decode_unknown<'ser, K, V>(
+    unknown: &mut BTreeMap<K, V>,
+    key: &'ser [u8],
+    value: Object<'_, 'ser>,
+) -> Result<usize, decoding::Error>
+where
+    K: Ord + From<&'ser [u8]>,
+    V: FromBencode,
+{
+    let value = V::decode_bencode_object(value)?;
+    unknown.insert(K::from(key), value);
+    Ok(key.len() + mem::size_of::<(K, V)>())
+}
+
+/// The pairs of a flattened field, which are emitted in between the other fields so
+/// that all keys stay sorted
+pub struct UnknownPairs<'a, K, V> {
+    pairs: Peekable<btree_map::Iter<'a, K, V>>,
+}
+
+impl<'a, K, V> UnknownPairs<'a, K, V>
+where
+    K: AsRef<[u8]>,
+    V: ToBencode,
+{
+    pub fn new(unknown: &'a BTreeMap<K, V>) -> Self {
+        UnknownPairs {
+            pairs: unknown.iter().peekable(),
+        }
+    }
+
+    /// Emit the pairs whose keys sort before `key`
+    pub fn emit_before(
+        &mut self,
+        dict: &mut SortedDictEncoder,
+        key: &[u8],
+    ) -> Result<(), encoding::Error> {
+        while let Some((unknown_key, value)) = self.pairs.peek() {
+            if unknown_key.as_ref() >= key {
+                break;
+            }
+            dict.emit_pair(unknown_key.as_ref(), value)?;
+            self.pairs.next();
+        }
+        Ok(())
+    }
+
+    /// Emit all remaining pairs
+    pub fn emit_rest(mut self, dict: &mut SortedDictEncoder) -> Result<(), encoding::Error> {
+        for (key, value) in &mut self.pairs {
+            dict.emit_pair(key.as_ref(), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Build the list of a `bencode!` literal
+pub fn list<'a, const N: usize>(items: [Value<'a>; N]) -> Value<'a> {
+    Value::List(IntoIterator::into_iter(items).collect())
+}
+
+/// Build the dictionary of a `bencode!` literal. The macro has already checked that
+/// all keys are unique.
+pub fn dict<'a, const N: usize>(pairs: [(&'static [u8], Value<'a>); N]) -> Value<'a> {
+    Value::Dict(
+        IntoIterator::into_iter(pairs)
+            .map(|(key, value)| (Cow::Borrowed(key), value))
+            .collect(),
+    )
+}
+
+/// Build an integer of a `bencode!` literal that doesn't fit into an `i128`. The macro
+/// has already checked that `digits` is a valid integer.
+pub fn big_integer(digits: &str) -> Value<'static> {
+    Value::Integer(digits.parse::<Integer>().expect("valid integer literal"))
+}
@@ -74,7 +74,9 @@
 //! # }
 //! ```
 //!
-//! Most primitive types already implement [`ToBencode`].
+//! Most primitive types already implement [`ToBencode`]. With the `derive` feature, it can
+//! also be derived for structs with `#[derive(ToBencode)]`; see the `bendy-derive` crate for
+//! the supported attributes.
 //!
 //! # Nesting depth limits
 //!
@@ -127,3 +129,6 @@ pub use self::{
     printable_integer::PrintableInteger,
     to_bencode::{AsString, ToBencode},
 };
+
+#[cfg(feature = "derive")]
+pub use bendy_derive::ToBencode;
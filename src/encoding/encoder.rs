@@ -1,17 +1,12 @@
 #[cfg(not(feature = "std"))]
-use alloc::{
-    borrow::ToOwned,
-    collections::BTreeMap,
-    format,
-    string::{String, ToString},
-    vec::Vec,
-};
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::ToString, vec::Vec};
 #[cfg(feature = "std")]
 use std::{collections::BTreeMap, vec::Vec};
 
 use crate::{
     encoding::{Error, PrintableInteger, ToBencode},
-    state_tracker::{StateTracker, StructureError, Token},
+    raw::check_single_value,
+    state_tracker::{InvalidState, StateTracker, StructureError, Token},
 };
 
 /// The actual encoder. Unlike the decoder, this is not zero-copy, as that would
@@ -80,11 +75,9 @@ impl Encoder {
         self.state.latch_err(ret)?;
 
         if !value_written {
-            return self
-                .state
-                .latch_err(Err(Error::from(StructureError::invalid_state(
-                    "No value was emitted",
-                ))));
+            return self.state.latch_err(Err(Error::from(StructureError::from(
+                InvalidState::NoValueEmitted,
+            ))));
         }
 
         Ok(())
@@ -116,6 +109,22 @@ impl Encoder {
         self.emit_token(Token::String(value))
     }
 
+    /// Emit the verbatim encoding of a single value
+    ///
+    /// The value is validated before it is written, so this fails if `value` is not
+    /// exactly one canonical bencode value or if it would exceed the maximum depth.
+    pub fn emit_raw(&mut self, value: &[u8]) -> Result<(), Error> {
+        self.state.check_error()?;
+
+        let first = check_single_value(value, self.state.remaining_depth())
+            .map_err(Error::malformed_content);
+        let first = self.state.latch_err(first)?;
+
+        self.state.observe_value(&first)?;
+        self.output.extend_from_slice(value);
+        Ok(())
+    }
+
     /// Emit a dictionary where you know that the keys are already
     /// sorted.  The callback must emit key/value pairs to the given
     /// encoder in sorted order.  If the key/value pairs may not be
@@ -271,6 +280,12 @@ impl<'a> SingleItemEncoder<'a> {
         self.encoder.emit_bytes(value)
     }
 
+    /// Emit the verbatim encoding of a single value. See [`Encoder::emit_raw`]
+    pub fn emit_raw(self, value: &[u8]) -> Result<(), Error> {
+        *self.value_written = true;
+        self.encoder.emit_raw(value)
+    }
+
     /// Emit an arbitrary list
     pub fn emit_list<F>(self, list_cb: F) -> Result<(), Error>
     where
@@ -389,8 +404,8 @@ impl UnsortedDictEncoder {
         }
 
         if !value_written {
-            self.error = Err(Error::from(StructureError::InvalidState(
-                "No value was emitted".to_owned(),
+            self.error = Err(Error::from(StructureError::from(
+                InvalidState::NoValueEmitted,
             )));
         } else {
             self.error = encoder.state.observe_eof().map_err(Error::from);
@@ -429,10 +444,9 @@ impl UnsortedDictEncoder {
         let vacancy = match self.content.entry(unencoded_key.to_owned()) {
             Entry::Vacant(vacancy) => vacancy,
             Entry::Occupied(occupation) => {
-                self.error = Err(Error::from(StructureError::InvalidState(format!(
-                    "Duplicate key {}",
-                    String::from_utf8_lossy(occupation.key())
-                ))));
+                self.error = Err(Error::from(StructureError::from(
+                    InvalidState::DuplicateKey(occupation.key().clone()),
+                )));
                 return self.error.clone();
             },
         };
@@ -451,6 +465,7 @@ impl UnsortedDictEncoder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{encoding::ErrorKind, state_tracker::TokenKind};
 
     #[test]
     pub fn simple_encoding_works() {
@@ -474,6 +489,33 @@ mod test {
         );
     }
 
+    #[test]
+    fn raw_values_advance_the_state() {
+        let mut encoder = Encoder::new();
+        encoder.emit_token(Token::Dict).unwrap();
+        encoder.emit_raw(b"3:bar").unwrap();
+        encoder.emit_raw(b"li1ee").unwrap();
+        encoder.emit_raw(b"3:foo").unwrap();
+        encoder.emit_raw(b"d1:ai1ee").unwrap();
+        encoder.emit_token(Token::End).unwrap();
+        assert_eq!(encoder.get_output().unwrap(), b"d3:barli1ee3:food1:ai1eee");
+
+        let mut encoder = Encoder::new();
+        encoder.emit_token(Token::Dict).unwrap();
+        match encoder.emit_raw(b"le") {
+            Err(Error(ErrorKind::StructureError(StructureError::InvalidState(
+                InvalidState::NonStringKey(TokenKind::List),
+            )))) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.emit_token(Token::Dict).unwrap();
+        encoder.emit_raw(b"3:foo").unwrap();
+        encoder.emit_raw(b"le").unwrap();
+        assert!(encoder.emit_raw(b"3:bar").is_err());
+    }
+
     #[test]
     fn emit_cb_must_emit() {
         let mut encoder = Encoder::new();
@@ -1,5 +1,5 @@
 #[cfg(feature = "std")]
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 use failure::Fail;
 
@@ -23,6 +23,10 @@ pub enum ErrorKind {
     /// Error in the bencode structure (e.g. a missing field end separator).
     #[fail(display = "bencode encoding corrupted")]
     StructureError(#[fail(cause)] StructureError),
+    /// Error that occurs if writing the encoded output fails.
+    #[cfg(feature = "std")]
+    #[fail(display = "i/o error: {}", _0)]
+    Io(Arc<io::Error>),
 }
 
 impl Error {
@@ -41,6 +45,12 @@ impl Error {
     pub fn malformed_content<T>(_cause: T) -> Error {
         Self(ErrorKind::MalformedContent)
     }
+
+    /// Raised when writing the encoded output to its destination fails.
+    #[cfg(feature = "std")]
+    pub fn io(cause: io::Error) -> Error {
+        Self(ErrorKind::Io(Arc::new(cause)))
+    }
 }
 
 impl From<StructureError> for Error {
@@ -0,0 +1,450 @@
+//! Lossless conversion between [`Value`]s and JSON text.
+//!
+//! JSON lacks byte strings and limits integers to the precision of a double in many
+//! tools, so some values are written in an escape form, an object with a single key
+//! that starts with `$`:
+//!
+//! | bencode                                     | JSON                                  |
+//! |---------------------------------------------|---------------------------------------|
+//! | byte string that is valid UTF-8             | string                                |
+//! | any other byte string                       | `{"$bytes": "<lowercase hex>"}`       |
+//! | integer within ±(2<sup>53</sup> - 1)        | number                                |
+//! | any other integer                           | `{"$int": "<decimal digits>"}`        |
+//! | list                                        | array                                 |
+//! | dictionary with UTF-8 keys                  | object                                |
+//! | any other dictionary                        | `{"$dict": [[<key>, <value>], ...]}`  |
+//!
+//! A dictionary is also written in the `$dict` form if its only key is `$bytes`, `$int`
+//! or `$dict`, so that it can't be mistaken for an escape. Keys in the `$dict` form are
+//! strings or `$bytes` objects.
+//!
+//! Reading JSON accepts the escape forms as well as integers of any size written as
+//! plain numbers. As dictionaries are sorted and integers normalized while reading,
+//! encoding the result always produces canonical bencode. JSON values without a bencode
+//! counterpart, i.e. `null`, `true`, `false` and numbers with a fraction or exponent,
+//! are rejected, as are objects with duplicate keys.
+//!
+//! Both directions limit arrays and objects to 512 levels of nesting. A `$dict` object
+//! takes three of them and a `$bytes` or `$int` object one, so values with fewer levels
+//! of lists and dictionaries may exceed the limit already. Converting them with
+//! [`to_json`] fails, instead of producing JSON that can't be read back.
+//!
+//! ```
+//! use bendy::{
+//!     decoding::FromBencode,
+//!     encoding::ToBencode,
+//!     json::{from_json, to_json},
+//!     value::Value,
+//! };
+//!
+//! let torrent = Value::from_bencode(b"d4:infod6:lengthi5e6:pieces2:\xab\xcdee").unwrap();
+//!
+//! let json = to_json(&torrent).unwrap();
+//! assert_eq!(json, r#"{"info":{"length":5,"pieces":{"$bytes":"abcd"}}}"#);
+//!
+//! let edited = json.replace("5", "9007199254740993");
+//! let value = from_json(&edited).unwrap();
+//! assert_eq!(
+//!     value.to_bencode().unwrap(),
+//!     &b"d4:infod6:lengthi9007199254740993e6:pieces2:\xab\xcdee"[..]
+//! );
+//! assert_eq!(
+//!     to_json(&value).unwrap(),
+//!     r#"{"info":{"length":{"$int":"9007199254740993"},"pieces":{"$bytes":"abcd"}}}"#
+//! );
+//! ```
+
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display, Formatter},
+    str,
+};
+
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, error::Error as StdError};
+
+use crate::value::Value;
+
+mod reader;
+
+/// The maximum nesting depth of arrays and objects, which are read recursively
+const MAX_DEPTH: usize = 512;
+
+/// The largest integer that JSON tools reading numbers as doubles represent exactly
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+const BYTES: &str = "$bytes";
+const INT: &str = "$int";
+const DICT: &str = "$dict";
+
+/// Convert `value` into compact JSON text
+///
+/// Fails with [`ErrorKind::NestingTooDeep`] if the JSON text would be nested too deeply
+/// to be read back, see the [module documentation](self).
+pub fn to_json(value: &Value) -> Result<String, Error> {
+    Writer::new(false).write(value)
+}
+
+/// Convert `value` into JSON text with one entry per line, for editing by hand
+///
+/// Fails like [`to_json`].
+pub fn to_json_pretty(value: &Value) -> Result<String, Error> {
+    Writer::new(true).write(value)
+}
+
+/// Read a value from JSON text, resolving the escape forms described in the
+/// [module documentation](self)
+pub fn from_json(text: &str) -> Result<Value<'static>, Error> {
+    reader::read(text)
+}
+
+/// The error returned when JSON text can't be converted into a [`Value`]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+/// The reasons why JSON text can't be converted into a [`Value`]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ErrorKind {
+    /// The text isn't valid JSON
+    Syntax,
+    /// `null`, `true`, `false` or a number with a fraction or exponent, which have no
+    /// counterpart in bencode
+    Unsupported,
+    /// A `$bytes`, `$int` or `$dict` object whose content doesn't have the expected form
+    InvalidEscape,
+    /// A key that appears more than once in the same object
+    DuplicateKey(Vec<u8>),
+    /// Arrays and objects are nested more than 512 levels deep
+    NestingTooDeep,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, offset: usize) -> Self {
+        Error { kind, offset }
+    }
+
+    /// The reason of the error
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte offset in the JSON text at which the problem was found. When writing
+    /// JSON, this is the length of the text written so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Syntax => f.write_str("invalid JSON")?,
+            ErrorKind::Unsupported => f.write_str("JSON value without bencode counterpart")?,
+            ErrorKind::InvalidEscape => {
+                write!(f, "malformed `{}`, `{}` or `{}` object", BYTES, INT, DICT)?
+            },
+            ErrorKind::DuplicateKey(key) => {
+                write!(f, "duplicate key `{}`", String::from_utf8_lossy(key))?
+            },
+            ErrorKind::NestingTooDeep => f.write_str("nesting too deep")?,
+        }
+        write!(f, " at offset {}", self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for Error {}
+
+struct Writer {
+    out: String,
+    pretty: bool,
+    /// The number of arrays and objects that are currently open
+    nesting: usize,
+}
+
+impl Writer {
+    fn new(pretty: bool) -> Self {
+        Writer {
+            out: String::new(),
+            pretty,
+            nesting: 0,
+        }
+    }
+
+    fn write(mut self, value: &Value) -> Result<String, Error> {
+        self.write_value(value, 0)?;
+        Ok(self.out)
+    }
+
+    fn write_value(&mut self, value: &Value, depth: usize) -> Result<(), Error> {
+        match value {
+            Value::Bytes(bytes) => self.write_bytes(bytes)?,
+            Value::Integer(integer) => match integer.to_i64() {
+                Some(small) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&small) => {
+                    self.out.push_str(&small.to_string())
+                },
+                _ => self.write_escape(INT, |writer| {
+                    writer.write_string(&integer.to_string());
+                    Ok(())
+                })?,
+            },
+            Value::List(list) => {
+                self.open('[')?;
+                for (index, item) in list.iter().enumerate() {
+                    self.begin_entry(index, depth + 1);
+                    self.write_value(item, depth + 1)?;
+                }
+                self.end_container(list.len(), depth, ']');
+            },
+            Value::Dict(dict) if needs_escape(dict) => self.write_escape(DICT, |writer| {
+                writer.open('[')?;
+                for (index, (key, value)) in dict.iter().enumerate() {
+                    writer.begin_entry(index, depth + 1);
+                    writer.open('[')?;
+                    writer.write_bytes(key)?;
+                    writer.out.push_str(if writer.pretty { ", " } else { "," });
+                    writer.write_value(value, depth + 1)?;
+                    writer.close(']');
+                }
+                writer.end_container(dict.len(), depth, ']');
+                Ok(())
+            })?,
+            Value::Dict(dict) => {
+                self.open('{')?;
+                for (index, (key, value)) in dict.iter().enumerate() {
+                    self.begin_entry(index, depth + 1);
+                    self.write_bytes(key)?;
+                    self.write_colon();
+                    self.write_value(value, depth + 1)?;
+                }
+                self.end_container(dict.len(), depth, '}');
+            },
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        match str::from_utf8(bytes) {
+            Ok(text) => {
+                self.write_string(text);
+                Ok(())
+            },
+            Err(_) => self.write_escape(BYTES, |writer| {
+                writer.out.push('"');
+                for byte in bytes {
+                    writer.out.push(hex_digit(byte >> 4));
+                    writer.out.push(hex_digit(byte & 0xf));
+                }
+                writer.out.push('"');
+                Ok(())
+            }),
+        }
+    }
+
+    fn write_string(&mut self, text: &str) {
+        self.out.push('"');
+        for char in text.chars() {
+            match char {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\u{0}'..='\u{1f}' => {
+                    self.out.push_str("\\u00");
+                    self.out.push(hex_digit(char as u8 >> 4));
+                    self.out.push(hex_digit(char as u8 & 0xf));
+                },
+                _ => self.out.push(char),
+            }
+        }
+        self.out.push('"');
+    }
+
+    /// Write an object with `marker` as its only key and the value written by `content`
+    fn write_escape(
+        &mut self,
+        marker: &str,
+        content: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.open('{')?;
+        self.write_string(marker);
+        self.write_colon();
+        content(self)?;
+        self.close('}');
+        Ok(())
+    }
+
+    /// Open an array or object, unless that nests them deeper than they can be read
+    fn open(&mut self, bracket: char) -> Result<(), Error> {
+        if self.nesting == MAX_DEPTH {
+            return Err(Error::new(ErrorKind::NestingTooDeep, self.out.len()));
+        }
+        self.nesting += 1;
+        self.out.push(bracket);
+        Ok(())
+    }
+
+    fn close(&mut self, bracket: char) {
+        self.nesting -= 1;
+        self.out.push(bracket);
+    }
+
+    fn write_colon(&mut self) {
+        self.out.push_str(if self.pretty { ": " } else { ":" });
+    }
+
+    /// Start the entry at `index` of a container, on a new line if printing pretty
+    fn begin_entry(&mut self, index: usize, depth: usize) {
+        if index > 0 {
+            self.out.push(',');
+        }
+        if self.pretty {
+            self.out.push('\n');
+            self.indent(depth);
+        }
+    }
+
+    /// Close a container after `count` entries
+    fn end_container(&mut self, count: usize, depth: usize, close: char) {
+        if self.pretty && count > 0 {
+            self.out.push('\n');
+            self.indent(depth);
+        }
+        self.close(close);
+    }
+
+    fn indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str("  ");
+        }
+    }
+}
+
+/// Whether `dict` can't be written as plain object, because one of its keys isn't
+/// UTF-8 or it could be mistaken for an escape form
+fn needs_escape(dict: &BTreeMap<Cow<[u8]>, Value>) -> bool {
+    let is_marker = |key: &[u8]| {
+        [BYTES, INT, DICT]
+            .iter()
+            .any(|marker| marker.as_bytes() == key)
+    };
+    match dict.keys().next() {
+        Some(key) if dict.len() == 1 && is_marker(key) => true,
+        _ => dict.keys().any(|key| str::from_utf8(key).is_err()),
+    }
+}
+
+fn hex_digit(value: u8) -> char {
+    char::from_digit(u32::from(value), 16).expect("value below 16")
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::{decoding::FromBencode, encoding::ToBencode};
+
+    fn round_trip(bencode: &[u8], json: &str) {
+        let value = Value::from_bencode(bencode).unwrap();
+        assert_eq!(to_json(&value).unwrap(), json);
+        assert_eq!(from_json(json).unwrap(), value);
+        assert_eq!(from_json(&to_json_pretty(&value).unwrap()).unwrap(), value);
+    }
+
+    #[test]
+    fn text_should_become_json_strings() {
+        round_trip(b"10:caf\xc3\xa9 \"\\\n\x01", r#""café \"\\\n\u0001""#);
+        round_trip(b"0:", r#""""#);
+    }
+
+    #[test]
+    fn binary_strings_should_be_escaped() {
+        round_trip(b"3:\x00\xff\x10", r#"{"$bytes":"00ff10"}"#);
+        round_trip(b"d1:\xffi1ee", r#"{"$dict":[[{"$bytes":"ff"},1]]}"#);
+    }
+
+    #[test]
+    fn large_integers_should_be_escaped() {
+        round_trip(b"i9007199254740991e", "9007199254740991");
+        round_trip(b"i-9007199254740991e", "-9007199254740991");
+        round_trip(b"i9007199254740992e", r#"{"$int":"9007199254740992"}"#);
+        round_trip(
+            b"i-123456789012345678901234567890e",
+            r#"{"$int":"-123456789012345678901234567890"}"#,
+        );
+    }
+
+    #[test]
+    fn dicts_like_escapes_should_be_escaped() {
+        round_trip(b"d6:$bytes2:ffe", r#"{"$dict":[["$bytes","ff"]]}"#);
+        round_trip(b"d4:$intli1eee", r#"{"$dict":[["$int",[1]]]}"#);
+        round_trip(b"d4:$inti1e1:ai2ee", r#"{"$int":1,"a":2}"#);
+        round_trip(b"d5:$listdee", r#"{"$list":{}}"#);
+    }
+
+    #[test]
+    fn pretty_json_should_be_indented() {
+        let value = Value::from_bencode(b"d1:ali1ei2ee1:bde1:cd1:\xffleee").unwrap();
+        assert_eq!(
+            to_json_pretty(&value).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {},\n  \"c\": {\"$dict\": [\n    \
+             [{\"$bytes\": \"ff\"}, []]\n  ]}\n}"
+        );
+    }
+
+    /// Wrap `leaf` into `levels` lists, or into dicts with a binary key if `binary_dicts`
+    fn nested(levels: usize, leaf: Value<'static>, binary_dicts: bool) -> Value<'static> {
+        (0..levels).fold(leaf, |value, _| {
+            if binary_dicts {
+                Value::Dict(Some((Cow::from(&b"\xff"[..]), value)).into_iter().collect())
+            } else {
+                Value::List(vec![value])
+            }
+        })
+    }
+
+    #[test]
+    fn values_nested_too_deeply_should_fail() {
+        let value = nested(MAX_DEPTH, Value::from(1), false);
+        assert_eq!(from_json(&to_json(&value).unwrap()).unwrap(), value);
+
+        let value = nested(MAX_DEPTH, Value::from(&b"\xff"[..]), false);
+        let err = to_json(&value).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NestingTooDeep);
+
+        let value = nested(MAX_DEPTH / 3, Value::from(1), true);
+        assert_eq!(from_json(&to_json(&value).unwrap()).unwrap(), value);
+
+        let value = nested(MAX_DEPTH / 3 + 1, Value::from(1), true);
+        let err = to_json_pretty(&value).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NestingTooDeep);
+    }
+
+    #[test]
+    fn json_should_produce_canonical_bencode() {
+        let value = from_json(r#" { "b" : [ -0, 7, 12345678901234567890123 ], "a": "" } "#);
+        let encoded = value.unwrap().to_bencode().unwrap();
+        assert_eq!(encoded, &b"d1:a0:1:bli0ei7ei12345678901234567890123eee"[..]);
+
+        let value = from_json(r#"{"$dict": [["b", 1], [{"$bytes": "61"}, 2]]}"#).unwrap();
+        assert_eq!(value.to_bencode().unwrap(), &b"d1:ai2e1:bi1ee"[..]);
+
+        let value = from_json(r#"{"$int": "-00"}"#).unwrap();
+        assert_eq!(value.to_bencode().unwrap(), &b"i0e"[..]);
+
+        let value = from_json(r#""\ud83d\ude00\/""#).unwrap();
+        assert_eq!(value, Value::from(vec![0xf0, 0x9f, 0x98, 0x80, b'/']));
+    }
+}
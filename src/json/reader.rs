@@ -0,0 +1,406 @@
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::str;
+
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use crate::{
+    json::{Error, ErrorKind, BYTES, DICT, INT, MAX_DEPTH},
+    value::{Integer, Value},
+};
+
+pub(crate) fn read(text: &str) -> Result<Value<'static>, Error> {
+    let mut reader = Reader {
+        text: text.as_bytes(),
+        offset: 0,
+        depth: 0,
+    };
+
+    let value = reader.read_value()?;
+    reader.skip_whitespace();
+    if reader.offset != reader.text.len() {
+        return Err(reader.error(ErrorKind::Syntax));
+    }
+    Ok(value)
+}
+
+struct Reader<'t> {
+    text: &'t [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl<'t> Reader<'t> {
+    fn read_value(&mut self) -> Result<Value<'static>, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'"') => Ok(Value::Bytes(Cow::Owned(self.read_string()?.into_bytes()))),
+            Some(b'-') | Some(b'0'..=b'9') => self.read_number(),
+            Some(b'[') => self.nested(Self::read_array),
+            Some(b'{') => self.nested(Self::read_object),
+            Some(b't') => self.read_unsupported("true"),
+            Some(b'f') => self.read_unsupported("false"),
+            Some(b'n') => self.read_unsupported("null"),
+            _ => Err(self.error(ErrorKind::Syntax)),
+        }
+    }
+
+    /// Read an array or object, keeping track of the nesting depth
+    fn nested(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<Value<'static>, Error>,
+    ) -> Result<Value<'static>, Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(ErrorKind::NestingTooDeep));
+        }
+        self.depth += 1;
+        let value = read(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn read_unsupported(&mut self, literal: &str) -> Result<Value<'static>, Error> {
+        if self.text[self.offset..].starts_with(literal.as_bytes()) {
+            Err(self.error(ErrorKind::Unsupported))
+        } else {
+            Err(self.error(ErrorKind::Syntax))
+        }
+    }
+
+    fn read_number(&mut self) -> Result<Value<'static>, Error> {
+        let start = self.offset;
+        self.eat(b'-');
+
+        match self.peek() {
+            Some(b'0') => self.offset += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error(ErrorKind::Syntax)),
+        }
+
+        if let Some(b'.') | Some(b'e') | Some(b'E') = self.peek() {
+            return Err(Error::new(ErrorKind::Unsupported, start));
+        }
+
+        let digits = str::from_utf8(&self.text[start..self.offset]).expect("ASCII digits");
+        let integer: Integer = digits.parse().expect("valid integer");
+        Ok(Value::Integer(integer))
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        self.expect(b'"')?;
+
+        let mut string = String::new();
+        loop {
+            let run = self.text[self.offset..]
+                .iter()
+                .position(|&byte| byte == b'"' || byte == b'\\' || byte < 0x20)
+                .ok_or_else(|| Error::new(ErrorKind::Syntax, self.text.len()))?;
+            let run = &self.text[self.offset..self.offset + run];
+            string.push_str(str::from_utf8(run).expect("slice of a str at ASCII bounds"));
+            self.offset += run.len();
+
+            match self.next() {
+                Some(b'"') => return Ok(string),
+                Some(b'\\') => string.push(self.read_escape()?),
+                _ => return Err(self.error_before(ErrorKind::Syntax)),
+            }
+        }
+    }
+
+    /// Read the rest of an escape sequence after its backslash
+    fn read_escape(&mut self) -> Result<char, Error> {
+        let char = match self.next() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let start = self.offset - 2;
+                let high = self.read_hex4()?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    if !(self.eat(b'\\') && self.eat(b'u')) {
+                        return Err(Error::new(ErrorKind::Syntax, start));
+                    }
+                    let low = self.read_hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(Error::new(ErrorKind::Syntax, start));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| Error::new(ErrorKind::Syntax, start))?
+            },
+            _ => return Err(self.error_before(ErrorKind::Syntax)),
+        };
+        Ok(char)
+    }
+
+    fn read_hex4(&mut self) -> Result<u32, Error> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            .and_then(|digits| str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error(ErrorKind::Syntax))?;
+        self.offset += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("hex digits"))
+    }
+
+    fn read_array(&mut self) -> Result<Value<'static>, Error> {
+        self.expect(b'[')?;
+        let mut list = Vec::new();
+
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Ok(Value::List(list));
+        }
+
+        loop {
+            list.push(self.read_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(b',') => (),
+                Some(b']') => return Ok(Value::List(list)),
+                _ => return Err(self.error_before(ErrorKind::Syntax)),
+            }
+        }
+    }
+
+    fn read_object(&mut self) -> Result<Value<'static>, Error> {
+        self.expect(b'{')?;
+        let mut pairs = Vec::new();
+
+        self.skip_whitespace();
+        if !self.eat(b'}') {
+            loop {
+                self.skip_whitespace();
+                let key_offset = self.offset;
+                let key = self.read_string()?;
+                self.skip_whitespace();
+                self.expect(b':')?;
+                self.skip_whitespace();
+                let value_offset = self.offset;
+                let value = self.read_value()?;
+                pairs.push((key, key_offset, value, value_offset));
+
+                self.skip_whitespace();
+                match self.next() {
+                    Some(b',') => (),
+                    Some(b'}') => break,
+                    _ => return Err(self.error_before(ErrorKind::Syntax)),
+                }
+            }
+        }
+
+        if let [(key, ..)] = pairs.as_slice() {
+            let unescape: Option<fn(_) -> _> = match key.as_str() {
+                BYTES => Some(unescape_bytes),
+                INT => Some(unescape_int),
+                DICT => Some(unescape_dict),
+                _ => None,
+            };
+            if let Some(unescape) = unescape {
+                let (_, _, value, value_offset) = pairs.pop().expect("a single pair");
+                return unescape(value).map_err(|kind| Error::new(kind, value_offset));
+            }
+        }
+
+        let mut dict = BTreeMap::new();
+        for (key, key_offset, value, _) in pairs {
+            let key = key.into_bytes();
+            if dict.contains_key(&key[..]) {
+                return Err(Error::new(ErrorKind::DuplicateKey(key), key_offset));
+            }
+            dict.insert(Cow::Owned(key), value);
+        }
+        Ok(Value::Dict(dict))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.offset).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.offset += 1;
+        Some(byte)
+    }
+
+    /// Skip `byte` if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.offset += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(ErrorKind::Syntax))
+        }
+    }
+
+    /// An error at the current offset
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.offset)
+    }
+
+    /// An error at the byte that was just read
+    fn error_before(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.offset.saturating_sub(1))
+    }
+}
+
+/// Decode the hex digits of a `$bytes` object
+fn unescape_bytes(value: Value<'static>) -> Result<Value<'static>, ErrorKind> {
+    let digits = match &value {
+        Value::Bytes(digits) if digits.len() % 2 == 0 => digits,
+        _ => return Err(ErrorKind::InvalidEscape),
+    };
+
+    digits
+        .chunks(2)
+        .map(|pair| match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(high), Some(low)) => Ok(high << 4 | low),
+            _ => Err(ErrorKind::InvalidEscape),
+        })
+        .collect::<Result<Vec<u8>, _>>()
+        .map(|bytes| Value::Bytes(Cow::Owned(bytes)))
+}
+
+/// Parse the decimal digits of an `$int` object
+fn unescape_int(value: Value<'static>) -> Result<Value<'static>, ErrorKind> {
+    let digits = value.as_str().ok_or(ErrorKind::InvalidEscape)?;
+    digits
+        .parse::<Integer>()
+        .map(Value::Integer)
+        .map_err(|_| ErrorKind::InvalidEscape)
+}
+
+/// Collect the `[key, value]` pairs of a `$dict` object
+fn unescape_dict(value: Value<'static>) -> Result<Value<'static>, ErrorKind> {
+    let pairs = match value {
+        Value::List(pairs) => pairs,
+        _ => return Err(ErrorKind::InvalidEscape),
+    };
+
+    let mut dict = BTreeMap::new();
+    for pair in pairs {
+        let mut pair = match pair {
+            Value::List(pair) if pair.len() == 2 => pair.into_iter(),
+            _ => return Err(ErrorKind::InvalidEscape),
+        };
+        let (key, value) = match (pair.next(), pair.next()) {
+            (Some(Value::Bytes(key)), Some(value)) => (key, value),
+            _ => return Err(ErrorKind::InvalidEscape),
+        };
+        if dict.contains_key(&key) {
+            return Err(ErrorKind::DuplicateKey(key.into_owned()));
+        }
+        dict.insert(key, value);
+    }
+    Ok(Value::Dict(dict))
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (ErrorKind, usize) {
+        let error = read(text).unwrap_err();
+        (error.kind().clone(), error.offset())
+    }
+
+    #[test]
+    fn invalid_json_should_fail() {
+        assert_eq!(error(""), (ErrorKind::Syntax, 0));
+        assert_eq!(error("[1,]"), (ErrorKind::Syntax, 3));
+        assert_eq!(error("[1 2]"), (ErrorKind::Syntax, 3));
+        assert_eq!(error("{\"a\" 1}"), (ErrorKind::Syntax, 5));
+        assert_eq!(error("{1: 2}"), (ErrorKind::Syntax, 1));
+        assert_eq!(error("01"), (ErrorKind::Syntax, 1));
+        assert_eq!(error("-"), (ErrorKind::Syntax, 1));
+        assert_eq!(error("\"a\nb\""), (ErrorKind::Syntax, 2));
+        assert_eq!(error("\"\\x\""), (ErrorKind::Syntax, 2));
+        assert_eq!(error("\"\\ud83d\""), (ErrorKind::Syntax, 1));
+        assert_eq!(error("\"abc"), (ErrorKind::Syntax, 4));
+        assert_eq!(error("1 1"), (ErrorKind::Syntax, 2));
+        assert_eq!(error("nul"), (ErrorKind::Syntax, 0));
+    }
+
+    #[test]
+    fn values_without_bencode_counterpart_should_fail() {
+        assert_eq!(error("[null]"), (ErrorKind::Unsupported, 1));
+        assert_eq!(error("true"), (ErrorKind::Unsupported, 0));
+        assert_eq!(error("false"), (ErrorKind::Unsupported, 0));
+        assert_eq!(error("[1.5]"), (ErrorKind::Unsupported, 1));
+        assert_eq!(error("-1e3"), (ErrorKind::Unsupported, 0));
+    }
+
+    #[test]
+    fn malformed_escapes_should_fail() {
+        assert_eq!(
+            error(r#"{"$bytes": "abc"}"#),
+            (ErrorKind::InvalidEscape, 11)
+        );
+        assert_eq!(error(r#"{"$bytes": "zz"}"#), (ErrorKind::InvalidEscape, 11));
+        assert_eq!(error(r#"{"$bytes": 12}"#), (ErrorKind::InvalidEscape, 11));
+        assert_eq!(error(r#"{"$int": "1.0"}"#), (ErrorKind::InvalidEscape, 9));
+        assert_eq!(
+            error(r#"{"$dict": [["a"]]}"#),
+            (ErrorKind::InvalidEscape, 10)
+        );
+        assert_eq!(
+            error(r#"{"$dict": [[1, 2]]}"#),
+            (ErrorKind::InvalidEscape, 10)
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_should_fail() {
+        let duplicate = ErrorKind::DuplicateKey(b"a".to_vec());
+        assert_eq!(
+            error(r#"{"a": 1, "b": 2, "a": 3}"#),
+            (duplicate.clone(), 17)
+        );
+        assert_eq!(
+            error(r#"{"$dict": [["a", 1], [{"$bytes": "61"}, 2]]}"#),
+            (duplicate, 10)
+        );
+    }
+
+    #[test]
+    fn deep_nesting_should_fail() {
+        let deep = "[".repeat(MAX_DEPTH + 1);
+        assert_eq!(error(&deep), (ErrorKind::NestingTooDeep, MAX_DEPTH));
+
+        let nested = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(read(&nested).is_ok());
+    }
+}
@@ -1,7 +1,8 @@
 //! Encodes and decodes bencoded structures.
 //!
 //! The decoder is explicitly designed to be zero-copy as much as possible, and to not
-//! accept any sort of invalid encoding in any mode (including non-canonical encodings)
+//! accept any sort of invalid encoding (including non-canonical encodings) unless lenient
+//! mode is requested explicitly. Non-canonical input can be rewritten with [`canonical`].
 //!
 //! The encoder is likewise designed to ensure that it only produces valid structures.
 
@@ -13,11 +14,25 @@ extern crate alloc;
 #[macro_use]
 mod assert_matches;
 
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod canonical;
+#[cfg(feature = "codec")]
+pub mod codec;
 pub mod decoding;
+#[doc(hidden)]
+pub mod derive_support;
 pub mod encoding;
+pub mod json;
+mod pointer;
+pub mod pretty;
+pub mod raw;
 pub mod state_tracker;
 
 #[cfg(feature = "serde")]
 pub mod serde;
 
 pub mod value;
+
+#[cfg(feature = "derive")]
+pub use bendy_derive::{bencode, bencode_bytes};
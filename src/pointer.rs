@@ -0,0 +1,183 @@
+//! Parsing of the pointers accepted by [`Value::pointer`] and [`resolve_pointer`].
+//!
+//! A pointer is either empty, referring to the whole value, or a sequence of segments
+//! that each start with `/`. A segment selects the value under a dictionary key, or the
+//! item at an index of a list. As keys are byte strings, segments may contain escapes:
+//!
+//! - `~0` stands for `~`
+//! - `~1` stands for `/`
+//! - `~xHH` stands for the byte with the hexadecimal value `HH`
+//!
+//! [`Value::pointer`]: crate::value::Value::pointer
+//! [`resolve_pointer`]: crate::decoding::resolve_pointer
+
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Split `pointer` into its segments. Returns `None` if the pointer is malformed.
+pub(crate) fn segments(pointer: &str) -> Option<Segments<'_>> {
+    let rest = match pointer {
+        "" => None,
+        _ => Some(pointer.strip_prefix('/')?),
+    };
+
+    let segments = Segments { rest };
+    if segments.clone().all(|segment| segment.is_valid()) {
+        Some(segments)
+    } else {
+        None
+    }
+}
+
+/// An iterator over the segments of a pointer
+#[derive(Clone)]
+pub(crate) struct Segments<'p> {
+    rest: Option<&'p str>,
+}
+
+/// A single, still escaped segment of a pointer
+#[derive(Copy, Clone)]
+pub(crate) struct Segment<'p>(&'p str);
+
+impl<'p> Iterator for Segments<'p> {
+    type Item = Segment<'p>;
+
+    fn next(&mut self) -> Option<Segment<'p>> {
+        let rest = self.rest?;
+        match rest.find('/') {
+            Some(end) => {
+                self.rest = Some(&rest[end + 1..]);
+                Some(Segment(&rest[..end]))
+            },
+            None => {
+                self.rest = None;
+                Some(Segment(rest))
+            },
+        }
+    }
+}
+
+impl<'p> Segment<'p> {
+    /// The dictionary key this segment selects
+    pub(crate) fn key(self) -> Cow<'p, [u8]> {
+        if self.0.contains('~') {
+            Cow::Owned(self.bytes().collect::<Vec<_>>())
+        } else {
+            Cow::Borrowed(self.0.as_bytes())
+        }
+    }
+
+    /// Whether this segment selects the dictionary key `key`, without allocating
+    pub(crate) fn matches(self, key: &[u8]) -> bool {
+        self.bytes().eq(key.iter().copied())
+    }
+
+    /// The list index this segment selects, if it is a number without leading zeros
+    pub(crate) fn index(self) -> Option<usize> {
+        let digits = self.0.as_bytes();
+        if digits.is_empty() || (digits[0] == b'0' && digits.len() > 1) {
+            return None;
+        }
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        self.0.parse().ok()
+    }
+
+    fn is_valid(self) -> bool {
+        let mut bytes = Unescape(self.0.as_bytes());
+        while let Some(byte) = bytes.next_escaped() {
+            if byte.is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn bytes(self) -> impl Iterator<Item = u8> + 'p {
+        let mut bytes = Unescape(self.0.as_bytes());
+        core::iter::from_fn(move || bytes.next_escaped().flatten())
+    }
+}
+
+/// Resolves the escapes of a segment one byte at a time
+struct Unescape<'p>(&'p [u8]);
+
+impl<'p> Unescape<'p> {
+    /// The next byte, or `Some(None)` if the next escape is malformed
+    fn next_escaped(&mut self) -> Option<Option<u8>> {
+        let (&first, rest) = self.0.split_first()?;
+        if first != b'~' {
+            self.0 = rest;
+            return Some(Some(first));
+        }
+
+        let (byte, len) = match rest {
+            [b'0', ..] => (b'~', 1),
+            [b'1', ..] => (b'/', 1),
+            [b'x', high, low, ..] => match (hex_digit(*high), hex_digit(*low)) {
+                (Some(high), Some(low)) => (high << 4 | low, 3),
+                _ => return Some(None),
+            },
+            _ => return Some(None),
+        };
+
+        self.0 = &rest[len..];
+        Some(Some(byte))
+    }
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+
+    fn keys(pointer: &str) -> Option<Vec<Vec<u8>>> {
+        segments(pointer)
+            .map(|segments| segments.map(|segment| segment.key().into_owned()).collect())
+    }
+
+    #[test]
+    fn segments_should_be_split_at_slashes() {
+        assert_eq!(keys(""), Some(vec![]));
+        assert_eq!(keys("/"), Some(vec![vec![]]));
+        assert_eq!(
+            keys("/info/files/0"),
+            Some(vec![b"info".to_vec(), b"files".to_vec(), b"0".to_vec()])
+        );
+        assert_eq!(keys("info"), None);
+    }
+
+    #[test]
+    fn escapes_should_be_resolved() {
+        assert_eq!(
+            keys("/a~1b/~0/~xff~x0A"),
+            Some(vec![b"a/b".to_vec(), b"~".to_vec(), vec![0xff, 0x0a]])
+        );
+        assert!(segments("/a~1b").unwrap().next().unwrap().matches(b"a/b"));
+
+        assert_eq!(keys("/~"), None);
+        assert_eq!(keys("/~2"), None);
+        assert_eq!(keys("/~xf"), None);
+        assert_eq!(keys("/~xfg"), None);
+    }
+
+    #[test]
+    fn indices_should_not_have_leading_zeros() {
+        let index = |pointer: &str| segments(pointer).unwrap().next().unwrap().index();
+
+        assert_eq!(index("/0"), Some(0));
+        assert_eq!(index("/12"), Some(12));
+        assert_eq!(index("/012"), None);
+        assert_eq!(index("/-1"), None);
+        assert_eq!(index("/+1"), None);
+        assert_eq!(index("/"), None);
+    }
+}
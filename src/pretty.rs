@@ -0,0 +1,347 @@
+//! Human-readable rendering of bencode for debugging.
+//!
+//! The output looks like the literals accepted by `bencode!`: dictionaries and lists
+//! are indented one entry per line, integers are printed as numbers, and strings that
+//! are valid UTF-8 are quoted. Other byte strings are printed as hex along with their
+//! length. Long strings and deeply nested containers are truncated, so even large
+//! values stay readable.
+//!
+//! ```
+//! use bendy::pretty::{pretty_print, PrettyPrinter};
+//!
+//! let torrent = b"d8:announce3:url4:infod6:lengthi5e6:pieces4:\x00\x01\x02\x03ee";
+//! assert_eq!(
+//!     pretty_print(torrent).unwrap(),
+//!     "{\n  \"announce\": \"url\",\n  \"info\": {\n    \"length\": 5,\n    \
+//!      \"pieces\": <4 bytes: 00010203>\n  }\n}"
+//! );
+//!
+//! let printer = PrettyPrinter::new().with_max_depth(1);
+//! assert_eq!(
+//!     printer.print(torrent).unwrap(),
+//!     "{\n  \"announce\": \"url\",\n  \"info\": {... 2 entries}\n}"
+//! );
+//! ```
+//!
+//! [`Value`] implements [`Display`](core::fmt::Display) with the default settings of
+//! [`PrettyPrinter`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str};
+
+use crate::{
+    decoding::{self, Decoder, Object},
+    state_tracker::{StructureError, SyntaxError},
+    value::Value,
+};
+
+const INDENT: &str = "  ";
+const ELLIPSIS: &str = "...";
+
+/// Render `input`, which must contain exactly one value, with the default settings of
+/// [`PrettyPrinter`].
+pub fn pretty_print(input: &[u8]) -> Result<String, decoding::Error> {
+    PrettyPrinter::new().print(input)
+}
+
+/// A configurable renderer of values and encoded buffers
+///
+/// By default, strings are truncated after 64 characters and containers are printed
+/// at any depth.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PrettyPrinter {
+    max_string_width: usize,
+    max_depth: usize,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        PrettyPrinter {
+            max_string_width: 64,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl PrettyPrinter {
+    /// Create a new printer with the default settings
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set the maximum number of characters printed of a single string. Byte strings
+    /// that aren't text take two characters per byte.
+    pub fn with_max_string_width(mut self, max_string_width: usize) -> Self {
+        self.max_string_width = max_string_width;
+        self
+    }
+
+    /// Set the number of nested containers whose content is printed. Deeper containers
+    /// are summarized by their number of entries, and a depth of zero summarizes even
+    /// the outermost container.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Render `value`
+    pub fn print_value(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out, value, 0);
+        out
+    }
+
+    /// Render `input`, which must contain exactly one value, without decoding it into
+    /// a [`Value`] first
+    pub fn print(&self, input: &[u8]) -> Result<String, decoding::Error> {
+        let mut decoder = Decoder::new(input);
+
+        let mut out = String::new();
+        match decoder.next_object()? {
+            Some(object) => self.write_object(&mut out, object, 0)?,
+            None => return Err(StructureError::UnexpectedEof.into()),
+        }
+
+        if decoder.offset() != input.len() {
+            let offset = decoder.offset();
+            let error = StructureError::from(SyntaxError::TrailingData { offset });
+            return Err(decoding::Error::from(error).at(offset, Vec::new()));
+        }
+
+        Ok(out)
+    }
+
+    fn write_value(&self, out: &mut String, value: &Value, depth: usize) {
+        match value {
+            Value::Bytes(bytes) => self.write_bytes(out, bytes),
+            Value::Integer(integer) => out.push_str(&integer.to_string()),
+            Value::List(list) if depth >= self.max_depth => {
+                write_summary(out, '[', list.len(), ("item", "items"), ']')
+            },
+            Value::List(list) => {
+                out.push('[');
+                for (index, item) in list.iter().enumerate() {
+                    begin_entry(out, index, depth + 1);
+                    self.write_value(out, item, depth + 1);
+                }
+                end_container(out, list.len(), depth, ']');
+            },
+            Value::Dict(dict) if depth >= self.max_depth => {
+                write_summary(out, '{', dict.len(), ("entry", "entries"), '}')
+            },
+            Value::Dict(dict) => {
+                out.push('{');
+                for (index, (key, value)) in dict.iter().enumerate() {
+                    begin_entry(out, index, depth + 1);
+                    self.write_bytes(out, key);
+                    out.push_str(": ");
+                    self.write_value(out, value, depth + 1);
+                }
+                end_container(out, dict.len(), depth, '}');
+            },
+        }
+    }
+
+    fn write_object(
+        &self,
+        out: &mut String,
+        object: Object,
+        depth: usize,
+    ) -> Result<(), decoding::Error> {
+        match object {
+            Object::Bytes(bytes) => self.write_bytes(out, bytes),
+            Object::Integer(integer) => out.push_str(integer),
+            Object::List(mut list) if depth >= self.max_depth => {
+                let mut count = 0;
+                while list.next_object()?.is_some() {
+                    count += 1;
+                }
+                write_summary(out, '[', count, ("item", "items"), ']');
+            },
+            Object::List(mut list) => {
+                out.push('[');
+                let mut count = 0;
+                while let Some(item) = list.next_object()? {
+                    begin_entry(out, count, depth + 1);
+                    self.write_object(out, item, depth + 1)?;
+                    count += 1;
+                }
+                end_container(out, count, depth, ']');
+            },
+            Object::Dict(mut dict) if depth >= self.max_depth => {
+                let mut count = 0;
+                while dict.next_pair()?.is_some() {
+                    count += 1;
+                }
+                write_summary(out, '{', count, ("entry", "entries"), '}');
+            },
+            Object::Dict(mut dict) => {
+                out.push('{');
+                let mut count = 0;
+                while let Some((key, value)) = dict.next_pair()? {
+                    begin_entry(out, count, depth + 1);
+                    self.write_bytes(out, key);
+                    out.push_str(": ");
+                    self.write_object(out, value, depth + 1)?;
+                    count += 1;
+                }
+                end_container(out, count, depth, '}');
+            },
+        }
+        Ok(())
+    }
+
+    /// Write text as quoted string and anything else as hex, truncated to the maximum
+    /// string width
+    fn write_bytes(&self, out: &mut String, bytes: &[u8]) {
+        match text(bytes) {
+            Some(text) => {
+                out.push('"');
+                let mut chars = text.chars();
+                for char in chars.by_ref().take(self.max_string_width) {
+                    out.extend(char.escape_debug());
+                }
+                out.push('"');
+                if chars.next().is_some() {
+                    out.push_str(ELLIPSIS);
+                    out.push_str(&format!(" ({} bytes)", bytes.len()));
+                }
+            },
+            None => {
+                let shown = bytes.len().min(self.max_string_width / 2);
+                out.push_str(&format!("<{} bytes: ", bytes.len()));
+                for byte in &bytes[..shown] {
+                    out.push(hex_digit(byte >> 4));
+                    out.push(hex_digit(byte & 0xf));
+                }
+                if shown < bytes.len() {
+                    out.push_str(ELLIPSIS);
+                }
+                out.push('>');
+            },
+        }
+    }
+}
+
+/// Render `self` with the default settings of [`PrettyPrinter`]
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&PrettyPrinter::new().print_value(self))
+    }
+}
+
+/// The byte string as text, if it is valid UTF-8 without control characters other than
+/// whitespace
+fn text(bytes: &[u8]) -> Option<&str> {
+    let text = str::from_utf8(bytes).ok()?;
+    if text
+        .chars()
+        .any(|char| char.is_control() && !char.is_whitespace())
+    {
+        return None;
+    }
+    Some(text)
+}
+
+fn hex_digit(value: u8) -> char {
+    char::from_digit(u32::from(value), 16).expect("value below 16")
+}
+
+/// Start a new line for the entry at `index` of a container
+fn begin_entry(out: &mut String, index: usize, depth: usize) {
+    if index > 0 {
+        out.push(',');
+    }
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
+
+/// Close a container after `count` entries
+fn end_container(out: &mut String, count: usize, depth: usize, close: char) {
+    if count > 0 {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str(INDENT);
+        }
+    }
+    out.push(close);
+}
+
+/// Write a container that is too deep to print as the number of its entries
+fn write_summary(out: &mut String, open: char, count: usize, noun: (&str, &str), close: char) {
+    out.push(open);
+    if count > 0 {
+        let noun = if count == 1 { noun.0 } else { noun.1 };
+        out.push_str(&format!("{} {} {}", ELLIPSIS, count, noun));
+    }
+    out.push(close);
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::decoding::FromBencode;
+
+    const INPUT: &[u8] = b"d0:de5:bytes3:\x00\xff\x107:escapes4:\"\t\n\\4:listli1eli-2eeledeee";
+
+    #[test]
+    fn values_and_buffers_should_print_alike() {
+        let expected = "{\n  \"\": {},\n  \"bytes\": <3 bytes: 00ff10>,\n  \
+                        \"escapes\": \"\\\"\\t\\n\\\\\",\n  \"list\": [\n    1,\n    [\n      -2\n    ],\n    \
+                        [],\n    {}\n  ]\n}";
+
+        assert_eq!(pretty_print(INPUT).unwrap(), expected);
+        let value = Value::from_bencode(INPUT).unwrap();
+        assert_eq!(PrettyPrinter::new().print_value(&value), expected);
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[test]
+    fn deep_containers_should_be_summarized() {
+        let printer = PrettyPrinter::new().with_max_depth(1);
+        let expected = "{\n  \"\": {},\n  \"bytes\": <3 bytes: 00ff10>,\n  \
+                        \"escapes\": \"\\\"\\t\\n\\\\\",\n  \"list\": [... 4 items]\n}";
+
+        assert_eq!(printer.print(INPUT).unwrap(), expected);
+        let value = Value::from_bencode(INPUT).unwrap();
+        assert_eq!(printer.print_value(&value), expected);
+
+        let printer = PrettyPrinter::new().with_max_depth(0);
+        assert_eq!(printer.print(b"ld1:ai1eee").unwrap(), "[... 1 item]");
+        assert_eq!(printer.print(b"d1:ai1e1:bi2ee").unwrap(), "{... 2 entries}");
+        assert_eq!(printer.print(b"le").unwrap(), "[]");
+    }
+
+    #[test]
+    fn long_strings_should_be_truncated() {
+        let printer = PrettyPrinter::new().with_max_string_width(4);
+
+        let text = Value::from("abcdefgh");
+        assert_eq!(printer.print_value(&text), "\"abcd\"... (8 bytes)");
+        assert_eq!(printer.print_value(&Value::from("abcd")), "\"abcd\"");
+
+        let binary = Value::from(vec![0xab; 8]);
+        assert_eq!(printer.print_value(&binary), "<8 bytes: abab...>");
+        assert_eq!(
+            printer.print(b"3:\xab\xab\x00").unwrap(),
+            "<3 bytes: abab...>"
+        );
+    }
+
+    #[test]
+    fn malformed_input_should_fail() {
+        assert!(pretty_print(b"").is_err());
+        assert!(pretty_print(b"i1ei2e").is_err());
+        assert!(pretty_print(b"d1:bi1e1:ai2ee").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! `RawBencode` captures the exact encoding of a single value, so it can be passed
+//! through or re-emitted without ever being decoded.
+//!
+//! This is useful whenever a part of a structure needs to be preserved byte for byte,
+//! e.g. to compute the info hash of a torrent file.
+//!
+//! ```
+//! use bendy::{
+//!     decoding::{FromBencode, Object, ResultExt},
+//!     encoding::ToBencode,
+//!     raw::RawBencode,
+//! };
+//!
+//! struct Torrent {
+//!     announce: String,
+//!     info: RawBencode<'static>,
+//! }
+//!
+//! impl FromBencode for Torrent {
+//!     fn decode_bencode_object(object: Object) -> Result<Self, bendy::decoding::Error> {
+//!         let mut announce = None;
+//!         let mut info = None;
+//!
+//!         let mut dict = object.try_into_dictionary()?;
+//!         while let Some(pair) = dict.next_pair()? {
+//!             match pair {
+//!                 (b"announce", value) => {
+//!                     announce = String::decode_bencode_object(value).context("announce").map(Some)?;
+//!                 },
+//!                 (b"info", value) => {
+//!                     info = RawBencode::decode_bencode_object(value).context("info").map(Some)?;
+//!                 },
+//!                 _ => (),
+//!             }
+//!         }
+//!
+//!         Ok(Torrent {
+//!             announce: announce.ok_or_else(|| bendy::decoding::Error::missing_field("announce"))?,
+//!             info: info.ok_or_else(|| bendy::decoding::Error::missing_field("info"))?,
+//!         })
+//!     }
+//! }
+//!
+//! let torrent = Torrent::from_bencode(b"d8:announce3:foo4:infod4:name3:bar5:piecei1eee").unwrap();
+//! assert_eq!(torrent.info.as_bytes(), b"d4:name3:bar5:piecei1ee");
+//! assert_eq!(torrent.info.to_bencode().unwrap(), b"d4:name3:bar5:piecei1ee");
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::Cow, vec::Vec};
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, FromBencodeBorrowed, Object},
+    encoding::{self, SingleItemEncoder, ToBencode},
+    state_tracker::{StructureError, SyntaxError, Token},
+    value::Value,
+};
+
+/// The verbatim encoding of a single, valid bencode value
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+pub struct RawBencode<'a>(Cow<'a, [u8]>);
+
+impl<'a> RawBencode<'a> {
+    /// Wrap the encoding of a single value, after validating that it is canonical
+    /// bencode and that there is no trailing data.
+    pub fn new(bytes: impl Into<Cow<'a, [u8]>>) -> Result<Self, decoding::Error> {
+        let bytes = bytes.into();
+        check_single_value(&bytes, <Self as FromBencode>::EXPECTED_RECURSION_DEPTH)?;
+        Ok(RawBencode(bytes))
+    }
+
+    /// The encoded value
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Unwrap the encoded value
+    pub fn into_inner(self) -> Cow<'a, [u8]> {
+        self.0
+    }
+
+    /// Convert this value into an owned value with static lifetime
+    pub fn into_owned(self) -> RawBencode<'static> {
+        RawBencode(Cow::Owned(self.0.into_owned()))
+    }
+}
+
+impl<'a> AsRef<[u8]> for RawBencode<'a> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> ToBencode for RawBencode<'a> {
+    // The depth of the captured value isn't known statically, so this leaves the same
+    // room for external containers as `Value` does.
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_raw(&self.0)
+    }
+}
+
+impl<'a> FromBencode for RawBencode<'a> {
+    const EXPECTED_RECURSION_DEPTH: usize = <Self as ToBencode>::MAX_DEPTH;
+
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let raw = object.into_raw()?;
+        Ok(RawBencode(Cow::Owned(raw.into_owned())))
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for RawBencode<'ser> {
+    const EXPECTED_RECURSION_DEPTH: usize = <Self as ToBencode>::MAX_DEPTH;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, decoding::Error> {
+        object.into_raw().map(RawBencode)
+    }
+}
+
+/// Validate that `bytes` contain exactly one complete value that doesn't nest deeper
+/// than `max_depth`, and return the first token of that value.
+pub(crate) fn check_single_value(
+    bytes: &[u8],
+    max_depth: usize,
+) -> Result<Token<'_>, decoding::Error> {
+    let mut decoder = Decoder::new(bytes).with_max_depth(max_depth);
+    let first = match decoder.next_object()? {
+        Some(Object::List(mut list)) => {
+            list.consume_all()?;
+            Token::List
+        },
+        Some(Object::Dict(mut dict)) => {
+            dict.consume_all()?;
+            Token::Dict
+        },
+        Some(atom) => atom.into_token(),
+        None => return Err(StructureError::UnexpectedEof.into()),
+    };
+
+    if decoder.offset() != bytes.len() {
+        let offset = decoder.offset();
+        let error = StructureError::from(SyntaxError::TrailingData { offset });
+        return Err(decoding::Error::from(error).at(offset, Vec::new()));
+    }
+
+    Ok(first)
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn nested_values_are_captured_verbatim() {
+        let input = b"ld3:fooi1ee5:helloi-3elee";
+        let items = Vec::<RawBencode>::from_bencode(input).unwrap();
+
+        let raw: Vec<&[u8]> = items.iter().map(RawBencode::as_bytes).collect();
+        assert_eq!(
+            raw,
+            vec![
+                &b"d3:fooi1ee"[..],
+                &b"5:hello"[..],
+                &b"i-3e"[..],
+                &b"le"[..]
+            ]
+        );
+        assert_eq!(items.to_bencode().unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn borrowed_values_point_into_the_input() {
+        let input = b"ld3:fooi1ee5:helloe";
+        let items = Vec::<RawBencode>::from_bencode_borrowed(input).unwrap();
+
+        match items[0].clone().into_inner() {
+            Cow::Borrowed(raw) => assert_eq!(raw.as_ptr(), input[1..].as_ptr()),
+            Cow::Owned(_) => panic!("expected a borrowed value"),
+        }
+        assert_eq!(items[1].as_bytes(), b"5:hello");
+    }
+
+    #[test]
+    fn new_should_validate_input() {
+        assert!(RawBencode::new(&b"d3:fooi1ee"[..]).is_ok());
+        assert!(RawBencode::new(&b""[..]).is_err());
+        assert!(RawBencode::new(&b"i01e"[..]).is_err());
+        assert!(RawBencode::new(&b"d3:fooi1e3:bari2ee"[..]).is_err());
+
+        let err = RawBencode::new(&b"i1ei2e"[..]).unwrap_err().to_string();
+        assert!(err.contains("Trailing data at offset 3"), "{}", err);
+    }
+
+    #[test]
+    fn raw_values_respect_encoder_depth() {
+        let raw = RawBencode::new(&b"llee"[..]).unwrap();
+
+        let mut encoder = encoding::Encoder::new().with_max_depth(2);
+        assert!(encoder.emit(&raw).is_ok());
+
+        let mut encoder = encoding::Encoder::new().with_max_depth(2);
+        assert!(encoder.emit_list(|e| e.emit(&raw)).is_err());
+    }
+}
@@ -26,5 +26,5 @@ pub(crate) use crate::{
     decoding::{self, Decoder, Tokens},
     encoding::{self, Encoder, UnsortedDictEncoder},
     serde::{ser::Serializer, Error, Result},
-    state_tracker::{StructureError, Token},
+    state_tracker::{InvalidState, StructureError, Token, TokenKind},
 };
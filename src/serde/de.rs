@@ -127,8 +127,21 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
             Some(Token::Dict) => self.deserialize_map(visitor),
             Some(Token::String(_)) => self.deserialize_bytes(visitor),
             Some(Token::List) => self.deserialize_seq(visitor),
-            Some(Token::Num(_)) => self.deserialize_i64(visitor),
-            Some(Token::End) => Err(Error::Decode(StructureError::invalid_state("End").into())),
+            Some(Token::Num(num)) => {
+                // Pick the smallest type that holds the integer, preferring signed ones
+                if num.parse::<i64>().is_ok() {
+                    self.deserialize_i64(visitor)
+                } else if num.parse::<u64>().is_ok() {
+                    self.deserialize_u64(visitor)
+                } else if num.parse::<i128>().is_ok() {
+                    self.deserialize_i128(visitor)
+                } else {
+                    self.deserialize_u128(visitor)
+                }
+            },
+            Some(Token::End) => Err(Error::Decode(
+                StructureError::from(InvalidState::UnexpectedToken(TokenKind::End)).into(),
+            )),
             None => Err(Error::Decode(StructureError::UnexpectedEof.into())),
         }
     }
@@ -3,5 +3,8 @@ mod state;
 mod structure_error;
 mod token;
 
-pub use self::token::Token;
-pub(crate) use self::{stack::Stack, state::StateTracker, structure_error::StructureError};
+pub(crate) use self::{stack::Stack, state::StateTracker};
+pub use self::{
+    structure_error::{Expected, InvalidState, LimitExceeded, StructureError, SyntaxError},
+    token::{Token, TokenKind},
+};
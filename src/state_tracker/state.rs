@@ -1,13 +1,17 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+use core::cmp::Ordering;
 
-use crate::state_tracker::{Stack, StructureError, Token};
+use crate::{
+    decoding::{self, PathSegment},
+    state_tracker::{InvalidState, Stack, StructureError, Token},
+};
 
 /// The state of current level of the decoder
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 enum State<S: AsRef<[u8]>, E> {
-    /// An inner list. Allows any token
-    Seq,
+    /// An inner list. Allows any token. Contains the number of items started so far
+    Seq(usize),
     /// Inside a map, expecting a key. Contains the last key read, so sorting can be validated
     MapKey(Option<S>),
     /// Inside a map, expecting a value. Contains the last key read, so sorting can be validated
@@ -49,6 +53,12 @@ where
         self.max_depth - self.state.len()
     }
 
+    /// Whether all lists and dicts that were opened have been closed again, i.e.
+    /// whether the tokens observed so far form complete values.
+    pub fn is_at_top_level(&self) -> bool {
+        self.state.is_empty()
+    }
+
     /// Observe that an EOF was seen. This function is idempotent.
     pub fn observe_eof(&mut self) -> Result<(), E> {
         self.check_error()?;
@@ -60,87 +70,183 @@ where
         }
     }
 
-    #[allow(clippy::match_same_arms)]
+    /// Observe that the given token was seen, latching any error.
     pub fn observe_token<'a>(&mut self, token: &Token<'a>) -> Result<(), E>
+    where
+        S: From<&'a [u8]>,
+    {
+        let result = self.transition(token).map_err(E::from);
+        self.latch_err(result)
+    }
+
+    /// Observe a complete value whose tokens aren't observed one by one, such as a
+    /// verbatim copy of an encoded value, latching any error. `first` is the first token
+    /// of the value, so that byte strings can still act as dictionary keys. The caller is
+    /// responsible for validating the content of containers, including their depth.
+    pub fn observe_value<'a>(&mut self, first: &Token<'a>) -> Result<(), E>
     where
         S: From<&'a [u8]>,
     {
         use self::{State::*, Token::*};
 
+        let result = match (self.state.pop(), *first) {
+            (Some(oldstate @ MapKey(_)), tok @ List) | (Some(oldstate @ MapKey(_)), tok @ Dict) => {
+                self.state.push(oldstate);
+                Err(InvalidState::NonStringKey(tok.kind()).into())
+            },
+            (Some(MapValue(label)), List) | (Some(MapValue(label)), Dict) => {
+                self.state.push(MapKey(Some(label)));
+                Ok(())
+            },
+            (Some(Seq(count)), List) | (Some(Seq(count)), Dict) => {
+                self.state.push(Seq(count + 1));
+                Ok(())
+            },
+            (None, List) | (None, Dict) => Ok(()),
+            (state, tok) => {
+                // Atoms are complete values on their own
+                self.state.extend(state);
+                self.transition(&tok)
+            },
+        };
+        self.latch_err(result.map_err(E::from))
+    }
+
+    /// Advance the state past `token` without latching errors. If the token isn't
+    /// valid at this position, the state is left untouched.
+    #[allow(clippy::match_same_arms)]
+    pub fn transition<'a>(&mut self, token: &Token<'a>) -> Result<(), StructureError>
+    where
+        S: From<&'a [u8]>,
+    {
+        use self::{State::*, Token::*};
+
+        // Opening a container is the only way to exceed the depth limit
+        let too_deep = match *token {
+            List | Dict => self.state.len() >= self.max_depth,
+            _ => false,
+        };
+
         match (self.state.pop(), *token) {
             (None, End) => {
-                return self.latch_err(Err(E::from(StructureError::invalid_state(
-                    "End not allowed at top level",
-                ))));
+                return Err(InvalidState::EndAtTopLevel.into());
             },
-            (Some(Seq), End) => {},
+            (Some(Seq(_)), End) => {},
             (Some(MapKey(_)), End) => {},
             (Some(MapKey(None)), String(label)) => {
                 self.state.push(MapValue(S::from(label)));
             },
             (Some(MapKey(Some(oldlabel))), String(label)) => {
                 if oldlabel.as_ref() >= label {
-                    return self.latch_err(Err(E::from(StructureError::UnsortedKeys)));
+                    self.state.push(MapKey(Some(oldlabel)));
+                    return Err(StructureError::UnsortedKeys);
                 }
                 self.state.push(MapValue(S::from(label)));
             },
-            (Some(oldstate @ MapKey(_)), _tok) => {
+            (Some(oldstate @ MapKey(_)), tok) => {
                 self.state.push(oldstate);
-                return self.latch_err(Err(E::from(StructureError::invalid_state(
-                    "Map keys must be strings",
-                ))));
-            },
-            (Some(MapValue(label)), List) => {
-                self.state.push(MapKey(Some(label)));
-                if self.state.len() >= self.max_depth {
-                    return self.latch_err(Err(E::from(StructureError::NestingTooDeep)));
-                }
-                self.state.push(Seq);
-            },
-            (Some(MapValue(label)), Dict) => {
-                self.state.push(MapKey(Some(label)));
-                if self.state.len() >= self.max_depth {
-                    return self.latch_err(Err(E::from(StructureError::NestingTooDeep)));
-                }
-                self.state.push(MapKey(None));
+                return Err(InvalidState::NonStringKey(tok.kind()).into());
             },
             (Some(oldstate @ MapValue(_)), End) => {
                 self.state.push(oldstate);
-                return self.latch_err(Err(E::from(StructureError::invalid_state(
-                    "Missing map value",
-                ))));
+                return Err(InvalidState::MissingValue.into());
+            },
+            (Some(oldstate), _) if too_deep => {
+                self.state.push(oldstate);
+                return Err(StructureError::NestingTooDeep);
             },
-            (Some(MapValue(label)), _) => {
+            (Some(MapValue(label)), tok) => {
                 self.state.push(MapKey(Some(label)));
+                self.open_container(tok);
             },
-            (oldstate, List) => {
-                if let Some(oldstate) = oldstate {
-                    self.state.push(oldstate);
-                }
-                if self.state.len() >= self.max_depth {
-                    return self.latch_err(Err(E::from(StructureError::NestingTooDeep)));
-                }
-                self.state.push(Seq);
+            (Some(Seq(count)), tok) => {
+                self.state.push(Seq(count + 1));
+                self.open_container(tok);
             },
-            (oldstate, Dict) => {
-                if let Some(oldstate) = oldstate {
-                    self.state.push(oldstate);
-                }
-
-                if self.state.len() >= self.max_depth {
-                    return self.latch_err(Err(E::from(StructureError::NestingTooDeep)));
-                }
-                self.state.push(MapKey(None));
+            (Some(failed @ Failed(_)), _) => {
+                self.state.push(failed);
             },
-            (oldstate, _) => {
-                if let Some(oldstate) = oldstate {
-                    self.state.push(oldstate);
-                }
+            (None, _) if too_deep => {
+                return Err(StructureError::NestingTooDeep);
+            },
+            (None, tok) => {
+                self.open_container(tok);
             },
         }
         Ok(())
     }
 
+    /// Whether the next token is expected to be a dictionary key
+    pub fn expects_key(&self) -> bool {
+        matches!(self.state.peek(), Some(State::MapKey(_)))
+    }
+
+    /// Advance the state past `token` like [`StateTracker::transition`], but accept
+    /// dictionary keys that are out of order or repeated. If the key isn't greater than
+    /// the previous one, returns how it compares to it.
+    pub fn transition_lenient<'a>(
+        &mut self,
+        token: &Token<'a>,
+    ) -> Result<Option<Ordering>, StructureError>
+    where
+        S: From<&'a [u8]>,
+    {
+        if let (Some(State::MapKey(Some(previous))), Token::String(label)) =
+            (self.state.peek(), *token)
+        {
+            let ordering = label.cmp(previous.as_ref());
+            if ordering != Ordering::Greater {
+                self.state.pop();
+                self.state.push(State::MapValue(S::from(label)));
+                return Ok(Some(ordering));
+            }
+        }
+
+        self.transition(token).map(|()| None)
+    }
+
+    fn open_container(&mut self, token: Token) {
+        match token {
+            Token::List => self.state.push(State::Seq(0)),
+            Token::Dict => self.state.push(State::MapKey(None)),
+            _ => (),
+        }
+    }
+
+    /// The location of the next token within the structure, from the outermost
+    /// container inwards
+    pub fn path(&self) -> Vec<PathSegment> {
+        self.build_path(true)
+    }
+
+    /// The location of the most recently started value within the structure, from the
+    /// outermost container inwards
+    pub fn item_path(&self) -> Vec<PathSegment> {
+        self.build_path(false)
+    }
+
+    fn build_path(&self, next_token: bool) -> Vec<PathSegment> {
+        let innermost = self.state.len().saturating_sub(1);
+        let mut path = Vec::new();
+
+        for (depth, state) in self.state.iter().enumerate() {
+            // Outer containers always refer to the value that is being decoded
+            let next_item = next_token && depth == innermost;
+            match state {
+                State::Seq(count) if next_item => path.push(PathSegment::Index(*count)),
+                State::Seq(0) => (),
+                State::Seq(count) => path.push(PathSegment::Index(count - 1)),
+                State::MapKey(Some(key)) if !next_item => {
+                    path.push(PathSegment::Key(key.as_ref().to_vec()))
+                },
+                State::MapValue(key) => path.push(PathSegment::Key(key.as_ref().to_vec())),
+                State::MapKey(_) | State::Failed(_) => (),
+            }
+        }
+
+        path
+    }
+
     pub fn latch_err<T>(&mut self, result: Result<T, E>) -> Result<T, E> {
         self.check_error()?;
         if let Err(ref err) = result {
@@ -157,3 +263,49 @@ where
         }
     }
 }
+
+impl<S: AsRef<[u8]>> StateTracker<S, decoding::Error> {
+    /// Observe a token that starts at `offset` in the input. Errors record where they
+    /// occurred.
+    pub fn observe_token_at<'a>(
+        &mut self,
+        token: &Token<'a>,
+        offset: usize,
+    ) -> Result<(), decoding::Error>
+    where
+        S: From<&'a [u8]>,
+    {
+        self.check_error()?;
+        let result = self.transition(token);
+        self.latch_at(result, offset)
+    }
+
+    /// Observe that the input ends at `offset`. This function is idempotent.
+    pub fn observe_eof_at(&mut self, offset: usize) -> Result<(), decoding::Error> {
+        self.check_error()?;
+
+        let result = if self.state.is_empty() {
+            Ok(())
+        } else {
+            Err(StructureError::UnexpectedEof)
+        };
+        self.latch_at(result, offset)
+    }
+
+    /// Latch an error that occurred at `offset`, recording the offset and the current
+    /// path. Syntax errors know their exact offset, which takes precedence.
+    pub fn latch_at<T>(
+        &mut self,
+        result: Result<T, StructureError>,
+        offset: usize,
+    ) -> Result<T, decoding::Error> {
+        let result = result.map_err(|err| {
+            let offset = match &err {
+                StructureError::SyntaxError(syntax_error) => syntax_error.offset(),
+                _ => offset,
+            };
+            decoding::Error::from(err).at(offset, self.path())
+        });
+        self.latch_err(result)
+    }
+}
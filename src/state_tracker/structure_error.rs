@@ -1,21 +1,17 @@
 #[cfg(not(feature = "std"))]
-use alloc::{
-    format,
-    string::{String, ToString},
-};
-#[cfg(not(feature = "std"))]
-use core::fmt::Display;
-#[cfg(feature = "std")]
-use std::fmt::Display;
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
 
 use failure::Fail;
 
+use crate::state_tracker::TokenKind;
+
 /// An encoding or decoding error
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Fail)]
 pub enum StructureError {
     #[fail(display = "Saw the wrong type of token: {}", _0)]
     /// Wrong type of token detected.
-    InvalidState(String),
+    InvalidState(InvalidState),
     #[fail(display = "Keys were not sorted")]
     /// Keys were not sorted.
     UnsortedKeys,
@@ -24,21 +20,233 @@ pub enum StructureError {
     UnexpectedEof,
     #[fail(display = "Malformed number of unexpected character: {}", _0)]
     /// Unexpected characters detected.
-    SyntaxError(String),
+    SyntaxError(SyntaxError),
     #[fail(display = "Maximum nesting depth exceeded")]
     /// Exceeded the recursion limit.
     NestingTooDeep,
+    #[fail(display = "Resource limit exceeded: {}", _0)]
+    /// Exceeded one of the configured decoding limits.
+    LimitExceeded(LimitExceeded),
+}
+
+/// A token that is not allowed at its position in the structure
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum InvalidState {
+    /// An end token was found outside of any list or dictionary.
+    EndAtTopLevel,
+    /// A dictionary key was not a byte string. Contains the kind of token found instead.
+    NonStringKey(TokenKind),
+    /// A dictionary ended right after a key.
+    MissingValue,
+    /// A token was found where no token of its kind can be handled.
+    UnexpectedToken(TokenKind),
+    /// An encoding callback returned without emitting a value.
+    NoValueEmitted,
+    /// The same dictionary key was emitted twice. Contains the key.
+    DuplicateKey(Vec<u8>),
+}
+
+impl InvalidState {
+    /// The kind of token that was found, if it is known
+    pub fn found(&self) -> Option<TokenKind> {
+        match *self {
+            InvalidState::EndAtTopLevel | InvalidState::MissingValue => Some(TokenKind::End),
+            InvalidState::NonStringKey(found) | InvalidState::UnexpectedToken(found) => Some(found),
+            InvalidState::NoValueEmitted | InvalidState::DuplicateKey(_) => None,
+        }
+    }
+
+    /// The kind of token that would have been valid instead, if there is exactly one
+    pub fn expected(&self) -> Option<TokenKind> {
+        match *self {
+            InvalidState::NonStringKey(_) => Some(TokenKind::String),
+            _ => None,
+        }
+    }
+}
+
+impl Display for InvalidState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            InvalidState::EndAtTopLevel => f.write_str("End not allowed at top level"),
+            InvalidState::NonStringKey(_) => f.write_str("Map keys must be strings"),
+            InvalidState::MissingValue => f.write_str("Missing map value"),
+            InvalidState::UnexpectedToken(found) => write!(f, "{}", found),
+            InvalidState::NoValueEmitted => f.write_str("No value was emitted"),
+            InvalidState::DuplicateKey(key) => {
+                write!(f, "Duplicate key {}", String::from_utf8_lossy(key))
+            },
+        }
+    }
+}
+
+/// A resource limit that was exceeded while decoding
+///
+/// See [`DecodeLimits`](crate::decoding::DecodeLimits) for how to configure the limits.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum LimitExceeded {
+    /// The input is larger than the maximum input size.
+    InputSize {
+        /// The configured maximum in bytes
+        limit: usize,
+    },
+    /// A byte string is longer than the maximum string length.
+    StringLength {
+        /// The length of the byte string
+        length: usize,
+        /// The configured maximum in bytes
+        limit: usize,
+    },
+    /// A list or dict contains more than the maximum number of items.
+    ContainerItems {
+        /// The configured maximum
+        limit: usize,
+    },
+    /// The input contains more than the maximum number of tokens.
+    TokenCount {
+        /// The configured maximum
+        limit: usize,
+    },
+    /// The decoded values need more memory than the allocation budget allows.
+    Allocation {
+        /// The configured maximum in bytes
+        limit: usize,
+    },
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::InputSize { limit } => {
+                write!(f, "input is larger than {} bytes", limit)
+            },
+            LimitExceeded::StringLength { length, limit } => write!(
+                f,
+                "byte string of {} bytes is longer than {} bytes",
+                length, limit
+            ),
+            LimitExceeded::ContainerItems { limit } => {
+                write!(f, "container has more than {} items", limit)
+            },
+            LimitExceeded::TokenCount { limit } => {
+                write!(f, "input has more than {} tokens", limit)
+            },
+            LimitExceeded::Allocation { limit } => {
+                write!(f, "allocation budget of {} bytes exhausted", limit)
+            },
+        }
+    }
 }
 
-impl StructureError {
-    pub fn unexpected(expected: impl Display, got: char, offset: usize) -> Self {
-        StructureError::SyntaxError(format!(
-            "Expected {}, got {:?} at offset {}",
-            expected, got, offset
-        ))
+/// Malformed input within a single token
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum SyntaxError {
+    /// A character that doesn't fit the token it is part of.
+    UnexpectedCharacter {
+        /// The characters that would have been valid
+        expected: Expected,
+        /// The character that was found
+        found: char,
+        /// The position of the character
+        offset: usize,
+    },
+    /// A number that doesn't fit into the range of supported values.
+    InvalidInteger {
+        /// The position of the first byte of the token
+        offset: usize,
+    },
+    /// A character that can't start any token.
+    InvalidToken {
+        /// The character that was found
+        found: char,
+        /// The position of the character
+        offset: usize,
+    },
+    /// Input that continues after a value that was expected to be complete.
+    TrailingData {
+        /// The position of the first byte after the value
+        offset: usize,
+    },
+}
+
+impl SyntaxError {
+    /// The position in the input at which the error was detected
+    pub fn offset(&self) -> usize {
+        match *self {
+            SyntaxError::UnexpectedCharacter { offset, .. }
+            | SyntaxError::InvalidInteger { offset }
+            | SyntaxError::InvalidToken { offset, .. }
+            | SyntaxError::TrailingData { offset } => offset,
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SyntaxError::UnexpectedCharacter {
+                expected,
+                found,
+                offset,
+            } => write!(
+                f,
+                "Expected {}, got {:?} at offset {}",
+                expected, found, offset
+            ),
+            SyntaxError::InvalidInteger { offset } => {
+                write!(f, "Invalid integer at offset {}", offset)
+            },
+            SyntaxError::InvalidToken { found, offset } => write!(
+                f,
+                "Invalid token starting with {:?} at offset {}",
+                found, offset
+            ),
+            SyntaxError::TrailingData { offset } => write!(f, "Trailing data at offset {}", offset),
+        }
     }
+}
+
+/// The characters that would have been valid at the position of a [`SyntaxError`]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Expected {
+    /// A minus sign or any digit, at the start of a number
+    SignOrDigit,
+    /// A digit other than zero, after a minus sign
+    NonZeroDigit,
+    /// Any digit, after a minus sign in lenient mode
+    Digit,
+    /// The given terminator, after a zero
+    Terminator(char),
+    /// Another digit or the given terminator
+    DigitOrTerminator(char),
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Expected::SignOrDigit => f.write_str("'-' or '0'..'9'"),
+            Expected::NonZeroDigit => f.write_str("'1'..'9'"),
+            Expected::Digit => f.write_str("'0'..'9'"),
+            Expected::Terminator(terminator) => write!(f, "{:?}", terminator),
+            Expected::DigitOrTerminator(terminator) => write!(f, "{:?} or '0'..'9'", terminator),
+        }
+    }
+}
+
+impl From<InvalidState> for StructureError {
+    fn from(error: InvalidState) -> Self {
+        StructureError::InvalidState(error)
+    }
+}
+
+impl From<SyntaxError> for StructureError {
+    fn from(error: SyntaxError) -> Self {
+        StructureError::SyntaxError(error)
+    }
+}
 
-    pub fn invalid_state(expected: impl Display) -> Self {
-        StructureError::InvalidState(expected.to_string())
+impl From<LimitExceeded> for StructureError {
+    fn from(error: LimitExceeded) -> Self {
+        StructureError::LimitExceeded(error)
     }
 }
@@ -1,3 +1,5 @@
+use core::fmt::{self, Display, Formatter};
+
 /// A raw bencode token
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum Token<'a> {
@@ -15,12 +17,50 @@ pub enum Token<'a> {
 
 impl<'a> Token<'a> {
     pub fn name(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    /// The kind of this token, without its content
+    pub fn kind(&self) -> TokenKind {
         match *self {
-            Token::Dict => "Dict",
-            Token::End => "End",
-            Token::List => "List",
-            Token::Num(_) => "Num",
-            Token::String(_) => "String",
+            Token::Dict => TokenKind::Dict,
+            Token::End => TokenKind::End,
+            Token::List => TokenKind::List,
+            Token::Num(_) => TokenKind::Num,
+            Token::String(_) => TokenKind::String,
+        }
+    }
+}
+
+/// The kind of a [`Token`], used to describe tokens in errors
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum TokenKind {
+    /// The beginning of a list
+    List,
+    /// The beginning of a dictionary
+    Dict,
+    /// A byte string
+    String,
+    /// A number
+    Num,
+    /// The end of a list or dictionary
+    End,
+}
+
+impl TokenKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Dict => "Dict",
+            TokenKind::End => "End",
+            TokenKind::List => "List",
+            TokenKind::Num => "Num",
+            TokenKind::String => "String",
         }
     }
 }
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -1,20 +1,52 @@
 //! `Value`s hold arbitrary borrowed or owneed bencode data. Unlike `Objects`,
 //! they can be cloned and traversed multiple times.
 //!
-//! `Value` implements `FromBencode`, `ToBencode`. If the `serde` feature is
-//! enabled, it also implements `Serialize` and `Deserialize`.
+//! `Value` implements `FromBencode`, `FromBencodeBorrowed` and `ToBencode`. If the
+//! `serde` feature is enabled, it also implements `Serialize` and `Deserialize`.
+//!
+//! Nested values can be read with accessors like [`Value::as_str`] and [`Value::get`],
+//! by indexing with `[]` or with [`Value::pointer`], and edited with methods like
+//! [`Value::insert`] and [`Value::entry`]. Values convert from integers, strings, byte
+//! strings, vectors and maps, and can be collected from iterators:
+//!
+//! ```
+//! use bendy::{encoding::ToBencode, value::Value};
+//!
+//! let mut info: Value = vec![("name", Value::from("debian.iso"))].into_iter().collect();
+//! info.insert("piece length", 262_144);
+//! info["name"] = Value::from("ubuntu.iso");
+//!
+//! assert_eq!(info["piece length"].as_u64(), Some(262_144));
+//! assert_eq!(
+//!     info.to_bencode().unwrap(),
+//!     &b"d4:name10:ubuntu.iso12:piece lengthi262144ee"[..]
+//! );
+//! ```
+//!
+//! Values decoded with `FromBencode` own all of their byte strings, while values
+//! decoded with `FromBencodeBorrowed` borrow them from the input:
+//!
+//! ```
+//! use bendy::{decoding::FromBencodeBorrowed, value::Value};
+//! use std::borrow::Cow;
+//!
+//! let input = b"d6:pieces4:\x01\x02\x03\x04e";
+//! let value = Value::from_bencode_borrowed(input).unwrap();
+//!
+//! if let Value::Dict(dict) = value {
+//!     assert!(matches!(dict[&b"pieces"[..]], Value::Bytes(Cow::Borrowed(_))));
+//! }
+//! ```
 
 use alloc::{
-    borrow::{Cow, ToOwned},
-    collections::BTreeMap,
+    borrow::Cow,
+    collections::{btree_map::Entry, BTreeMap},
     vec::Vec,
 };
+use core::mem;
 
 #[cfg(feature = "serde")]
-use std::{
-    convert::TryInto,
-    fmt::{self, Formatter},
-};
+use std::fmt::{self, Formatter};
 
 #[cfg(feature = "serde")]
 use serde_ as serde;
@@ -26,10 +58,20 @@ use serde::{
 };
 
 use crate::{
-    decoding::{FromBencode, Object},
+    decoding::{from_bencode::push_charged, FromBencode, FromBencodeBorrowed, Object},
     encoding::{SingleItemEncoder, ToBencode},
 };
 
+mod convert;
+mod index;
+mod integer;
+
+pub use self::{
+    convert::IntoKey,
+    index::Index,
+    integer::{Integer, ParseIntegerError},
+};
+
 /// An owned or borrowed bencoded value.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Value<'a> {
@@ -37,8 +79,8 @@ pub enum Value<'a> {
     Bytes(Cow<'a, [u8]>),
     /// A dictionary mapping byte strings to values
     Dict(BTreeMap<Cow<'a, [u8]>, Value<'a>>),
-    /// A signed integer
-    Integer(i64),
+    /// A signed integer of arbitrary size
+    Integer(Integer),
     /// A list of values
     List(Vec<Value<'a>>),
 }
@@ -57,6 +99,224 @@ impl<'a> Value<'a> {
             Value::List(list) => Value::List(list.into_iter().map(Value::into_owned).collect()),
         }
     }
+
+    /// Look up a nested value by a pointer like `/info/files/0/length`. Each segment of
+    /// the pointer selects a key of a dictionary or an index of a list; `~0`, `~1` and
+    /// `~xHH` stand for `~`, `/` and the byte with the hexadecimal value `HH` in keys.
+    /// The empty pointer refers to the value itself.
+    ///
+    /// Returns `None` if the value doesn't exist or the pointer is malformed.
+    ///
+    /// ```
+    /// use bendy::{decoding::FromBencode, value::Value};
+    ///
+    /// let torrent = Value::from_bencode(b"d4:infod5:filesld6:lengthi5eeeee").unwrap();
+    ///
+    /// let length = torrent.pointer("/info/files/0/length").unwrap();
+    /// assert_eq!(length, &Value::Integer(5.into()));
+    /// assert!(torrent.pointer("/info/files/1").is_none());
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value<'a>> {
+        let mut value = self;
+        for segment in crate::pointer::segments(pointer)? {
+            value = match value {
+                Value::Dict(dict) => dict.get(&*segment.key())?,
+                Value::List(list) => list.get(segment.index()?)?,
+                Value::Bytes(_) | Value::Integer(_) => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Like [`Value::pointer`], but returns a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value<'a>> {
+        let mut value = self;
+        for segment in crate::pointer::segments(pointer)? {
+            value = match value {
+                Value::Dict(dict) => dict.get_mut(&*segment.key())?,
+                Value::List(list) => list.get_mut(segment.index()?)?,
+                Value::Bytes(_) | Value::Integer(_) => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Whether this is a byte string
+    pub fn is_bytes(&self) -> bool {
+        self.as_bytes().is_some()
+    }
+
+    /// Whether this is an integer
+    pub fn is_integer(&self) -> bool {
+        self.as_integer().is_some()
+    }
+
+    /// Whether this is a list
+    pub fn is_list(&self) -> bool {
+        self.as_list().is_some()
+    }
+
+    /// Whether this is a dictionary
+    pub fn is_dict(&self) -> bool {
+        self.as_dict().is_some()
+    }
+
+    /// The content of a byte string, or `None` for other values
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// The content of a byte string that is valid UTF-8, or `None` for other values
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes()
+            .and_then(|bytes| core::str::from_utf8(bytes).ok())
+    }
+
+    /// The integer, or `None` for other values
+    pub fn as_integer(&self) -> Option<&Integer> {
+        match self {
+            Value::Integer(integer) => Some(integer),
+            _ => None,
+        }
+    }
+
+    /// The integer if it fits into an `i64`, or `None` otherwise
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_integer().and_then(Integer::to_i64)
+    }
+
+    /// The integer if it fits into a `u64`, or `None` otherwise
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_integer().and_then(Integer::to_u64)
+    }
+
+    /// The items of a list, or `None` for other values
+    pub fn as_list(&self) -> Option<&Vec<Value<'a>>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// The mutable items of a list, or `None` for other values
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value<'a>>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// The entries of a dictionary, or `None` for other values
+    pub fn as_dict(&self) -> Option<&BTreeMap<Cow<'a, [u8]>, Value<'a>>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// The mutable entries of a dictionary, or `None` for other values
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<Cow<'a, [u8]>, Value<'a>>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// The value under a key of a dictionary or at an index of a list. Returns `None` if
+    /// there is no such value, or if this is neither a dictionary nor a list.
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let value: Value = vec![("name", Value::from("bendy"))].into_iter().collect();
+    /// assert_eq!(value.get("name").and_then(Value::as_str), Some("bendy"));
+    /// assert_eq!(value.get(b"version"), None);
+    /// assert_eq!(value.get(0), None);
+    /// ```
+    pub fn get<I: Index>(&self, index: I) -> Option<&Value<'a>> {
+        index.index_into(self)
+    }
+
+    /// Like [`Value::get`], but returns a mutable reference.
+    pub fn get_mut<I: Index>(&mut self, index: I) -> Option<&mut Value<'a>> {
+        index.index_into_mut(self)
+    }
+
+    /// Insert `value` under `key` into a dictionary, returning the value that was
+    /// stored under `key` before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not a dictionary.
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let mut value = Value::Dict(Default::default());
+    /// value.insert("length", 42);
+    /// value.insert(b"pieces", vec![Value::from(&b"\x01\x02"[..])]);
+    ///
+    /// assert_eq!(value["length"].as_u64(), Some(42));
+    /// assert_eq!(value.insert("length", 7), Some(Value::from(42)));
+    /// ```
+    pub fn insert(
+        &mut self,
+        key: impl IntoKey<'a>,
+        value: impl Into<Value<'a>>,
+    ) -> Option<Value<'a>> {
+        self.dict_or_panic("insert")
+            .insert(key.into_key(), value.into())
+    }
+
+    /// Append `value` to a list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not a list.
+    pub fn push(&mut self, value: impl Into<Value<'a>>) {
+        match self {
+            Value::List(list) => list.push(value.into()),
+            _ => panic!("push called on a Value that is not a list"),
+        }
+    }
+
+    /// Remove the value under a key of a dictionary or at an index of a list, shifting
+    /// the following items of a list to the front. Returns `None` if there is no such
+    /// value, or if this is neither a dictionary nor a list.
+    pub fn remove<I: Index>(&mut self, index: I) -> Option<Value<'a>> {
+        index.remove_from(self)
+    }
+
+    /// The entry for `key` in a dictionary, for in-place manipulation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not a dictionary.
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let mut value = Value::Dict(Default::default());
+    /// for tracker in &["udp://a", "udp://b"] {
+    ///     let list = value.entry("announce-list").or_insert_with(|| Value::List(Vec::new()));
+    ///     list.push(*tracker);
+    /// }
+    ///
+    /// assert_eq!(value["announce-list"][1].as_str(), Some("udp://b"));
+    /// ```
+    pub fn entry(&mut self, key: impl IntoKey<'a>) -> Entry<'_, Cow<'a, [u8]>, Value<'a>> {
+        self.dict_or_panic("entry").entry(key.into_key())
+    }
+
+    fn dict_or_panic(&mut self, method: &str) -> &mut BTreeMap<Cow<'a, [u8]>, Value<'a>> {
+        match self {
+            Value::Dict(dict) => dict,
+            _ => panic!("{} called on a Value that is not a dictionary", method),
+        }
+    }
 }
 
 impl<'a> ToBencode for Value<'a> {
@@ -76,25 +336,55 @@ impl<'a> ToBencode for Value<'a> {
 
 impl<'a> FromBencode for Value<'a> {
     const EXPECTED_RECURSION_DEPTH: usize = <Self as ToBencode>::MAX_DEPTH;
-    
+
     fn decode_bencode_object(object: Object) -> Result<Self, crate::decoding::Error> {
+        Value::decode_with(object, |bytes| Cow::Owned(bytes.to_vec()))
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for Value<'ser> {
+    const EXPECTED_RECURSION_DEPTH: usize = <Self as ToBencode>::MAX_DEPTH;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, crate::decoding::Error> {
+        Value::decode_with(object, Cow::Borrowed)
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Decode `object`, turning its keys and byte strings into `Cow`s with `bytes`. Keys
+    /// that are copied into a `Cow::Owned` are charged against the allocation budget.
+    /// Shared by the owned and the borrowed implementations.
+    fn decode_with<'ser>(
+        object: Object<'_, 'ser>,
+        bytes: fn(&'ser [u8]) -> Cow<'a, [u8]>,
+    ) -> Result<Self, crate::decoding::Error> {
         match object {
-            Object::Bytes(bytes) => Ok(Value::Bytes(Cow::Owned(bytes.to_owned()))),
+            Object::Bytes(content) => Ok(Value::Bytes(bytes(content))),
             Object::Dict(mut decoder) => {
                 let mut dict = BTreeMap::new();
-                while let Some((key, value)) = decoder.next_pair()? {
-                    dict.insert(
-                        Cow::Owned(key.to_owned()),
-                        Value::decode_bencode_object(value)?,
-                    );
+                loop {
+                    let (key, value) = match decoder.next_pair()? {
+                        Some((key, value)) => (bytes(key), Value::decode_with(value, bytes)?),
+                        None => break,
+                    };
+                    let copied = match &key {
+                        Cow::Owned(key) => key.len(),
+                        Cow::Borrowed(_) => 0,
+                    };
+                    decoder.charge_allocation(copied + mem::size_of::<(Cow<[u8]>, Value)>())?;
+                    dict.insert(key, value);
                 }
                 Ok(Value::Dict(dict))
             },
             Object::Integer(text) => Ok(Value::Integer(text.parse()?)),
             Object::List(mut decoder) => {
                 let mut list = Vec::new();
-                while let Some(object) = decoder.next_object()? {
-                    list.push(Value::decode_bencode_object(object)?);
+                loop {
+                    let value = match decoder.next_object()? {
+                        Some(object) => Value::decode_with(object, bytes)?,
+                        None => break,
+                    };
+                    push_charged(&mut decoder, &mut list, value)?;
                 }
                 Ok(Value::List(list))
             },
@@ -115,7 +405,22 @@ mod serde_impls {
         {
             match self {
                 Value::Bytes(string) => serializer.serialize_bytes(string),
-                Value::Integer(int) => serializer.serialize_i64(*int),
+                Value::Integer(int) => {
+                    if let Some(int) = int.to_i64() {
+                        serializer.serialize_i64(int)
+                    } else if let Some(int) = int.to_u64() {
+                        serializer.serialize_u64(int)
+                    } else if let Some(int) = int.to_i128() {
+                        serializer.serialize_i128(int)
+                    } else if let Some(int) = int.to_u128() {
+                        serializer.serialize_u128(int)
+                    } else {
+                        Err(serde::ser::Error::custom(format_args!(
+                            "integer {} is too large to serialize",
+                            int
+                        )))
+                    }
+                },
                 Value::List(list) => {
                     let mut seed = serializer.serialize_seq(Some(list.len()))?;
                     for value in list {
@@ -155,11 +460,19 @@ mod serde_impls {
         }
 
         fn visit_i64<E>(self, value: i64) -> Result<Value<'a>, E> {
-            Ok(Value::Integer(value))
+            Ok(Value::Integer(value.into()))
         }
 
         fn visit_u64<E>(self, value: u64) -> Result<Value<'a>, E> {
-            Ok(Value::Integer(value.try_into().unwrap()))
+            Ok(Value::Integer(value.into()))
+        }
+
+        fn visit_i128<E>(self, value: i128) -> Result<Value<'a>, E> {
+            Ok(Value::Integer(value.into()))
+        }
+
+        fn visit_u128<E>(self, value: u128) -> Result<Value<'a>, E> {
+            Ok(Value::Integer(value.into()))
         }
 
         fn visit_borrowed_bytes<E>(self, value: &'a [u8]) -> Result<Value<'a>, E>
@@ -242,6 +555,17 @@ mod tests {
 
         assert_eq!(decoded, value);
 
+        let borrowed = match Value::from_bencode_borrowed(&encoded) {
+            Ok(borrowed) => borrowed,
+            Err(err) => panic!(
+                "Failed to decode borrowed value from `{}`: {}",
+                String::from_utf8_lossy(&encoded),
+                err,
+            ),
+        };
+
+        assert_eq!(borrowed, value);
+
         #[cfg(feature = "serde")]
         {
             let deserialized = match crate::serde::de::from_bytes::<Value>(expected) {
@@ -286,15 +610,25 @@ mod tests {
         case(Value::Dict(BTreeMap::new()), "de");
 
         let mut dict = BTreeMap::new();
-        dict.insert(Cow::Borrowed("foo".as_bytes()), Value::Integer(1));
-        dict.insert(Cow::Borrowed("bar".as_bytes()), Value::Integer(2));
+        dict.insert(Cow::Borrowed("foo".as_bytes()), Value::Integer(1.into()));
+        dict.insert(Cow::Borrowed("bar".as_bytes()), Value::Integer(2.into()));
         case(Value::Dict(dict), "d3:bari2e3:fooi1ee");
     }
 
     #[test]
     fn integer() {
-        case(Value::Integer(0), "i0e");
-        case(Value::Integer(-1), "i-1e");
+        case(Value::Integer(0.into()), "i0e");
+        case(Value::Integer((-1).into()), "i-1e");
+        case(Value::Integer(u64::MAX.into()), "i18446744073709551615e");
+    }
+
+    #[test]
+    fn large_integer() {
+        let input = b"i-123456789012345678901234567890123456789012e";
+        let value = Value::from_bencode(input).unwrap();
+
+        assert_eq!(value.to_bencode().unwrap(), &input[..]);
+        assert_eq!(Value::from_bencode_borrowed(input).unwrap(), value);
     }
 
     #[test]
@@ -302,10 +636,141 @@ mod tests {
         case(Value::List(Vec::new()), "le");
         case(
             Value::List(vec![
-                Value::Integer(0),
+                Value::Integer(0.into()),
                 Value::Bytes(Cow::Borrowed(&[1, 2, 3])),
             ]),
             b"li0e3:\x01\x02\x03e",
         );
     }
+
+    #[test]
+    fn borrowed_values_should_not_copy() {
+        let input = b"d6:lengthi42e6:piecesli1e20:aaaaaaaaaaaaaaaaaaaaee";
+        let value = Value::from_bencode_borrowed(input).unwrap();
+
+        let dict = match value {
+            Value::Dict(dict) => dict,
+            _ => panic!("Expected a dict, got `{:?}`", value),
+        };
+        for key in dict.keys() {
+            assert!(matches!(key, Cow::Borrowed(_)));
+        }
+
+        let pieces = match &dict[&b"pieces"[..]] {
+            Value::List(list) => &list[1],
+            value => panic!("Expected a list, got `{:?}`", value),
+        };
+        match pieces {
+            Value::Bytes(Cow::Borrowed(bytes)) => {
+                assert_eq!(bytes.as_ptr(), input[28..].as_ptr())
+            },
+            value => panic!("Expected borrowed bytes, got `{:?}`", value),
+        }
+    }
+
+    #[test]
+    fn decoded_strings_should_be_charged_against_the_allocation_budget() {
+        let limits = crate::decoding::DecodeLimits::new()
+            .with_max_allocation(3 + 4 * mem::size_of::<Value>());
+        let mut input = b"l100000:".to_vec();
+        input.resize(input.len() + 100_000, b'a');
+        input.push(b'e');
+
+        assert!(Value::from_bencode_with_limits(&input, limits).is_err());
+        assert!(Value::from_bencode_with_limits(b"l3:fooe", limits).is_ok());
+
+        // Borrowed strings aren't copied, so they aren't charged either
+        assert!(Value::from_bencode_borrowed_with_limits(&input, limits).is_ok());
+    }
+
+    #[test]
+    fn pointers_should_find_nested_values() {
+        let mut value = Value::from_bencode(b"d4:infod5:filesld6:lengthi5eee3:~/xi1eee").unwrap();
+
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(
+            value.pointer("/info/files/0/length"),
+            Some(&Value::Integer(5.into()))
+        );
+        assert_eq!(
+            value.pointer("/info/~0~1x"),
+            Some(&Value::Integer(1.into()))
+        );
+        assert_eq!(
+            value.pointer("/info/~x7e~x2fx"),
+            Some(&Value::Integer(1.into()))
+        );
+        assert_eq!(value.pointer("/info/files/1"), None);
+        assert_eq!(value.pointer("/info/files/length"), None);
+        assert_eq!(value.pointer("/info/~"), None);
+
+        *value.pointer_mut("/info/files/0/length").unwrap() = Value::Integer(6.into());
+        assert_eq!(
+            value.to_bencode().unwrap(),
+            &b"d4:infod5:filesld6:lengthi6eee3:~/xi1eee"[..]
+        );
+    }
+
+    #[test]
+    fn accessors_should_check_the_variant() {
+        let value = Value::from_bencode(b"d1:ai-1e1:b3:\xff\xfe\xfd1:cli1ee1:d2:hie").unwrap();
+
+        assert_eq!(value["a"].as_i64(), Some(-1));
+        assert_eq!(value["a"].as_u64(), None);
+        assert_eq!(value["b"].as_bytes(), Some(&b"\xff\xfe\xfd"[..]));
+        assert_eq!(value["b"].as_str(), None);
+        assert_eq!(value["d"].as_str(), Some("hi"));
+        assert_eq!(value["c"][0].as_integer(), Some(&Integer::from(1)));
+        assert!(value["c"].is_list() && value.is_dict());
+        assert!(value["d"].is_bytes() && value["a"].is_integer());
+        assert_eq!(value.get("e"), None);
+        assert_eq!(value["c"].get(1), None);
+        assert_eq!(value["d"].get(0), None);
+    }
+
+    #[test]
+    fn dicts_and_lists_should_be_editable() {
+        let mut value = Value::from(BTreeMap::<String, Value>::new());
+        value.insert("b", 1);
+        value.insert(b"a".to_vec(), "x");
+        value
+            .entry("c")
+            .or_insert_with(|| Value::List(Vec::new()))
+            .push(2u8);
+        value["c"].push(Value::from(&b"y"[..]));
+        *value.get_mut("b").unwrap() = Value::from(3);
+
+        assert_eq!(
+            value.to_bencode().unwrap(),
+            &b"d1:a1:x1:bi3e1:cli2e1:yee"[..]
+        );
+
+        assert_eq!(value["c"].remove(0), Some(Value::from(2)));
+        assert_eq!(value["c"].remove(1), None);
+        assert_eq!(value.remove("a"), Some(Value::from("x")));
+        assert_eq!(value.remove("a"), None);
+        assert_eq!(value.to_bencode().unwrap(), &b"d1:bi3e1:cl1:yee"[..]);
+    }
+
+    #[test]
+    fn values_should_be_collected() {
+        let list: Value = (1..=2).map(Value::from).collect();
+        assert_eq!(list, Value::List(vec![1.into(), 2.into()]));
+
+        let dict: Value = vec![("b", 2), ("a", 1)].into_iter().collect();
+        assert_eq!(dict.to_bencode().unwrap(), &b"d1:ai1e1:bi2ee"[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no value at key `missing`")]
+    fn indexing_missing_keys_should_panic() {
+        let value = Value::Dict(BTreeMap::new());
+        let _ = &value["missing"];
+    }
+
+    #[test]
+    #[should_panic(expected = "insert called on a Value that is not a dictionary")]
+    fn inserting_into_lists_should_panic() {
+        Value::List(Vec::new()).insert("key", 1);
+    }
 }
@@ -0,0 +1,167 @@
+use alloc::{borrow::Cow, collections::BTreeMap};
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+use core::iter::FromIterator;
+
+#[cfg(feature = "std")]
+use std::{collections::HashMap, hash::BuildHasher};
+
+use crate::value::{Integer, Value};
+
+/// Conversion into the key of a dictionary [`Value`]
+///
+/// Implemented for strings and byte strings, so that keys can be given as `"name"`,
+/// `b"name"` or owned equivalents.
+pub trait IntoKey<'a> {
+    /// Convert into a key
+    fn into_key(self) -> Cow<'a, [u8]>;
+}
+
+impl<'a> IntoKey<'a> for Cow<'a, [u8]> {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        self
+    }
+}
+
+impl<'a> IntoKey<'a> for &'a [u8] {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a, const N: usize> IntoKey<'a> for &'a [u8; N] {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        Cow::Borrowed(&self[..])
+    }
+}
+
+impl<'a> IntoKey<'a> for Vec<u8> {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        Cow::Owned(self)
+    }
+}
+
+impl<'a> IntoKey<'a> for &'a str {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+impl<'a> IntoKey<'a> for String {
+    fn into_key(self) -> Cow<'a, [u8]> {
+        Cow::Owned(self.into_bytes())
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($type:ty)*) => {$(
+        impl<'a> From<$type> for Value<'a> {
+            fn from(value: $type) -> Self {
+                Value::Integer(Integer::from(value))
+            }
+        }
+    )*}
+}
+
+impl_from_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+impl<'a> From<Integer> for Value<'a> {
+    fn from(value: Integer) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Self {
+        Value::Bytes(Cow::Borrowed(value.as_bytes()))
+    }
+}
+
+impl<'a> From<String> for Value<'a> {
+    fn from(value: String) -> Self {
+        Value::Bytes(Cow::Owned(value.into_bytes()))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Value::Bytes(Cow::Borrowed(value))
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for Value<'a> {
+    fn from(value: &'a [u8; N]) -> Self {
+        Value::Bytes(Cow::Borrowed(&value[..]))
+    }
+}
+
+impl<'a> From<Vec<u8>> for Value<'a> {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Bytes(Cow::Owned(value))
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Value<'a> {
+    fn from(value: Cow<'a, [u8]>) -> Self {
+        Value::Bytes(value)
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Value<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        match value {
+            Cow::Borrowed(value) => Value::from(value),
+            Cow::Owned(value) => Value::from(value),
+        }
+    }
+}
+
+impl<'a> From<Vec<Value<'a>>> for Value<'a> {
+    fn from(value: Vec<Value<'a>>) -> Self {
+        Value::List(value)
+    }
+}
+
+impl<'a, K, V> From<BTreeMap<K, V>> for Value<'a>
+where
+    K: IntoKey<'a>,
+    V: Into<Value<'a>>,
+{
+    fn from(value: BTreeMap<K, V>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V, S> From<HashMap<K, V, S>> for Value<'a>
+where
+    K: IntoKey<'a>,
+    V: Into<Value<'a>>,
+    S: BuildHasher,
+{
+    fn from(value: HashMap<K, V, S>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+/// Collect values into a list
+impl<'a> FromIterator<Value<'a>> for Value<'a> {
+    fn from_iter<I: IntoIterator<Item = Value<'a>>>(iter: I) -> Self {
+        Value::List(iter.into_iter().collect())
+    }
+}
+
+/// Collect pairs into a dictionary. Later pairs replace earlier ones with the same key.
+impl<'a, K, V> FromIterator<(K, V)> for Value<'a>
+where
+    K: IntoKey<'a>,
+    V: Into<Value<'a>>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Value::Dict(
+            iter.into_iter()
+                .map(|(key, value)| (key.into_key(), value.into()))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+use core::ops;
+
+use crate::value::Value;
+
+/// A key of a dictionary or an index of a list, used by [`Value::get`],
+/// [`Value::get_mut`], [`Value::remove`] and to index values with `[]`.
+///
+/// Implemented for `usize` to index lists, and for strings and byte strings to index
+/// dictionaries. This trait is sealed and can't be implemented outside of bendy.
+pub trait Index: private::Sealed {
+    #[doc(hidden)]
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>>;
+
+    #[doc(hidden)]
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>>;
+
+    #[doc(hidden)]
+    fn remove_from<'a>(&self, value: &mut Value<'a>) -> Option<Value<'a>>;
+
+    #[doc(hidden)]
+    fn describe(&self) -> String;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+impl Index for usize {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        match value {
+            Value::List(list) => list.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        match value {
+            Value::List(list) => list.get_mut(*self),
+            _ => None,
+        }
+    }
+
+    fn remove_from<'a>(&self, value: &mut Value<'a>) -> Option<Value<'a>> {
+        match value {
+            Value::List(list) if *self < list.len() => Some(list.remove(*self)),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("index {}", self)
+    }
+}
+
+impl Index for [u8] {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        match value {
+            Value::Dict(dict) => dict.get(self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        match value {
+            Value::Dict(dict) => dict.get_mut(self),
+            _ => None,
+        }
+    }
+
+    fn remove_from<'a>(&self, value: &mut Value<'a>) -> Option<Value<'a>> {
+        match value {
+            Value::Dict(dict) => dict.remove(self),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("key `{}`", String::from_utf8_lossy(self))
+    }
+}
+
+/// Forward to the implementation for `[u8]`
+macro_rules! impl_index_for_bytes {
+    ($([$($generic:tt)*] $type:ty),*) => {$(
+        impl<$($generic)*> Index for $type {
+            fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+                AsRef::<[u8]>::as_ref(self).index_into(value)
+            }
+
+            fn index_into_mut<'v, 'a>(
+                &self,
+                value: &'v mut Value<'a>,
+            ) -> Option<&'v mut Value<'a>> {
+                AsRef::<[u8]>::as_ref(self).index_into_mut(value)
+            }
+
+            fn remove_from<'a>(&self, value: &mut Value<'a>) -> Option<Value<'a>> {
+                AsRef::<[u8]>::as_ref(self).remove_from(value)
+            }
+
+            fn describe(&self) -> String {
+                AsRef::<[u8]>::as_ref(self).describe()
+            }
+        }
+
+        impl<$($generic)*> private::Sealed for $type {}
+    )*}
+}
+
+impl_index_for_bytes!([] str, [] String, [] Vec<u8>, [const N: usize] [u8; N]);
+
+impl<T: Index + ?Sized> Index for &T {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        (**self).index_into_mut(value)
+    }
+
+    fn remove_from<'a>(&self, value: &mut Value<'a>) -> Option<Value<'a>> {
+        (**self).remove_from(value)
+    }
+
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+}
+
+impl private::Sealed for usize {}
+impl private::Sealed for [u8] {}
+impl<T: private::Sealed + ?Sized> private::Sealed for &T {}
+
+/// Look up a value in a dictionary or list.
+///
+/// # Panics
+///
+/// Panics if there is no value for `index`, like indexing a `Vec` or `BTreeMap` does.
+/// Use [`Value::get`] to handle missing values.
+impl<'a, I: Index> ops::Index<I> for Value<'a> {
+    type Output = Value<'a>;
+
+    fn index(&self, index: I) -> &Value<'a> {
+        match index.index_into(self) {
+            Some(value) => value,
+            None => panic!("no value at {}", index.describe()),
+        }
+    }
+}
+
+/// Look up a value in a dictionary or list.
+///
+/// # Panics
+///
+/// Panics if there is no value for `index`. Use [`Value::insert`] to add values to a
+/// dictionary.
+impl<'a, I: Index> ops::IndexMut<I> for Value<'a> {
+    fn index_mut(&mut self, index: I) -> &mut Value<'a> {
+        match index.index_into_mut(self) {
+            Some(value) => value,
+            None => panic!("no value at {}", index.describe()),
+        }
+    }
+}
@@ -0,0 +1,378 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+
+use core::{
+    cmp::Ordering,
+    fmt::{self, Debug, Display, Formatter},
+    str::FromStr,
+};
+
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+
+use crate::{
+    decoding::{self, FromBencode, FromBencodeBorrowed, Object},
+    encoding::{self, PrintableInteger, SingleItemEncoder, ToBencode},
+};
+
+/// A bencode integer of arbitrary size
+///
+/// Bencode doesn't restrict the size of integers, so this type keeps every integer
+/// exactly as it was encoded. Integers that fit into an `i128` are stored as such, all
+/// others as their canonical decimal representation. The checked conversions like
+/// [`Integer::to_i64`] return `None` if the value doesn't fit into the target type.
+///
+/// With the `bigint` feature enabled, integers also convert from and into
+/// `num_bigint::BigInt`.
+///
+/// ```
+/// use bendy::value::Integer;
+///
+/// let small = Integer::from(-7i64);
+/// assert_eq!(small.to_i8(), Some(-7));
+/// assert_eq!(small.to_u64(), None);
+///
+/// let large: Integer = "340282366920938463463374607431768211456".parse().unwrap();
+/// assert_eq!(large.to_u128(), None);
+/// assert_eq!(large.to_string(), "340282366920938463463374607431768211456");
+/// assert!(large > Integer::from(u128::MAX));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Integer(Repr);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Small(i128),
+    /// The canonical decimal representation of an integer outside the range of `i128`
+    Large(String),
+}
+
+/// The error returned when a string isn't a decimal integer
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ParseIntegerError;
+
+impl Integer {
+    /// Whether the integer is smaller than zero
+    pub fn is_negative(&self) -> bool {
+        match &self.0 {
+            Repr::Small(value) => *value < 0,
+            Repr::Large(text) => text.starts_with('-'),
+        }
+    }
+}
+
+macro_rules! impl_checked_conversions {
+    ($($name:ident $type:ty)*) => {
+        impl Integer {$(
+            /// Convert the integer into the primitive type, or return `None` if it is
+            /// out of range
+            pub fn $name(&self) -> Option<$type> {
+                use core::convert::TryFrom;
+
+                match &self.0 {
+                    Repr::Small(value) => <$type>::try_from(*value).ok(),
+                    // Negative large integers fail to parse, as they should
+                    Repr::Large(text) => text
+                        .parse::<u128>()
+                        .ok()
+                        .and_then(|value| <$type>::try_from(value).ok()),
+                }
+            }
+        )*}
+    }
+}
+
+impl_checked_conversions!(
+    to_u8 u8 to_u16 u16 to_u32 u32 to_u64 u64 to_u128 u128 to_usize usize
+    to_i8 i8 to_i16 i16 to_i32 i32 to_i64 i64 to_i128 i128 to_isize isize
+);
+
+macro_rules! impl_from_primitive {
+    ($($type:ty)*) => {$(
+        impl From<$type> for Integer {
+            fn from(value: $type) -> Self {
+                Integer(Repr::Small(i128::from(value)))
+            }
+        }
+    )*}
+}
+
+impl_from_primitive!(u8 u16 u32 u64 i8 i16 i32 i64 i128);
+
+impl From<usize> for Integer {
+    fn from(value: usize) -> Self {
+        Integer(Repr::Small(value as i128))
+    }
+}
+
+impl From<isize> for Integer {
+    fn from(value: isize) -> Self {
+        Integer(Repr::Small(value as i128))
+    }
+}
+
+impl From<u128> for Integer {
+    fn from(value: u128) -> Self {
+        if value > i128::MAX as u128 {
+            Integer(Repr::Large(value.to_string()))
+        } else {
+            Integer(Repr::Small(value as i128))
+        }
+    }
+}
+
+impl FromStr for Integer {
+    type Err = ParseIntegerError;
+
+    /// Parse a decimal integer with an optional leading minus. Leading zeros and
+    /// negative zero are accepted and normalized.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseIntegerError);
+        }
+
+        if let Ok(value) = text.parse::<i128>() {
+            return Ok(Integer(Repr::Small(value)));
+        }
+
+        let digits = digits.trim_start_matches('0');
+        let mut canonical = String::with_capacity(digits.len() + 1);
+        if negative {
+            canonical.push('-');
+        }
+        canonical.push_str(digits);
+
+        Ok(Integer(Repr::Large(canonical)))
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.0, &other.0) {
+            (Repr::Small(lhs), Repr::Small(rhs)) => lhs.cmp(rhs),
+            // Large integers lie outside the range of small ones
+            (Repr::Small(_), Repr::Large(_)) if other.is_negative() => Ordering::Greater,
+            (Repr::Small(_), Repr::Large(_)) => Ordering::Less,
+            (Repr::Large(_), Repr::Small(_)) => other.cmp(self).reverse(),
+            (Repr::Large(lhs), Repr::Large(rhs)) => match (self.is_negative(), other.is_negative())
+            {
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (negative, _) => {
+                    let magnitude = lhs.len().cmp(&rhs.len()).then_with(|| lhs.cmp(rhs));
+                    if negative {
+                        magnitude.reverse()
+                    } else {
+                        magnitude
+                    }
+                },
+            },
+        }
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Integer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.0 {
+            Repr::Small(value) => Display::fmt(value, f),
+            Repr::Large(text) => {
+                f.pad_integral(!self.is_negative(), "", text.trim_start_matches('-'))
+            },
+        }
+    }
+}
+
+impl Debug for Integer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl PrintableInteger for Integer {}
+
+impl PrintableInteger for &Integer {}
+
+impl ToBencode for Integer {
+    const MAX_DEPTH: usize = 0;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_int(self)
+    }
+}
+
+impl FromBencode for Integer {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let content = object.try_into_integer()?;
+        let number = content.parse::<Integer>()?;
+
+        Ok(number)
+    }
+}
+
+impl<'ser> FromBencodeBorrowed<'ser> for Integer {
+    const EXPECTED_RECURSION_DEPTH: usize = 0;
+
+    fn decode_borrowed_object(object: Object<'_, 'ser>) -> Result<Self, decoding::Error> {
+        Integer::decode_bencode_object(object)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Integer {
+    fn from(value: BigInt) -> Self {
+        Integer::from(&value)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<&BigInt> for Integer {
+    fn from(value: &BigInt) -> Self {
+        value
+            .to_str_radix(10)
+            .parse()
+            .expect("BigInt is formatted as a decimal integer")
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<&Integer> for BigInt {
+    fn from(value: &Integer) -> Self {
+        match &value.0 {
+            Repr::Small(value) => BigInt::from(*value),
+            Repr::Large(text) => BigInt::parse_bytes(text.as_bytes(), 10)
+                .expect("Integer is stored as a decimal integer"),
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<Integer> for BigInt {
+    fn from(value: Integer) -> Self {
+        BigInt::from(&value)
+    }
+}
+
+impl Display for ParseIntegerError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("invalid decimal integer")
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for ParseIntegerError {}
+
+#[cfg(not(feature = "std"))]
+impl From<ParseIntegerError> for decoding::Error {
+    fn from(err: ParseIntegerError) -> Self {
+        Self::malformed_content(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+
+    const LARGE: &str = "-170141183460469231731687303715884105729";
+
+    #[test]
+    fn parsing_should_normalize() {
+        assert_eq!("007".parse::<Integer>().unwrap(), Integer::from(7u8));
+        assert_eq!("-0".parse::<Integer>().unwrap(), Integer::from(0u8));
+        assert_eq!(
+            "-00170141183460469231731687303715884105729"
+                .parse::<Integer>()
+                .unwrap()
+                .to_string(),
+            LARGE
+        );
+
+        assert!("".parse::<Integer>().is_err());
+        assert!("-".parse::<Integer>().is_err());
+        assert!("+1".parse::<Integer>().is_err());
+        assert!("1e3".parse::<Integer>().is_err());
+    }
+
+    #[test]
+    fn conversions_should_be_checked() {
+        let max = Integer::from(u128::MAX);
+        assert_eq!(max.to_u128(), Some(u128::MAX));
+        assert_eq!(max.to_i128(), None);
+        assert_eq!(max.to_u64(), None);
+
+        let small = Integer::from(-1i8);
+        assert_eq!(small.to_i128(), Some(-1));
+        assert_eq!(small.to_usize(), None);
+
+        let large = LARGE.parse::<Integer>().unwrap();
+        assert!(large.is_negative());
+        assert_eq!(large.to_i128(), None);
+        assert_eq!(large.to_u128(), None);
+    }
+
+    #[test]
+    fn integers_should_be_ordered_numerically() {
+        let mut integers = [
+            Integer::from(u128::MAX),
+            Integer::from(0u8),
+            LARGE.parse().unwrap(),
+            "-1000000000000000000000000000000000000000000"
+                .parse()
+                .unwrap(),
+            Integer::from(i128::MIN),
+            "340282366920938463463374607431768211456".parse().unwrap(),
+        ];
+        integers.sort();
+
+        let sorted: Vec<_> = integers.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "-1000000000000000000000000000000000000000000",
+                LARGE,
+                "-170141183460469231731687303715884105728",
+                "0",
+                "340282366920938463463374607431768211455",
+                "340282366920938463463374607431768211456",
+            ]
+        );
+    }
+
+    #[test]
+    fn large_integers_should_round_trip() {
+        let input = b"i123456789012345678901234567890123456789012e";
+        let decoded = Integer::from_bencode(input).unwrap();
+
+        assert_eq!(decoded.to_bencode().unwrap(), &input[..]);
+        assert!(Integer::from_bencode(b"i-0e").is_err());
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_conversions_should_be_lossless() {
+        let large = LARGE.parse::<Integer>().unwrap();
+        let bigint = BigInt::from(&large);
+
+        assert_eq!(bigint.to_string(), LARGE);
+        assert_eq!(Integer::from(bigint), large);
+        assert_eq!(BigInt::from(Integer::from(5u8)), BigInt::from(5));
+    }
+}
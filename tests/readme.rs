@@ -363,3 +363,37 @@ mod decoding_6 {
         Ok(())
     }
 }
+
+#[cfg(feature = "derive")]
+mod derive_1 {
+    use bendy::{
+        decoding::{Error, FromBencode},
+        encoding::ToBencode,
+    };
+
+    #[derive(ToBencode, FromBencode, Debug, Eq, PartialEq)]
+    struct Peer {
+        #[bencode(rename = "peer id", as_string)]
+        id: Vec<u8>,
+        ip: String,
+        port: u16,
+    }
+
+    #[test]
+    fn derive_peer() -> Result<(), Error> {
+        let peer = Peer {
+            id: b"-BD0300-".to_vec(),
+            ip: "127.0.0.1".to_owned(),
+            port: 6881,
+        };
+
+        let encoded = peer.to_bencode().expect("encoding a peer never fails");
+        assert_eq!(
+            &b"d2:ip9:127.0.0.17:peer id8:-BD0300-4:porti6881ee"[..],
+            &encoded[..]
+        );
+        assert_eq!(Peer::from_bencode(&encoded)?, peer);
+
+        Ok(())
+    }
+}